@@ -15,13 +15,16 @@ import kotlinx.coroutines.sync.withLock
 import kotlinx.datetime.Instant
 import uniffi.client.fileDescriptorChangeOpFromI32
 import uniffi.client.jniMethodNameFromI32
+import uniffi.shared.BackpressurePolicy
 import uniffi.shared.Cmd
 import uniffi.shared.EventData
 import uniffi.shared.FileDescriptorOp
 import uniffi.shared.Filter
+import uniffi.shared.FilterCombination
 import uniffi.shared.JniMethodName
 import uniffi.shared.LogEventData
 import uniffi.shared.MissingBehavior
+import uniffi.shared.StreamOptions
 import uniffi.shared.UInt32Filter
 
 private fun uniffi.shared.Process.into() =
@@ -40,6 +43,7 @@ private fun uniffi.shared.Process.into() =
 private fun uniffi.shared.Event.into() =
     when (val d = eventData) {
         is EventData.TimeSeries -> null
+        is EventData.StreamStatus -> null
         is EventData.Log ->
             run {
                 val context = d.v1.context ?: return null
@@ -117,6 +121,8 @@ private fun uniffi.shared.Event.into() =
                             fp = data.v1.filePath,
                             bytesWritten = data.v1.bytesWritten,
                         )
+                    is LogEventData.Process -> null
+                    is LogEventData.Crash -> null
                 }
             }
 
@@ -166,12 +172,16 @@ private fun uniffi.shared.Configuration.into() =
 
 private fun Configuration.into() =
     uniffi.shared.Configuration(
-        writeConfig = vfsWrite?.let { uniffi.shared.WriteConfig(it.pids.toPidFilter()) },
+        writeConfig =
+            vfsWrite?.let { uniffi.shared.WriteConfig(it.pids.toPidFilter(), rateLimit = null) },
         blockingConfig =
             sysSendmsg?.let {
                 uniffi.shared.BlockingConfig(
                     it.entries.keys.toPidFilter(),
                     it.entries.values.firstOrNull() ?: 32_000_000U,
+                    thresholdRules = listOf(),
+                    ignoredSyscalls = listOf(),
+                    rateLimit = null,
                 )
             },
         uprobeConfigs =
@@ -184,12 +194,28 @@ private fun Configuration.into() =
                 )
             },
         jniReferencesConfig =
-            jniReferences?.let { uniffi.shared.JniReferencesConfig(it.pids.toPidFilter()) },
-        signalConfig = sysSigquit?.let { uniffi.shared.SignalConfig(it.pids.toPidFilter()) },
+            jniReferences?.let {
+                uniffi.shared.JniReferencesConfig(it.pids.toPidFilter(), rateLimit = null)
+            },
+        signalConfig =
+            sysSigquit?.let {
+                uniffi.shared.SignalConfig(
+                    it.pids.toPidFilter(),
+                    traceGenerate = false,
+                    traceDeliver = false,
+                    allowedSignals = listOf(),
+                    deniedSignals = listOf(),
+                    rateLimit = null,
+                )
+            },
         garbageCollectConfig =
-            gc?.let { uniffi.shared.GarbageCollectConfig(it.pids.toPidFilter()) },
+            gc?.let { uniffi.shared.GarbageCollectConfig(it.pids.toPidFilter(), rateLimit = null) },
         fileDescriptorChangeConfig =
-            sysFdTracking?.let { uniffi.shared.FileDescriptorChangeConfig(it.pids.toPidFilter()) },
+            sysFdTracking?.let {
+                uniffi.shared.FileDescriptorChangeConfig(it.pids.toPidFilter(), rateLimit = null)
+            },
+        processConfig = null,
+        crashConfig = null,
     )
 
 private fun Filter?.toPidList() = this?.pidFilter?.match ?: listOf()
@@ -201,10 +227,15 @@ private fun Collection<UInt>.toPidFilter() =
                 missingBehavior = MissingBehavior.NOT_MATCH.value,
                 match = this.toList(),
                 notMatch = listOf(),
+                followChildren = false,
             ),
         commFilter = null,
         exePathFilter = null,
         cmdlineFilter = null,
+        processCommFilter = null,
+        uidFilter = null,
+        cgroupFilter = null,
+        combination = FilterCombination.ANY.value,
     )
 
 private fun uniffi.shared.Symbol.into() = Symbol(method, offset)
@@ -246,8 +277,17 @@ class RustClientFactory(val url: String) : ClientFactory {
     }
 }
 
+// The UI prefers fresh events over complete ones
+private val STREAM_OPTIONS =
+    StreamOptions(
+        backpressurePolicy = BackpressurePolicy.DROP_OLDEST.value,
+        capacity = 0u,
+        blockTimeoutMs = 0u,
+        resumeAfter = null,
+    )
+
 fun uniffi.client.Client.initStreamFlow() = flow {
-    initStream().use { stream ->
+    initStream(STREAM_OPTIONS).use { stream ->
         while (true) {
             stream.next()?.also { event -> emit(event) } ?: break
         }
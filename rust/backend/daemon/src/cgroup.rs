@@ -0,0 +1,57 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{
+    fs, io,
+    os::unix::fs::MetadataExt,
+    path::{Component, Path},
+};
+
+/// Resolves a cgroup v2 path relative to the mount point of the hierarchy,
+/// e.g. `/uid_10123` or `system.slice/ssh.service`, to its id.
+/// The id of a cgroup is the inode number of its directory.
+pub fn cgroup_id(root: impl AsRef<Path>, cgroup: &str) -> io::Result<u64> {
+    let relative = Path::new(cgroup.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cgroup path must not leave the hierarchy",
+        ));
+    }
+
+    let metadata = fs::metadata(root.as_ref().join(relative))?;
+    if !metadata.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cgroup path is not a directory",
+        ));
+    }
+
+    Ok(metadata.ino())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_directories_below_root() {
+        let root = std::env::temp_dir().join(format!("ziofa-cgroup-{}", std::process::id()));
+        let cgroup = root.join("uid_10123");
+        fs::create_dir_all(&cgroup).unwrap();
+        fs::write(root.join("cgroup.procs"), "").unwrap();
+
+        let expected = fs::metadata(&cgroup).unwrap().ino();
+        assert_eq!(cgroup_id(&root, "/uid_10123").unwrap(), expected);
+        assert_eq!(cgroup_id(&root, "uid_10123").unwrap(), expected);
+        assert!(cgroup_id(&root, "/uid_10124").is_err());
+        assert!(cgroup_id(&root, "/cgroup.procs").is_err());
+        assert!(cgroup_id(&root, "/uid_10123/../..").is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
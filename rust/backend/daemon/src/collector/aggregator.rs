@@ -97,7 +97,7 @@ impl Actor for Aggregator {
         myself: ActorRef<Self::Msg>,
         state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
-        state.timer = Some(myself.send_interval(state.timeframe, || Event { event_data: None }));
+        state.timer = Some(myself.send_interval(state.timeframe, Event::default));
         Ok(())
     }
 
@@ -120,7 +120,7 @@ impl Actor for Aggregator {
     ) -> Result<(), ActorProcessingErr> {
         match msg.event_data {
             Some(EventData::Log(event)) => {
-                let pid = event.context.unwrap().pid;
+                let pid = event.context.as_ref().unwrap().pid;
 
                 let msg_event_type = EventKind::from(&event);
 
@@ -156,7 +156,8 @@ impl Actor for Aggregator {
                 cast!(
                     state.event_actor,
                     Event {
-                        event_data: Some(EventData::TimeSeries(time_series))
+                        event_data: Some(EventData::TimeSeries(time_series)),
+                        sequence: 0,
                     }
                 )
                 .map_err(|_| ActorProcessingErr::from("Failed to send metric to event actor"))?;
@@ -0,0 +1,149 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        LazyLock,
+    },
+    time::Duration,
+};
+
+use nix::time::{clock_gettime, ClockId};
+use shared::google::protobuf::Timestamp;
+
+use crate::constants::CLOCK_CALIBRATION_INTERVAL;
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+/// The offset with the fewest nanoseconds between its reads of the wall clock is used
+const CALIBRATION_SAMPLES: usize = 5;
+
+static WALL_CLOCK: LazyLock<WallClock> = LazyLock::new(WallClock::new);
+
+/// Current `CLOCK_BOOTTIME`, the clock of the timestamps taken by the kernel
+pub fn boot_time() -> Duration {
+    Duration::from_nanos(nanos(ClockId::CLOCK_BOOTTIME) as u64)
+}
+
+/// Converts a timestamp of the kernel, taken with `CLOCK_BOOTTIME`, to wall-clock time
+pub fn boot_time_to_timestamp(boot_nanos: u64) -> Timestamp {
+    WALL_CLOCK.timestamp(boot_nanos)
+}
+
+/// `CLOCK_BOOTTIME` keeps counting during suspend, so its offset to `CLOCK_REALTIME` only
+/// changes when the wall clock is set, e.g. by NTP. It is measured again periodically.
+struct WallClock {
+    /// `CLOCK_REALTIME - CLOCK_BOOTTIME` in nanoseconds
+    offset: AtomicI64,
+    /// `CLOCK_BOOTTIME` of the last calibration in nanoseconds
+    calibrated_at: AtomicU64,
+}
+
+impl WallClock {
+    fn new() -> Self {
+        let clock = Self {
+            offset: AtomicI64::new(0),
+            calibrated_at: AtomicU64::new(0),
+        };
+        clock.calibrate();
+        clock
+    }
+
+    fn calibrate(&self) {
+        let (offset, boot_nanos) = measure_offset();
+        self.offset.store(offset, Ordering::Relaxed);
+        self.calibrated_at.store(boot_nanos, Ordering::Relaxed);
+    }
+
+    fn timestamp(&self, boot_nanos: u64) -> Timestamp {
+        let since_calibration =
+            boot_nanos.saturating_sub(self.calibrated_at.load(Ordering::Relaxed));
+        if since_calibration >= CLOCK_CALIBRATION_INTERVAL.as_nanos() as u64 {
+            self.calibrate();
+        }
+
+        to_timestamp(boot_nanos as i64 + self.offset.load(Ordering::Relaxed))
+    }
+}
+
+fn nanos(clock: ClockId) -> i64 {
+    let time = clock_gettime(clock).expect("clock is supported by linux");
+    time.tv_sec() * NANOS_PER_SEC + time.tv_nsec()
+}
+
+/// Reads the boot time between two reads of the wall clock and assumes it was taken
+/// in the middle, returns the offset and the boot time of the closest read.
+fn measure_offset() -> (i64, u64) {
+    (0..CALIBRATION_SAMPLES)
+        .map(|_| {
+            let before = nanos(ClockId::CLOCK_REALTIME);
+            let boot = nanos(ClockId::CLOCK_BOOTTIME);
+            let after = nanos(ClockId::CLOCK_REALTIME);
+            let gap = after - before;
+            (gap, before + gap / 2 - boot, boot as u64)
+        })
+        .min_by_key(|(gap, _, _)| *gap)
+        .map(|(_, offset, boot)| (offset, boot))
+        .expect("at least one sample is taken")
+}
+
+fn to_timestamp(nanos: i64) -> Timestamp {
+    Timestamp {
+        seconds: nanos.div_euclid(NANOS_PER_SEC),
+        nanos: nanos.rem_euclid(NANOS_PER_SEC) as i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    #[test]
+    fn timestamps_keep_nanoseconds() {
+        assert_eq!(
+            to_timestamp(1_700_000_000_123_456_789),
+            Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 123_456_789,
+            }
+        );
+        assert_eq!(
+            to_timestamp(-1),
+            Timestamp {
+                seconds: -1,
+                nanos: 999_999_999,
+            }
+        );
+    }
+
+    #[test]
+    fn boot_time_matches_wall_clock() {
+        let boot_nanos = nanos(ClockId::CLOCK_BOOTTIME) as u64;
+        let timestamp = boot_time_to_timestamp(boot_nanos);
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+
+        let converted = Duration::new(timestamp.seconds as u64, timestamp.nanos as u32);
+        assert!(now.abs_diff(converted) < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn recalibrates_after_the_interval() {
+        let clock = WallClock::new();
+        clock.offset.store(0, Ordering::Relaxed);
+        let calibrated_at = clock.calibrated_at.load(Ordering::Relaxed);
+
+        // Within the interval the stale offset is kept
+        let timestamp = clock.timestamp(calibrated_at);
+        assert_eq!(timestamp, to_timestamp(calibrated_at as i64));
+
+        let later = calibrated_at + CLOCK_CALIBRATION_INTERVAL.as_nanos() as u64;
+        clock.timestamp(later);
+        assert_ne!(clock.offset.load(Ordering::Relaxed), 0);
+    }
+}
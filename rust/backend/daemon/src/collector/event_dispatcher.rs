@@ -3,44 +3,362 @@
 //
 // SPDX-License-Identifier: MIT
 
-use async_broadcast::Sender;
-use ractor::Actor;
-use shared::events::Event;
-use tonic::Status;
+use std::{mem, sync::Arc, time::Duration};
+
+use ractor::{concurrency::JoinHandle, Actor, ActorProcessingErr, ActorRef};
+use shared::events::{Event, EventBatch};
+use tokio::{task::spawn_blocking, time::sleep};
+use tracing::{error, info};
+
+use super::{
+    clock,
+    flight_recorder::FlightRecorder,
+    journal::{Journal, JournalWriter},
+    record::DecodedRecord,
+    reorder::ReorderBuffer,
+    subscriptions::Subscriptions,
+    time_series_store::TimeSeriesStore,
+};
+use crate::{
+    constants::{EVENT_BATCH_SIZE, EVENT_BATCH_WINDOW, REORDER_BUFFER_MAX_EVENTS},
+    features::GlobFilters,
+};
 
 pub struct EventDispatcher;
 
+pub enum EventDispatcherMsg {
+    /// Events of a single read of a ring buffer
+    Events(Vec<DecodedRecord>),
+    /// Sends the pending batch even if it is not full and the events
+    /// whose reorder window has passed
+    Flush,
+}
+
+pub struct EventDispatcherArguments {
+    destination: Arc<Subscriptions<Event>>,
+    batch_destination: Arc<Subscriptions<EventBatch>>,
+    reorder_window: Duration,
+    flight_recorder: Option<Arc<FlightRecorder>>,
+    journal: Option<Arc<Journal>>,
+    time_series: Arc<TimeSeriesStore>,
+    globs: Arc<GlobFilters>,
+}
+
+impl EventDispatcherArguments {
+    /// Events are sent in the order they were read if the reorder window is zero
+    pub fn new(
+        destination: Arc<Subscriptions<Event>>,
+        batch_destination: Arc<Subscriptions<EventBatch>>,
+        reorder_window: Duration,
+        flight_recorder: Option<Arc<FlightRecorder>>,
+        journal: Option<Arc<Journal>>,
+        time_series: Arc<TimeSeriesStore>,
+        globs: Arc<GlobFilters>,
+    ) -> Self {
+        Self {
+            destination,
+            batch_destination,
+            reorder_window,
+            flight_recorder,
+            journal,
+            time_series,
+            globs,
+        }
+    }
+}
+
 pub struct EventDispatcherState {
-    destination: Sender<Result<Event, Status>>,
+    destination: Arc<Subscriptions<Event>>,
+    batch_destination: Arc<Subscriptions<EventBatch>>,
+    batch: Vec<Event>,
+    timer: Option<JoinHandle<()>>,
+    reorder: Option<ReorderBuffer>,
+    /// Of the latest sent event
+    sequence: u64,
+    flight_recorder: Option<Arc<FlightRecorder>>,
+    journal: Option<JournalWriter>,
+    time_series: Arc<TimeSeriesStore>,
+    /// Events failing a glob of the string filters are dropped before they get a sequence number
+    globs: Arc<GlobFilters>,
 }
 
 impl EventDispatcherState {
-    pub fn new(destination: Sender<Result<Event, Status>>) -> Self {
-        Self { destination }
+    async fn receive(&mut self, records: Vec<DecodedRecord>) {
+        match &mut self.reorder {
+            Some(reorder) => {
+                let mut released = Vec::new();
+                for record in records {
+                    reorder.push(record, &mut released);
+                }
+                self.release(released).await;
+            }
+            None => {
+                let events = records.into_iter().map(|record| record.event).collect();
+                self.send(events).await
+            }
+        }
+    }
+
+    /// Sends the held events whose reorder window has passed
+    async fn release(&mut self, mut released: Vec<Event>) {
+        let Some(reorder) = &mut self.reorder else {
+            return;
+        };
+
+        reorder.release(clock::boot_time(), &mut released);
+
+        let late_events = reorder.take_late_events();
+        if late_events > 0 {
+            self.destination.count_late_events(late_events);
+            self.batch_destination.count_late_events(late_events);
+        }
+
+        self.send(released).await;
+    }
+
+    async fn send(&mut self, events: Vec<Event>) {
+        for mut event in events {
+            if !self.globs.keeps(&event) {
+                continue;
+            }
+
+            self.sequence += 1;
+            event.sequence = self.sequence;
+            self.time_series.record(&event);
+            self.record(&event);
+
+            self.destination.publish(&event).await;
+
+            self.batch.push(event);
+            if self.batch.len() >= EVENT_BATCH_SIZE {
+                self.flush().await;
+            }
+        }
+    }
+
+    /// Dumps are written in the background to not hold up the events, after a
+    /// delay so they also cover the events following the trigger
+    fn record(&self, event: &Event) {
+        let Some(recorder) = &self.flight_recorder else {
+            return;
+        };
+        let Some(rule) = recorder.record(event) else {
+            return;
+        };
+
+        let recorder = recorder.clone();
+        tokio::spawn(async move {
+            sleep(recorder.dump_delay()).await;
+            match spawn_blocking(move || recorder.dump_triggered(rule)).await {
+                Ok(Ok((path, events))) => {
+                    info!("{rule:?} triggered a dump of {events} events to {path:?}")
+                }
+                Ok(Err(error)) => {
+                    error!("Failed to dump the flight recorder for {rule:?}: {error}")
+                }
+                Err(error) => error!("Dump of the flight recorder for {rule:?} panicked: {error}"),
+            }
+        });
+    }
+
+    async fn flush(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+
+        let batch = EventBatch {
+            events: mem::take(&mut self.batch),
+        };
+        self.batch_destination.publish(&batch).await;
+        if let Some(journal) = &self.journal {
+            journal.append(batch.events);
+        }
     }
 }
 
 impl Actor for EventDispatcher {
-    type Msg = Event;
+    type Msg = EventDispatcherMsg;
     type State = EventDispatcherState;
-    type Arguments = EventDispatcherState;
+    type Arguments = EventDispatcherArguments;
 
     async fn pre_start(
         &self,
-        _: ractor::ActorRef<Self::Msg>,
+        myself: ActorRef<Self::Msg>,
         args: Self::Arguments,
-    ) -> Result<Self::State, ractor::ActorProcessingErr> {
-        Ok(args)
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(EventDispatcherState {
+            destination: args.destination,
+            batch_destination: args.batch_destination,
+            batch: Vec::with_capacity(EVENT_BATCH_SIZE),
+            timer: Some(myself.send_interval(EVENT_BATCH_WINDOW, || EventDispatcherMsg::Flush)),
+            reorder: (!args.reorder_window.is_zero())
+                .then(|| ReorderBuffer::new(args.reorder_window, REORDER_BUFFER_MAX_EVENTS)),
+            sequence: 0,
+            flight_recorder: args.flight_recorder,
+            journal: args.journal.map(JournalWriter::spawn).transpose()?,
+            time_series: args.time_series,
+            globs: args.globs,
+        })
+    }
+
+    async fn post_stop(
+        &self,
+        _: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let Some(timer) = state.timer.take() {
+            timer.abort();
+        }
+        Ok(())
     }
 
     async fn handle(
         &self,
-        _: ractor::ActorRef<Self::Msg>,
+        _: ActorRef<Self::Msg>,
         message: Self::Msg,
         state: &mut Self::State,
-    ) -> Result<(), ractor::ActorProcessingErr> {
-        state.destination.broadcast_direct(Ok(message)).await?;
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            EventDispatcherMsg::Events(events) => state.receive(events).await,
+            EventDispatcherMsg::Flush => {
+                state.release(Vec::new()).await;
+                state.flush().await;
+            }
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use shared::events::{event::EventData, EventContext, LogEvent, StreamOptions};
+    use tokio_stream::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn batches_by_count_and_window() {
+        let subscriptions = Arc::new(Subscriptions::default());
+        let batch_subscriptions = Arc::new(Subscriptions::default());
+        let mut stream = subscriptions.subscribe(&StreamOptions::default()).unwrap();
+        let mut batch_stream = batch_subscriptions
+            .subscribe(&StreamOptions::default())
+            .unwrap();
+        let (dispatcher, handle) = Actor::spawn(
+            None,
+            EventDispatcher,
+            EventDispatcherArguments::new(
+                subscriptions,
+                batch_subscriptions,
+                Duration::ZERO,
+                None,
+                None,
+                Arc::new(TimeSeriesStore::new(&[], 0)),
+                Arc::default(),
+            ),
+        )
+        .await
+        .unwrap();
+
+        let record = DecodedRecord {
+            boot_time: Duration::ZERO,
+            event: Event::default(),
+        };
+        let records = vec![record; EVENT_BATCH_SIZE + 1];
+        dispatcher
+            .cast(EventDispatcherMsg::Events(records))
+            .unwrap();
+
+        // Streams start with their status
+        let status = batch_stream.next().await.unwrap().unwrap();
+        assert!(matches!(
+            status.events[0].event_data,
+            Some(EventData::StreamStatus(_))
+        ));
+
+        let full = batch_stream.next().await.unwrap().unwrap();
+        assert_eq!(full.events.len(), EVENT_BATCH_SIZE);
+
+        // The remaining event is sent once the time window has passed
+        let rest = batch_stream.next().await.unwrap().unwrap();
+        assert_eq!(rest.events.len(), 1);
+
+        // Single events are still sent for unbatched streams, numbered from 1
+        stream.next().await.unwrap().unwrap();
+        for sequence in 1..=EVENT_BATCH_SIZE as u64 + 1 {
+            let event = stream.next().await.unwrap().unwrap();
+            assert_eq!(event.sequence, sequence);
+        }
+
+        dispatcher.stop(None);
+        handle.await.unwrap();
+    }
+
+    fn record_at(boot_time: Duration) -> DecodedRecord {
+        DecodedRecord {
+            boot_time,
+            event: Event {
+                event_data: Some(EventData::Log(LogEvent {
+                    context: Some(EventContext {
+                        timestamp: Some(boot_time.into()),
+                        ..Default::default()
+                    }),
+                    log_event_data: None,
+                })),
+                sequence: 0,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn reorders_events_by_timestamp() {
+        let subscriptions = Arc::new(Subscriptions::default());
+        let mut stream = subscriptions.subscribe(&StreamOptions::default()).unwrap();
+        let (dispatcher, handle) = Actor::spawn(
+            None,
+            EventDispatcher,
+            EventDispatcherArguments::new(
+                subscriptions,
+                Arc::default(),
+                Duration::from_millis(10),
+                None,
+                None,
+                Arc::new(TimeSeriesStore::new(&[], 0)),
+                Arc::default(),
+            ),
+        )
+        .await
+        .unwrap();
+
+        let now = clock::boot_time();
+        let at = |millis| record_at(now + Duration::from_millis(millis));
+        dispatcher
+            .cast(EventDispatcherMsg::Events(vec![at(3), at(1), at(2)]))
+            .unwrap();
+
+        // Sequence numbers follow the order of sending
+        stream.next().await.unwrap().unwrap();
+        for millis in 1..=3 {
+            let event = stream.next().await.unwrap().unwrap();
+            assert_eq!(event.sequence, millis);
+            assert_eq!(event.event_data, at(millis).event.event_data);
+        }
+
+        // Older than the released events, it is sent anyway and reported
+        dispatcher
+            .cast(EventDispatcherMsg::Events(vec![at(0)]))
+            .unwrap();
+        let status = stream.next().await.unwrap().unwrap();
+        let Some(EventData::StreamStatus(status)) = status.event_data else {
+            panic!("expected a stream status");
+        };
+        assert_eq!(status.late_events, 1);
+        let event = stream.next().await.unwrap().unwrap();
+        assert_eq!(event.sequence, 4);
+        assert_eq!(event.event_data, at(0).event.event_data);
+
+        dispatcher.stop(None);
+        handle.await.unwrap();
+    }
+}
@@ -0,0 +1,362 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{create_dir_all, read_dir, remove_file, File},
+    io::{self, BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use prost::Message;
+use shared::events::{event::EventData, log_event::LogEventData, Event};
+
+pub struct FlightRecorderOptions {
+    /// Events older than this are dropped
+    pub window: Duration,
+    /// Upper bound of the encoded size of all kept events
+    pub max_bytes: usize,
+    /// Directory of the dumps written by triggers and on request
+    pub dump_dir: PathBuf,
+    /// Dumps kept in the directory, the oldest are removed first, 0 keeps all
+    pub max_dumps: usize,
+    /// Time between a trigger and its dump, at most half the window
+    pub dump_delay: Duration,
+    pub triggers: Vec<TriggerRule>,
+}
+
+/// File names of the dumps start with this, followed by the unix time in milliseconds
+const DUMP_PREFIX: &str = "flight-recorder-";
+
+/// Events which dump the flight recorder
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TriggerRule {
+    /// A blocking syscall which took longer than the duration
+    BlockingAbove(Duration),
+    /// A process crashed with a fatal signal
+    Crash,
+}
+
+impl TriggerRule {
+    fn matches(&self, event: &Event) -> bool {
+        let Some(EventData::Log(log)) = &event.event_data else {
+            return false;
+        };
+
+        match (self, &log.log_event_data) {
+            (TriggerRule::BlockingAbove(threshold), Some(LogEventData::Blocking(blocking))) => {
+                blocking
+                    .duration
+                    .is_some_and(|duration| Duration::from(duration) > *threshold)
+            }
+            (TriggerRule::Crash, Some(LogEventData::Crash(_))) => true,
+            _ => false,
+        }
+    }
+
+    /// Part of the file name of its dumps
+    fn name(&self) -> &'static str {
+        match self {
+            TriggerRule::BlockingAbove(_) => "blocking",
+            TriggerRule::Crash => "crash",
+        }
+    }
+}
+
+struct Recorded {
+    at: Instant,
+    size: usize,
+    /// Shared with snapshots, which copy the events without holding the lock
+    event: Arc<Event>,
+}
+
+#[derive(Default)]
+struct Recording {
+    events: VecDeque<Recorded>,
+    bytes: usize,
+    last_triggers: HashMap<TriggerRule, Instant>,
+}
+
+/// Keeps the latest events in memory, independent of any stream, so they can be
+/// dumped after an incident. Dumps are files of length-delimited `Event` messages.
+pub struct FlightRecorder {
+    options: FlightRecorderOptions,
+    recording: Mutex<Recording>,
+}
+
+impl FlightRecorder {
+    pub fn new(options: FlightRecorderOptions) -> Self {
+        Self {
+            options,
+            recording: Mutex::default(),
+        }
+    }
+
+    /// Returns the rule the event triggered, each rule triggers at most once per
+    /// window as its next dump would mostly repeat the events
+    pub fn record(&self, event: &Event) -> Option<TriggerRule> {
+        let now = Instant::now();
+        let mut recording = self.recording.lock().unwrap();
+
+        let size = event.encoded_len();
+        recording.bytes += size;
+        recording.events.push_back(Recorded {
+            at: now,
+            size,
+            event: Arc::new(event.clone()),
+        });
+        self.evict(&mut recording, now);
+
+        let rule = *self.options.triggers.iter().find(|rule| {
+            rule.matches(event)
+                && recording
+                    .last_triggers
+                    .get(rule)
+                    .is_none_or(|last| now.duration_since(*last) >= self.options.window)
+        })?;
+        recording.last_triggers.insert(rule, now);
+        Some(rule)
+    }
+
+    /// How long to wait after a trigger before dumping
+    pub fn dump_delay(&self) -> Duration {
+        self.options.dump_delay.min(self.options.window / 2)
+    }
+
+    fn evict(&self, recording: &mut Recording, now: Instant) {
+        while let Some(oldest) = recording.events.front() {
+            let expired = now.duration_since(oldest.at) > self.options.window;
+            if !expired && recording.bytes <= self.options.max_bytes {
+                break;
+            }
+
+            recording.bytes -= oldest.size;
+            recording.events.pop_front();
+        }
+    }
+
+    /// The events of the window, oldest first. Only the references are taken
+    /// under the lock, so `record` is not held up by copying the events.
+    fn recorded(&self) -> Vec<Arc<Event>> {
+        let mut recording = self.recording.lock().unwrap();
+        self.evict(&mut recording, Instant::now());
+        recording
+            .events
+            .iter()
+            .map(|recorded| recorded.event.clone())
+            .collect()
+    }
+
+    /// The events of the window, oldest first
+    pub fn snapshot(&self) -> Vec<Event> {
+        self.recorded()
+            .iter()
+            .map(|event| Event::clone(event))
+            .collect()
+    }
+
+    /// Writes the events of the window to a new file in the dump directory,
+    /// `reason` is part of its name. The oldest dumps beyond the limit are removed.
+    pub fn dump(&self, reason: &str) -> io::Result<(PathBuf, usize)> {
+        let events = self.recorded();
+
+        create_dir_all(&self.options.dump_dir)?;
+        let millis = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = self
+            .options
+            .dump_dir
+            .join(format!("{DUMP_PREFIX}{millis}-{reason}.pb"));
+        write_events(&path, &events)?;
+        self.remove_old_dumps()?;
+
+        Ok((path, events.len()))
+    }
+
+    fn remove_old_dumps(&self) -> io::Result<()> {
+        if self.options.max_dumps == 0 {
+            return Ok(());
+        }
+
+        let mut dumps = Vec::new();
+        for entry in read_dir(&self.options.dump_dir)? {
+            let path = entry?.path();
+            let is_dump = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(DUMP_PREFIX) && name.ends_with(".pb"));
+            if is_dump {
+                dumps.push(path);
+            }
+        }
+
+        // The names sort by the time of the dump
+        dumps.sort();
+        let excess = dumps.len().saturating_sub(self.options.max_dumps);
+        for path in &dumps[..excess] {
+            match remove_file(path) {
+                // Removed by a concurrent dump
+                Err(error) if error.kind() == ErrorKind::NotFound => {}
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn dump_triggered(&self, rule: TriggerRule) -> io::Result<(PathBuf, usize)> {
+        self.dump(rule.name())
+    }
+}
+
+fn write_events(path: &Path, events: &[Arc<Event>]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for event in events {
+        file.write_all(&event.encode_length_delimited_to_vec())?;
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread::sleep};
+
+    use shared::events::{BlockingEvent, CrashEvent, LogEvent};
+
+    use super::*;
+
+    fn options(window: Duration, max_bytes: usize) -> FlightRecorderOptions {
+        FlightRecorderOptions {
+            window,
+            max_bytes,
+            dump_dir: std::env::temp_dir().join(format!("ziofa-test-{}", std::process::id())),
+            max_dumps: 0,
+            dump_delay: Duration::ZERO,
+            triggers: vec![
+                TriggerRule::BlockingAbove(Duration::from_secs(1)),
+                TriggerRule::Crash,
+            ],
+        }
+    }
+
+    fn event(sequence: u64) -> Event {
+        Event {
+            sequence,
+            ..Default::default()
+        }
+    }
+
+    fn log_event(data: LogEventData) -> Event {
+        Event {
+            event_data: Some(EventData::Log(LogEvent {
+                context: None,
+                log_event_data: Some(data),
+            })),
+            sequence: 0,
+        }
+    }
+
+    fn blocking(duration: Duration) -> Event {
+        log_event(LogEventData::Blocking(BlockingEvent {
+            duration: Some(duration.into()),
+            ..Default::default()
+        }))
+    }
+
+    fn sequences(events: &[Event]) -> Vec<u64> {
+        events.iter().map(|event| event.sequence).collect()
+    }
+
+    #[test]
+    fn keeps_the_latest_bytes() {
+        let size = event(1).encoded_len();
+        let recorder = FlightRecorder::new(options(Duration::from_secs(60), 2 * size));
+
+        for sequence in 1..=3 {
+            recorder.record(&event(sequence));
+        }
+
+        assert_eq!(sequences(&recorder.snapshot()), vec![2, 3]);
+    }
+
+    #[test]
+    fn drops_events_older_than_the_window() {
+        let recorder = FlightRecorder::new(options(Duration::from_millis(20), usize::MAX));
+
+        recorder.record(&event(1));
+        sleep(Duration::from_millis(30));
+        recorder.record(&event(2));
+
+        assert_eq!(sequences(&recorder.snapshot()), vec![2]);
+    }
+
+    #[test]
+    fn each_rule_triggers_once_per_window() {
+        let recorder = FlightRecorder::new(options(Duration::from_secs(60), usize::MAX));
+        let crash = log_event(LogEventData::Crash(CrashEvent::default()));
+
+        assert_eq!(recorder.record(&blocking(Duration::from_millis(500))), None);
+        assert_eq!(
+            recorder.record(&blocking(Duration::from_secs(2))),
+            Some(TriggerRule::BlockingAbove(Duration::from_secs(1)))
+        );
+        assert_eq!(recorder.record(&blocking(Duration::from_secs(3))), None);
+        assert_eq!(recorder.record(&crash), Some(TriggerRule::Crash));
+        assert_eq!(recorder.record(&crash), None);
+    }
+
+    #[test]
+    fn dump_delay_is_at_most_half_the_window() {
+        let mut options = options(Duration::from_secs(4), usize::MAX);
+        options.dump_delay = Duration::from_secs(5);
+
+        assert_eq!(
+            FlightRecorder::new(options).dump_delay(),
+            Duration::from_secs(2)
+        );
+    }
+
+    #[test]
+    fn removes_the_oldest_dumps() {
+        let mut options = options(Duration::from_secs(60), usize::MAX);
+        options.dump_dir = options.dump_dir.with_extension("retention");
+        options.max_dumps = 2;
+        let recorder = FlightRecorder::new(options);
+
+        let mut paths = Vec::new();
+        for _ in 0..3 {
+            paths.push(recorder.dump("test").unwrap().0);
+            // Dumps of the same millisecond would share a name
+            sleep(Duration::from_millis(2));
+        }
+
+        let exists: Vec<_> = paths.iter().map(|path| path.exists()).collect();
+        assert_eq!(exists, vec![false, true, true]);
+
+        fs::remove_dir_all(paths[0].parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn dumps_length_delimited_events() {
+        let recorder = FlightRecorder::new(options(Duration::from_secs(60), usize::MAX));
+        recorder.record(&event(1));
+        recorder.record(&event(2));
+
+        let (path, count) = recorder.dump("test").unwrap();
+        assert_eq!(count, 2);
+
+        let content = fs::read(&path).unwrap();
+        let mut buffer = content.as_slice();
+        let mut dumped = Vec::new();
+        while !buffer.is_empty() {
+            dumped.push(Event::decode_length_delimited(&mut buffer).unwrap());
+        }
+        assert_eq!(sequences(&dumped), vec![1, 2]);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,499 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{
+    fs::{self, create_dir_all, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{sync_channel, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use prost::Message;
+use shared::events::{event::EventData, time_series_event::EventKind, Event, EventQuery};
+use tracing::{error, warn};
+
+use super::timestamp;
+use crate::constants::JOURNAL_WRITER_QUEUE_SIZE;
+
+const SEGMENT_EXTENSION: &str = "journal";
+
+pub struct JournalOptions {
+    pub dir: PathBuf,
+    /// Oldest segments are removed once all segments are larger than this
+    pub max_bytes: u64,
+    /// Segments whose events are all older than this are removed
+    pub max_age: Duration,
+    /// A new segment is started once the current one is this large or old
+    pub segment_size: u64,
+    pub segment_duration: Duration,
+    /// Events are written to the journal at most this long after their
+    /// timestamp, it has to cover the reorder window
+    pub max_delay: Duration,
+}
+
+struct Segment {
+    file: BufWriter<File>,
+    bytes: u64,
+    opened: Instant,
+}
+
+struct Writer {
+    segment: Option<Segment>,
+    /// Start of the latest segment, the starts are kept increasing so the
+    /// segments sort by their names
+    last_start: Duration,
+}
+
+/// Keeps events on disk across restarts of the daemon. The journal is a directory of
+/// append-only segments of length-delimited `Event` messages, each named after the
+/// time of its first event in milliseconds since the unix epoch.
+pub struct Journal {
+    options: JournalOptions,
+    writer: Mutex<Writer>,
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// All segments, oldest first
+fn segments(dir: &Path) -> io::Result<Vec<(Duration, PathBuf)>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_none_or(|extension| extension != SEGMENT_EXTENSION)
+        {
+            continue;
+        }
+        let start = path
+            .file_stem()
+            .and_then(|stem| stem.to_str()?.parse().ok())
+            .map(Duration::from_millis);
+        if let Some(start) = start {
+            segments.push((start, path));
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+/// Events of a segment, a partially written event at its end is left out
+fn read_segment(path: &Path) -> io::Result<Vec<Event>> {
+    let content = fs::read(path)?;
+    let mut buffer = content.as_slice();
+    let mut events = Vec::new();
+    while !buffer.is_empty() {
+        match Event::decode_length_delimited(&mut buffer) {
+            Ok(event) => events.push(event),
+            Err(_) => break,
+        }
+    }
+    Ok(events)
+}
+
+fn matches(query: &EventQuery, event: &Event) -> bool {
+    let Some(timestamp) = timestamp(event) else {
+        return false;
+    };
+    if query
+        .start
+        .is_some_and(|start| timestamp < Duration::from(start))
+        || query
+            .end
+            .is_some_and(|end| timestamp >= Duration::from(end))
+    {
+        return false;
+    }
+
+    if !query.kinds.is_empty() && !query.kinds.contains(&(EventKind::from(event) as i32)) {
+        return false;
+    }
+
+    let Some(EventData::Log(log)) = &event.event_data else {
+        return false;
+    };
+    query.pids.is_empty()
+        || log
+            .context
+            .as_ref()
+            .is_some_and(|context| query.pids.contains(&context.pid))
+}
+
+impl Journal {
+    /// Continues the journal in the directory, if there is one
+    pub fn open(options: JournalOptions) -> io::Result<Self> {
+        create_dir_all(&options.dir)?;
+        let last_start = segments(&options.dir)?
+            .last()
+            .map(|(start, _)| *start)
+            .unwrap_or_default();
+
+        Ok(Self {
+            options,
+            writer: Mutex::new(Writer {
+                segment: None,
+                last_start,
+            }),
+        })
+    }
+
+    /// Writes the events to the current segment and flushes it, so they can be queried
+    pub fn append(&self, events: &[Event]) -> io::Result<()> {
+        let Some(first) = events.first() else {
+            return Ok(());
+        };
+
+        let mut writer = self.writer.lock().unwrap();
+        let full = writer.segment.as_ref().is_some_and(|segment| {
+            segment.bytes >= self.options.segment_size
+                || segment.opened.elapsed() >= self.options.segment_duration
+        });
+        if full {
+            writer.segment = None;
+        }
+
+        if writer.segment.is_none() {
+            let start = timestamp(first)
+                .unwrap_or_else(now)
+                .max(writer.last_start + Duration::from_millis(1));
+            writer.segment = Some(self.create_segment(start)?);
+            writer.last_start = start;
+            self.remove_expired_segments()?;
+        }
+
+        let segment = writer.segment.as_mut().expect("was created");
+        let result = events.iter().try_for_each(|event| {
+            let record = event.encode_length_delimited_to_vec();
+            segment.bytes += record.len() as u64;
+            segment.file.write_all(&record)
+        });
+        let result = result.and_then(|_| segment.file.flush());
+
+        // Starts with a new segment instead of appending to a broken one
+        if result.is_err() {
+            writer.segment = None;
+        }
+        result
+    }
+
+    fn create_segment(&self, start: Duration) -> io::Result<Segment> {
+        let path = self
+            .options
+            .dir
+            .join(format!("{:016}.{SEGMENT_EXTENSION}", start.as_millis()));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Segment {
+            file: BufWriter::new(file),
+            bytes: 0,
+            opened: Instant::now(),
+        })
+    }
+
+    /// Removes the oldest segments, the latest one is always kept
+    fn remove_expired_segments(&self) -> io::Result<()> {
+        let segments = segments(&self.options.dir)?;
+        let sizes = segments
+            .iter()
+            .map(|(_, path)| Ok(fs::metadata(path)?.len()))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut total: u64 = sizes.iter().sum();
+        let oldest_kept = now().saturating_sub(self.options.max_age);
+
+        for (i, ((_, path), size)) in segments.iter().zip(sizes).enumerate() {
+            let Some((next_start, _)) = segments.get(i + 1) else {
+                break;
+            };
+
+            // The events of a segment are older than the start of the next one
+            let expired = *next_start + self.options.max_delay < oldest_kept;
+            if !expired && total <= self.options.max_bytes {
+                break;
+            }
+
+            fs::remove_file(path)?;
+            total -= size;
+        }
+
+        Ok(())
+    }
+
+    /// Passes the matching events in the order they were written until `send`
+    /// returns false. Events without a timestamp never match.
+    pub fn query(&self, query: &EventQuery, mut send: impl FnMut(Event) -> bool) -> io::Result<()> {
+        let segments = segments(&self.options.dir)?;
+
+        for (i, (start, path)) in segments.iter().enumerate() {
+            // Events are written shortly after their timestamp
+            let before_end = query.end.is_none_or(|end| {
+                start.saturating_sub(self.options.max_delay) < Duration::from(end)
+            });
+            let after_start = segments.get(i + 1).is_none_or(|(next_start, _)| {
+                query.start.is_none_or(|start| {
+                    *next_start + self.options.max_delay >= Duration::from(start)
+                })
+            });
+            if !before_end || !after_start {
+                continue;
+            }
+
+            let events = match read_segment(path) {
+                Ok(events) => events,
+                // Removed by the retention in the meantime
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error),
+            };
+            for event in events {
+                if matches(query, &event) && !send(event) {
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Appends the events to the journal on a thread of its own, so a slow disk
+/// does not hold up the collector. The thread ends once the writer is dropped.
+pub struct JournalWriter {
+    tx: SyncSender<Vec<Event>>,
+}
+
+impl JournalWriter {
+    pub fn spawn(journal: Arc<Journal>) -> io::Result<Self> {
+        let (tx, rx) = sync_channel::<Vec<Event>>(JOURNAL_WRITER_QUEUE_SIZE);
+        thread::Builder::new()
+            .name("journal-writer".to_owned())
+            .spawn(move || {
+                for events in rx {
+                    if let Err(error) = journal.append(&events) {
+                        error!(
+                            "Failed to write {} events to the journal: {error}",
+                            events.len()
+                        );
+                    }
+                }
+            })?;
+
+        Ok(Self { tx })
+    }
+
+    /// The events are dropped if the thread falls behind by more than the queue
+    pub fn append(&self, events: Vec<Event>) {
+        match self.tx.try_send(events) {
+            Ok(()) => {}
+            Err(TrySendError::Full(events)) => {
+                warn!("The journal fell behind, dropped {} events", events.len())
+            }
+            Err(TrySendError::Disconnected(events)) => error!(
+                "The journal writer stopped, dropped {} events",
+                events.len()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::events::{
+        log_event::LogEventData, BlockingEvent, EventContext, LogEvent, WriteEvent,
+    };
+
+    use super::*;
+
+    fn empty_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ziofa-journal-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn options(dir: &Path) -> JournalOptions {
+        JournalOptions {
+            dir: dir.to_path_buf(),
+            max_bytes: u64::MAX,
+            max_age: Duration::from_secs(3600),
+            segment_size: u64::MAX,
+            segment_duration: Duration::from_secs(3600),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+
+    fn event(seconds: u64, pid: u32, blocking: bool) -> Event {
+        let data = if blocking {
+            LogEventData::Blocking(BlockingEvent::default())
+        } else {
+            LogEventData::Write(WriteEvent::default())
+        };
+
+        Event {
+            event_data: Some(EventData::Log(LogEvent {
+                context: Some(EventContext {
+                    pid,
+                    timestamp: Some((now() - Duration::from_secs(seconds)).into()),
+                    ..Default::default()
+                }),
+                log_event_data: Some(data),
+            })),
+            sequence: seconds,
+        }
+    }
+
+    fn query(journal: &Journal, query: EventQuery) -> Vec<u64> {
+        let mut sequences = Vec::new();
+        journal
+            .query(&query, |event| {
+                sequences.push(event.sequence);
+                true
+            })
+            .unwrap();
+        sequences
+    }
+
+    #[test]
+    fn queries_by_time_kind_and_pid() {
+        let dir = empty_dir("query");
+        let journal = Journal::open(options(&dir)).unwrap();
+        journal
+            .append(&[event(30, 1, true), event(20, 2, false), event(10, 1, false)])
+            .unwrap();
+
+        assert_eq!(query(&journal, EventQuery::default()), vec![30, 20, 10]);
+        assert_eq!(
+            query(
+                &journal,
+                EventQuery {
+                    start: Some((now() - Duration::from_secs(25)).into()),
+                    end: Some((now() - Duration::from_secs(15)).into()),
+                    ..Default::default()
+                }
+            ),
+            vec![20]
+        );
+        assert_eq!(
+            query(
+                &journal,
+                EventQuery {
+                    kinds: vec![EventKind::Write as i32],
+                    pids: vec![1],
+                    ..Default::default()
+                }
+            ),
+            vec![10]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn continues_after_reopening() {
+        let dir = empty_dir("reopen");
+        Journal::open(options(&dir))
+            .unwrap()
+            .append(&[event(2, 1, false)])
+            .unwrap();
+
+        let journal = Journal::open(options(&dir)).unwrap();
+        journal.append(&[event(1, 1, false)]).unwrap();
+
+        assert_eq!(query(&journal, EventQuery::default()), vec![2, 1]);
+        assert_eq!(segments(&dir).unwrap().len(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn removes_the_oldest_segments() {
+        let dir = empty_dir("retention");
+        let journal = Journal::open(JournalOptions {
+            segment_size: 1,
+            max_bytes: 1,
+            ..options(&dir)
+        })
+        .unwrap();
+
+        for seconds in [3, 2, 1] {
+            journal.append(&[event(seconds, 1, false)]).unwrap();
+        }
+
+        // Every event is a segment of its own, only the latest one is kept
+        assert_eq!(segments(&dir).unwrap().len(), 1);
+        assert_eq!(query(&journal, EventQuery::default()), vec![1]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn writer_appends_in_the_background() {
+        let dir = empty_dir("writer");
+        let journal = Arc::new(Journal::open(options(&dir)).unwrap());
+        let writer = JournalWriter::spawn(journal.clone()).unwrap();
+
+        writer.append(vec![event(2, 1, false), event(1, 1, false)]);
+        let mut written = Vec::new();
+        for _ in 0..100 {
+            written = query(&journal, EventQuery::default());
+            if !written.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(written, vec![2, 1]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn finds_events_written_up_to_the_max_delay_late() {
+        let dir = empty_dir("delay");
+        let journal = Journal::open(JournalOptions {
+            segment_size: 1,
+            max_delay: Duration::from_secs(60),
+            ..options(&dir)
+        })
+        .unwrap();
+
+        // The second segment starts after the first, its event is 40s older
+        journal.append(&[event(10, 1, false)]).unwrap();
+        journal.append(&[event(50, 1, false)]).unwrap();
+
+        let found = query(
+            &journal,
+            EventQuery {
+                start: Some((now() - Duration::from_secs(60)).into()),
+                end: Some((now() - Duration::from_secs(40)).into()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(found, vec![50]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn skips_partially_written_events() {
+        let dir = empty_dir("partial");
+        let journal = Journal::open(options(&dir)).unwrap();
+        journal
+            .append(&[event(2, 1, false), event(1, 1, false)])
+            .unwrap();
+
+        let (_, path) = segments(&dir).unwrap().pop().unwrap();
+        let content = fs::read(&path).unwrap();
+        fs::write(&path, &content[..content.len() - 1]).unwrap();
+
+        assert_eq!(query(&journal, EventQuery::default()), vec![2]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -4,75 +4,152 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::{ffi::CStr, sync::LazyLock, time::Duration};
+use std::{ffi::CStr, fs, time::Duration};
 
-use aya::maps::ring_buf::RingBufItem;
-use procfs::boot_time_secs;
-use shared::{events::{file_descriptor_change_event, jni_references_event, BlockingEvent, FileDescriptorChangeEvent, GarbageCollectEvent, JniReferencesEvent, SignalEvent}, google::{self, protobuf::Timestamp}};
-use bytemuck::checked;
+use procfs::process::Process;
+use shared::{events::{file_descriptor_change_event, jni_references_event, process_event, signal_event, BlockingEvent, CrashEvent, FileDescriptorChangeEvent, GarbageCollectEvent, JniReferencesEvent, ProcessEvent, SignalEvent}};
 use ebpf_types::{
-    Blocking, Event as EbpfEvent, EventKind as EbpfEventKind, FileDescriptorChange, FileDescriptorOp, GarbageCollect, JniReferences, Signal, Write, WriteSource
+    Blocking, Crash, Event as EbpfEvent, EventContext as EbpfEventContext, FileDescriptorChange, FileDescriptorOp, GarbageCollect, JniReferences, ProcessLifecycle, ProcessOp, Signal, SignalSource, Write, WriteSource
 };
 
 mod aggregator;
+mod clock;
 mod event_dispatcher;
+mod flight_recorder;
+mod journal;
+mod record;
+mod reorder;
 mod ring_buf;
+mod subscriptions;
 mod supervisor;
 mod time_series;
+mod time_series_store;
 
 use shared::events::{event::EventData, log_event::LogEventData, Event, EventContext, LogEvent, WriteEvent};
+pub use flight_recorder::{FlightRecorder, FlightRecorderOptions, TriggerRule};
+pub use journal::{Journal, JournalOptions};
+pub use subscriptions::{SubscriptionStream, Subscriptions};
 pub use supervisor::{CollectorSupervisor, CollectorSupervisorArguments};
+pub use time_series_store::{Resolution, TimeSeriesStore};
 
-static BOOT_TIME: LazyLock<u64> = LazyLock::new(|| {
-    boot_time_secs().unwrap()
-});
+fn event_context(context: &EbpfEventContext) -> EventContext {
+    EventContext {
+        pid: context.task.pid,
+        tid: context.task.tid,
+        timestamp: Some(clock::boot_time_to_timestamp(context.timestamp)),
+        thread_comm: c_str_to_string(&context.task.comm),
+        process_comm: c_str_to_string(&context.task.process_comm),
+        uid: context.task.uid,
+        gid: context.task.gid,
+    }
+}
 
-fn duration_since_boot_to_timestamp(nanos: u64) -> google::protobuf::Timestamp {
+/// Pseudo files like sockets, pipes or anon inodes have no absolute path in the kernel,
+/// procfs describes them like `socket:[1234]` as long as the file descriptor is still open.
+fn describe_file(pid: u32, fd: u64, kernel_path: &[u8]) -> String {
+    let path = c_str_to_string(kernel_path);
 
-    Timestamp {
-        seconds: (*BOOT_TIME + nanos / 1_000_000_000) as i64,
-        nanos: (nanos % 1_000_000_000) as i32,
+    if path.is_empty() || path.starts_with('/') {
+        return path;
     }
+
+    fs::read_link(format!("/proc/{pid}/fd/{fd}"))
+        .map(|link| link.to_string_lossy().to_string())
+        .unwrap_or(path)
 }
 
-pub trait IntoEvent {
-    fn into_event(self) -> Event;
+/// A buffer without a NUL was filled up completely, all of it is decoded
+fn c_str_to_string(bytes: &[u8]) -> String {
+    CStr::from_bytes_until_nul(bytes)
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|_| String::from_utf8_lossy(bytes).to_string())
 }
 
-impl IntoEvent for RingBufItem<'_> {
-    fn into_event(self) -> Event {
-        let kind = checked::from_bytes::<EbpfEventKind>(&self[..size_of::<EbpfEventKind>()]);
-        match *kind {
-            EbpfEventKind::Write => checked::from_bytes::<EbpfEvent<Write>>(&self).into_event(),
-            EbpfEventKind::Blocking => checked::from_bytes::<EbpfEvent<Blocking>>(&self).into_event(),
-            EbpfEventKind::Signal => checked::from_bytes::<EbpfEvent<Signal>>(&self).into_event(),
-            EbpfEventKind::GarbageCollect => {
-                checked::from_bytes::<EbpfEvent<GarbageCollect>>(&self).into_event()
-            }
-            EbpfEventKind::FileDescriptorChange => {
-                checked::from_bytes::<EbpfEvent<FileDescriptorChange>>(&self).into_event()
-            }
-            EbpfEventKind::JniReferences => {
-                checked::from_bytes::<EbpfEvent<JniReferences>>(&self).into_event()
-            }
-            _ => todo!(),
+/// Comm of a thread or process, empty if it is already gone
+fn comm_from_procfs(id: i32) -> String {
+    Process::new(id)
+        .and_then(|process| process.stat())
+        .map(|stat| stat.comm)
+        .unwrap_or_default()
+}
+
+/// The kernel only knows the pidfd of the sender, procfs shows the pid behind it.
+fn pid_from_pidfd(sender_pid: u32, pidfd: i32) -> Option<i32> {
+    let fdinfo = fs::read_to_string(format!("/proc/{sender_pid}/fdinfo/{pidfd}")).ok()?;
+    fdinfo
+        .lines()
+        .find_map(|line| line.strip_prefix("Pid:"))
+        .and_then(|pid| pid.trim().parse().ok())
+}
+
+/// Fills in everything the kernel could not resolve, this is best effort
+/// as the processes might be gone already.
+fn signal_event(signal: &Signal) -> SignalEvent {
+    let target_pid = match signal.source {
+        SignalSource::PidfdSendSignal => pid_from_pidfd(signal.sender_pid, signal.target_pid).unwrap_or(0),
+        SignalSource::Tkill => Process::new(signal.target_tid)
+            .and_then(|thread| thread.status())
+            .map(|status| status.tgid)
+            .unwrap_or(0),
+        _ => signal.target_pid,
+    };
+
+    let mut sender_comm = c_str_to_string(&signal.sender_comm);
+    if sender_comm.is_empty() && signal.sender_pid != 0 {
+        sender_comm = comm_from_procfs(signal.sender_pid as i32);
+    }
+
+    let mut target_comm = c_str_to_string(&signal.target_comm);
+    if target_comm.is_empty() {
+        let target = if signal.target_tid != 0 { signal.target_tid } else { target_pid };
+        if target > 0 {
+            target_comm = comm_from_procfs(target);
         }
     }
+
+    SignalEvent {
+        target_pid,
+        signal: signal.signal,
+        target_tid: signal.target_tid,
+        source: match signal.source {
+            SignalSource::Kill => signal_event::SignalSource::Kill,
+            SignalSource::Tkill => signal_event::SignalSource::Tkill,
+            SignalSource::Tgkill => signal_event::SignalSource::Tgkill,
+            SignalSource::RtSigqueueinfo => signal_event::SignalSource::RtSigqueueinfo,
+            SignalSource::RtTgsigqueueinfo => signal_event::SignalSource::RtTgsigqueueinfo,
+            SignalSource::PidfdSendSignal => signal_event::SignalSource::PidfdSendSignal,
+            SignalSource::Generate => signal_event::SignalSource::Generate,
+            SignalSource::Deliver => signal_event::SignalSource::Deliver,
+        }.into(),
+        result: signal.result,
+        sender_pid: signal.sender_pid,
+        sender_comm,
+        target_comm,
+    }
+}
+
+/// The timestamp of a log event as time since the unix epoch
+fn timestamp(event: &Event) -> Option<Duration> {
+    let Some(EventData::Log(log)) = &event.event_data else {
+        return None;
+    };
+    Some(log.context.as_ref()?.timestamp?.into())
+}
+
+/// The sequence number is left at 0, it is assigned by the dispatcher when sending
+pub trait IntoEvent {
+    fn into_event(self) -> Event;
 }
 
 impl IntoEvent for EbpfEvent<Write> {
     fn into_event(self) -> Event {
         Event {
             event_data: Some(EventData::Log(LogEvent {
-                context: Some(EventContext {
-                    pid: self.context.task.pid,
-                    tid: self.context.task.tid,
-                    timestamp: Some(duration_since_boot_to_timestamp(self.context.timestamp)),
-                }),
+                context: Some(event_context(&self.context)),
                 log_event_data: Some(LogEventData::Write(WriteEvent {
                     bytes_written: self.data.bytes_written,
                     file_descriptor: self.data.file_descriptor,
-                    file_path: CStr::from_bytes_until_nul(&self.data.file_path).unwrap().to_string_lossy().to_string(),
+                    file_path: c_str_to_string(&self.data.file_path),
                     source: match self.data.source {
                         WriteSource::Write => shared::events::write_event::WriteSource::Write,
                         WriteSource::Write64 => shared::events::write_event::WriteSource::Write64,
@@ -81,6 +158,7 @@ impl IntoEvent for EbpfEvent<Write> {
                     }.into(),
                 })),
             })),
+            sequence: 0,
         }
     }
 }
@@ -89,16 +167,10 @@ impl IntoEvent for EbpfEvent<Signal> {
     fn into_event(self) -> Event {
         Event {
             event_data: Some(EventData::Log(LogEvent {
-                context: Some(EventContext {
-                    pid: self.context.task.pid,
-                    tid: self.context.task.tid,
-                    timestamp: Some(duration_since_boot_to_timestamp(self.context.timestamp)),
-                }),
-                log_event_data: Some(LogEventData::Signal(SignalEvent {
-                    target_pid: self.data.target_pid,
-                    signal: self.data.signal,
-                })),
+                context: Some(event_context(&self.context)),
+                log_event_data: Some(LogEventData::Signal(signal_event(&self.data))),
             })),
+            sequence: 0,
         }
     }
 }
@@ -107,11 +179,7 @@ impl IntoEvent for EbpfEvent<GarbageCollect> {
     fn into_event(self) -> Event {
         Event {
             event_data: Some(EventData::Log(LogEvent {
-                context: Some(EventContext {
-                    pid: self.context.task.pid,
-                    tid: self.context.task.tid,
-                    timestamp: Some(duration_since_boot_to_timestamp(self.context.timestamp)),
-                }),
+                context: Some(event_context(&self.context)),
                 log_event_data: Some(LogEventData::GarbageCollect(GarbageCollectEvent {
                     target_footprint: self.data.target_footprint,
                     num_bytes_allocated: self.data.num_bytes_allocated,
@@ -124,7 +192,8 @@ impl IntoEvent for EbpfEvent<GarbageCollect> {
                     freed_los_objects: self.data.freed_los_objects,
                     gcs_completed: self.data.gcs_completed,
                 })),
-            }))
+            })),
+            sequence: 0,
         }
     }
 }
@@ -133,11 +202,7 @@ impl IntoEvent for EbpfEvent<FileDescriptorChange> {
     fn into_event(self) -> Event {
         Event {
             event_data: Some(EventData::Log(LogEvent {
-                context: Some(EventContext {
-                    pid: self.context.task.pid,
-                    tid: self.context.task.tid,
-                    timestamp: Some(duration_since_boot_to_timestamp(self.context.timestamp)),
-                }),
+                context: Some(event_context(&self.context)),
                 log_event_data: Some(LogEventData::FileDescriptorChange(FileDescriptorChangeEvent {
                     open_file_descriptors: self.data.open_fds,
                     operation: match self.data.operation {
@@ -145,7 +210,8 @@ impl IntoEvent for EbpfEvent<FileDescriptorChange> {
                         FileDescriptorOp::Close => file_descriptor_change_event::FileDescriptorOp::Close,
                     }.into(),
                 })),
-            }))
+            })),
+            sequence: 0,
         }
     }
 }
@@ -154,11 +220,7 @@ impl IntoEvent for EbpfEvent<JniReferences> {
     fn into_event(self) -> Event {
         Event {
             event_data: Some(EventData::Log(LogEvent {
-                context: Some(EventContext {
-                    pid: self.context.task.pid,
-                    tid: self.context.task.tid,
-                    timestamp: Some(duration_since_boot_to_timestamp(self.context.timestamp)),
-                }),
+                context: Some(event_context(&self.context)),
                 log_event_data: Some(LogEventData::JniReferences(JniReferencesEvent {
                     method_name: match self.data {
                         JniReferences::AddLocalRef => jni_references_event::JniMethodName::AddLocalRef,
@@ -167,7 +229,8 @@ impl IntoEvent for EbpfEvent<JniReferences> {
                         JniReferences::DeleteGlobalRef => jni_references_event::JniMethodName::DeleteGlobalRef,
                     }.into(),
                 })),
-            }))
+            })),
+            sequence: 0,
         }
     }
 }
@@ -176,15 +239,181 @@ impl IntoEvent for EbpfEvent<Blocking> {
     fn into_event(self) -> Event {
         Event {
             event_data: Some(EventData::Log(LogEvent {
-                context: Some(EventContext {
-                    pid: self.context.task.pid,
-                    tid: self.context.task.tid,
-                    timestamp: Some(duration_since_boot_to_timestamp(self.context.timestamp)),
-                }),
+                context: Some(event_context(&self.context)),
                 log_event_data: Some(LogEventData::Blocking(BlockingEvent {
                     duration: Some(Duration::from_nanos(self.data.duration).into()),
+                    syscall_id: self.data.syscall_id as u32,
+                    syscall_name: syscall_numbers::native::sys_call_name(self.data.syscall_id as i64)
+                        .unwrap_or_default()
+                        .to_string(),
+                    first_argument: self.data.first_arg,
+                    file_description: describe_file(self.context.task.pid, self.data.first_arg, &self.data.file_path),
+                })),
+            })),
+            sequence: 0,
+        }
+    }
+}
+
+impl IntoEvent for EbpfEvent<Crash> {
+    fn into_event(self) -> Event {
+        let stack_len = (self.data.stack_len as usize).min(self.data.stack.len());
+
+        Event {
+            event_data: Some(EventData::Log(LogEvent {
+                context: Some(event_context(&self.context)),
+                log_event_data: Some(LogEventData::Crash(CrashEvent {
+                    signal: self.data.signal,
+                    code: self.data.si_code,
+                    fault_address: (self.data.si_code > 0).then_some(self.data.fault_addr),
+                    user_stack: self.data.stack[..stack_len].to_vec(),
+                    // The first argument is the package name for apps
+                    process_name: c_str_to_string(&self.data.cmdline),
+                    thread_name: c_str_to_string(&self.context.task.comm),
+                })),
+            })),
+            sequence: 0,
+        }
+    }
+}
+
+impl IntoEvent for EbpfEvent<ProcessLifecycle> {
+    fn into_event(self) -> Event {
+        // The exit code has the layout of a wait status
+        let exit_code = self.data.exit_code;
+        let (exit_status, terminating_signal) = match self.data.operation {
+            ProcessOp::Exit if exit_code & 0x7f == 0 => (Some((exit_code >> 8) & 0xff), None),
+            ProcessOp::Exit => (None, Some(exit_code & 0x7f)),
+            _ => (None, None),
+        };
+
+        Event {
+            event_data: Some(EventData::Log(LogEvent {
+                context: Some(event_context(&self.context)),
+                log_event_data: Some(LogEventData::Process(ProcessEvent {
+                    operation: match self.data.operation {
+                        ProcessOp::Fork => process_event::ProcessOp::Fork,
+                        ProcessOp::Exec => process_event::ProcessOp::Exec,
+                        ProcessOp::Exit => process_event::ProcessOp::Exit,
+                    }.into(),
+                    pid: self.data.pid,
+                    parent_pid: self.data.parent_pid,
+                    exe_path: c_str_to_string(&self.data.process.exe_path),
+                    cmdline: self.data.process.cmdline
+                        .split(|byte| *byte == 0)
+                        .filter(|arg| !arg.is_empty())
+                        .map(|arg| String::from_utf8_lossy(arg).to_string())
+                        .collect(),
+                    exit_status,
+                    terminating_signal,
+                    core_dumped: matches!(self.data.operation, ProcessOp::Exit) && exit_code & 0x80 != 0,
                 })),
-            }))
+            })),
+            sequence: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ebpf_types::{ProcessContext, RecordHeader, TaskContext, CRASH_STACK_DEPTH};
+    use shared::events::ProcessEvent;
+
+    use super::*;
+
+    fn ebpf_event<T: ebpf_types::EventData>(data: T) -> EbpfEvent<T> {
+        EbpfEvent {
+            header: RecordHeader::new::<T>(),
+            context: EbpfEventContext {
+                task: TaskContext::default(),
+                timestamp: 0,
+            },
+            data,
+        }
+    }
+
+    fn process_exit(exit_code: u32) -> ProcessEvent {
+        let event = ebpf_event(ProcessLifecycle {
+            pid: 1,
+            parent_pid: 0,
+            exit_code,
+            operation: ProcessOp::Exit,
+            process: ProcessContext::default(),
+        })
+        .into_event();
+
+        let Some(EventData::Log(LogEvent { log_event_data: Some(LogEventData::Process(process)), .. })) = event.event_data else {
+            panic!("expected a process event");
+        };
+        process
+    }
+
+    #[test]
+    fn decodes_the_exit_code() {
+        let process = process_exit(3 << 8);
+        assert_eq!(process.exit_status, Some(3));
+        assert_eq!(process.terminating_signal, None);
+        assert!(!process.core_dumped);
+    }
+
+    #[test]
+    fn decodes_the_terminating_signal() {
+        let process = process_exit(9);
+        assert_eq!(process.exit_status, None);
+        assert_eq!(process.terminating_signal, Some(9));
+        assert!(!process.core_dumped);
+    }
+
+    #[test]
+    fn decodes_core_dumps() {
+        let process = process_exit(0x80 | 11);
+        assert_eq!(process.exit_status, None);
+        assert_eq!(process.terminating_signal, Some(11));
+        assert!(process.core_dumped);
+    }
+
+    fn crash(si_code: i32, stack_len: u32) -> CrashEvent {
+        let mut cmdline = [0; 256];
+        cmdline[..11].copy_from_slice(b"com.example");
+        let mut stack = [0; CRASH_STACK_DEPTH];
+        for (i, frame) in stack.iter_mut().enumerate() {
+            *frame = i as u64;
         }
+
+        let event = ebpf_event(Crash {
+            signal: 11,
+            si_code,
+            fault_addr: 0xdead,
+            stack_len,
+            stack,
+            cmdline,
+        })
+        .into_event();
+
+        let Some(EventData::Log(LogEvent { log_event_data: Some(LogEventData::Crash(crash)), .. })) = event.event_data else {
+            panic!("expected a crash event");
+        };
+        crash
+    }
+
+    #[test]
+    fn crash_keeps_the_valid_stack_frames() {
+        let crash = crash(1, 3);
+        assert_eq!(crash.signal, 11);
+        assert_eq!(crash.user_stack, vec![0, 1, 2]);
+        assert_eq!(crash.process_name, "com.example");
+    }
+
+    #[test]
+    fn crash_clamps_the_stack_length() {
+        let crash = crash(1, u32::MAX);
+        assert_eq!(crash.user_stack.len(), CRASH_STACK_DEPTH);
+    }
+
+    #[test]
+    fn crash_has_a_fault_address_only_if_raised_by_the_kernel() {
+        assert_eq!(crash(1, 0).fault_address, Some(0xdead));
+        // `SI_TKILL`, sent by `tgkill` e.g. from `abort`
+        assert_eq!(crash(-6, 0).fault_address, None);
     }
 }
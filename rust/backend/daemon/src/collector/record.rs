@@ -0,0 +1,244 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use std::time::Duration;
+
+use bytemuck::{checked, CheckedBitPattern};
+use ebpf_types::{
+    Blocking, Crash, Event as EbpfEvent, EventKind as EbpfEventKind, FileDescriptorChange,
+    GarbageCollect, JniReferences, ProcessLifecycle, RecordHeader, Signal, Write,
+    RECORD_FORMAT_VERSION,
+};
+use shared::events::Event;
+use thiserror::Error;
+
+use super::IntoEvent;
+
+const HEADER_SIZE: usize = size_of::<RecordHeader>();
+
+#[derive(Debug, Error)]
+pub enum RecordError {
+    #[error("record of {0} bytes is shorter than its header")]
+    Truncated(usize),
+    #[error("record format version {0} is not supported, expected {RECORD_FORMAT_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("record header announces {announced} bytes, but the record has {actual}")]
+    LengthMismatch { announced: u32, actual: usize },
+    #[error("unknown event kind {0}")]
+    UnknownKind(u64),
+    #[error("record of kind {kind:?} has {actual} bytes, expected {expected}")]
+    UnexpectedSize {
+        kind: EbpfEventKind,
+        actual: usize,
+        expected: usize,
+    },
+    #[error("record of kind {0:?} contains invalid values")]
+    InvalidData(EbpfEventKind),
+}
+
+/// An event and the `CLOCK_BOOTTIME` of its record, which unlike the wall-clock
+/// timestamp of the event never jumps
+#[derive(Debug, Clone)]
+pub struct DecodedRecord {
+    pub boot_time: Duration,
+    pub event: Event,
+}
+
+/// Decodes a record of a ring buffer, records of other versions of the eBPF
+/// object are rejected instead of being misinterpreted.
+pub fn decode(record: &[u8]) -> Result<DecodedRecord, RecordError> {
+    let header = record
+        .get(..HEADER_SIZE)
+        .map(bytemuck::pod_read_unaligned::<RecordHeader>)
+        .ok_or(RecordError::Truncated(record.len()))?;
+
+    if header.version != RECORD_FORMAT_VERSION {
+        return Err(RecordError::UnsupportedVersion(header.version));
+    }
+    if header.length as usize != record.len() {
+        return Err(RecordError::LengthMismatch {
+            announced: header.length,
+            actual: record.len(),
+        });
+    }
+
+    let kind = checked::try_cast::<u64, EbpfEventKind>(header.kind)
+        .map_err(|_| RecordError::UnknownKind(header.kind))?;
+
+    match kind {
+        EbpfEventKind::Write => decode_as::<Write>(kind, record),
+        EbpfEventKind::Blocking => decode_as::<Blocking>(kind, record),
+        EbpfEventKind::Signal => decode_as::<Signal>(kind, record),
+        EbpfEventKind::GarbageCollect => decode_as::<GarbageCollect>(kind, record),
+        EbpfEventKind::FileDescriptorChange => decode_as::<FileDescriptorChange>(kind, record),
+        EbpfEventKind::JniReferences => decode_as::<JniReferences>(kind, record),
+        EbpfEventKind::ProcessLifecycle => decode_as::<ProcessLifecycle>(kind, record),
+        EbpfEventKind::Crash => decode_as::<Crash>(kind, record),
+        EbpfEventKind::MAX => Err(RecordError::UnknownKind(header.kind)),
+    }
+}
+
+fn decode_as<T>(kind: EbpfEventKind, record: &[u8]) -> Result<DecodedRecord, RecordError>
+where
+    T: CheckedBitPattern + 'static,
+    EbpfEvent<T>: IntoEvent,
+{
+    let expected = size_of::<EbpfEvent<T>>();
+    if record.len() != expected {
+        return Err(RecordError::UnexpectedSize {
+            kind,
+            actual: record.len(),
+            expected,
+        });
+    }
+
+    checked::try_pod_read_unaligned::<EbpfEvent<T>>(record)
+        .map(|event| DecodedRecord {
+            boot_time: Duration::from_nanos(event.context.timestamp),
+            event: event.into_event(),
+        })
+        .map_err(|_| RecordError::InvalidData(kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::offset_of;
+
+    use shared::events::{event::EventData, jni_references_event, log_event::LogEventData};
+
+    use super::*;
+
+    const JNI_RECORD_SIZE: usize = size_of::<EbpfEvent<JniReferences>>();
+    const JNI_DATA_OFFSET: usize = offset_of!(EbpfEvent<JniReferences>, data);
+
+    /// A zeroed record with the given header, zeroes are valid for every field of
+    /// `JniReferences` events
+    fn record(header: RecordHeader, length: usize) -> Vec<u8> {
+        let mut record = vec![0; length];
+        record[..HEADER_SIZE].copy_from_slice(&header_bytes(header));
+        record
+    }
+
+    fn header_bytes(header: RecordHeader) -> Vec<u8> {
+        [
+            &header.version.to_ne_bytes()[..],
+            &header.length.to_ne_bytes()[..],
+            &header.kind.to_ne_bytes()[..],
+        ]
+        .concat()
+    }
+
+    fn jni_record() -> Vec<u8> {
+        record(RecordHeader::new::<JniReferences>(), JNI_RECORD_SIZE)
+    }
+
+    #[test]
+    fn decodes_valid_records() {
+        let event = decode(&jni_record()).unwrap().event;
+        let Some(EventData::Log(log)) = event.event_data else {
+            panic!("expected a log event");
+        };
+        let Some(LogEventData::JniReferences(jni)) = log.log_event_data else {
+            panic!("expected a jni references event");
+        };
+        assert_eq!(
+            jni.method_name(),
+            jni_references_event::JniMethodName::AddLocalRef
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_records() {
+        let record = &jni_record()[..HEADER_SIZE - 1];
+
+        assert!(matches!(
+            decode(record),
+            Err(RecordError::Truncated(len)) if len == HEADER_SIZE - 1
+        ));
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let header = RecordHeader {
+            version: RECORD_FORMAT_VERSION + 1,
+            ..RecordHeader::new::<JniReferences>()
+        };
+
+        assert!(matches!(
+            decode(&record(header, JNI_RECORD_SIZE)),
+            Err(RecordError::UnsupportedVersion(version)) if version == RECORD_FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn rejects_length_mismatch() {
+        let record = &jni_record()[..JNI_RECORD_SIZE - 8];
+
+        assert!(matches!(
+            decode(record),
+            Err(RecordError::LengthMismatch { actual, .. }) if actual == JNI_RECORD_SIZE - 8
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_kinds() {
+        for kind in [EbpfEventKind::MAX as u64, 42] {
+            let header = RecordHeader {
+                kind,
+                ..RecordHeader::new::<JniReferences>()
+            };
+
+            assert!(matches!(
+                decode(&record(header, JNI_RECORD_SIZE)),
+                Err(RecordError::UnknownKind(unknown)) if unknown == kind
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_records_of_the_wrong_size_for_their_kind() {
+        // A write record which claims to be as long as a jni record
+        let header = RecordHeader {
+            kind: EbpfEventKind::Write as u64,
+            ..RecordHeader::new::<JniReferences>()
+        };
+
+        assert!(matches!(
+            decode(&record(header, JNI_RECORD_SIZE)),
+            Err(RecordError::UnexpectedSize {
+                kind: EbpfEventKind::Write,
+                actual: JNI_RECORD_SIZE,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn decodes_paths_without_a_nul() {
+        let mut record = record(RecordHeader::new::<Write>(), size_of::<EbpfEvent<Write>>());
+        let path = offset_of!(EbpfEvent<Write>, data) + offset_of!(Write, file_path);
+        record[path..path + 4096].fill(b'a');
+
+        let event = decode(&record).unwrap().event;
+        let Some(EventData::Log(log)) = event.event_data else {
+            panic!("expected a log event");
+        };
+        let Some(LogEventData::Write(write)) = log.log_event_data else {
+            panic!("expected a write event");
+        };
+        assert_eq!(write.file_path, "a".repeat(4096));
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        let mut record = jni_record();
+        // Not a variant of `JniReferences`
+        record[JNI_DATA_OFFSET] = 0xff;
+
+        assert!(matches!(
+            decode(&record),
+            Err(RecordError::InvalidData(EbpfEventKind::JniReferences))
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    mem,
+    time::Duration,
+};
+
+use shared::events::Event;
+
+use super::record::DecodedRecord;
+
+/// Holds events for a time window and releases them in the order of their
+/// timestamps. Records of different cpus and ring buffers are read in the
+/// order they were reserved, which is not always the order of their timestamps.
+///
+/// Events are ordered by the `CLOCK_BOOTTIME` of their records, a step of the
+/// wall clock neither holds them back nor makes them late.
+pub struct ReorderBuffer {
+    window: Duration,
+    /// The oldest event is released early once more events are held
+    max_pending: usize,
+    pending: BinaryHeap<Reverse<Pending>>,
+    /// Keeps the order of arrival for equal timestamps
+    arrivals: u64,
+    /// Boot time of the latest released event
+    released_until: Duration,
+    late_events: u64,
+}
+
+struct Pending {
+    boot_time: Duration,
+    arrival: u64,
+    event: Event,
+}
+
+impl Pending {
+    fn key(&self) -> (Duration, u64) {
+        (self.boot_time, self.arrival)
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl ReorderBuffer {
+    pub fn new(window: Duration, max_pending: usize) -> Self {
+        Self {
+            window,
+            max_pending,
+            pending: BinaryHeap::new(),
+            arrivals: 0,
+            released_until: Duration::ZERO,
+            late_events: 0,
+        }
+    }
+
+    /// Events older than the released ones cannot be ordered, they are released right away.
+    pub fn push(&mut self, record: DecodedRecord, released: &mut Vec<Event>) {
+        if record.boot_time < self.released_until {
+            self.late_events += 1;
+            released.push(record.event);
+            return;
+        }
+
+        self.arrivals += 1;
+        self.pending.push(Reverse(Pending {
+            boot_time: record.boot_time,
+            arrival: self.arrivals,
+            event: record.event,
+        }));
+
+        if self.pending.len() > self.max_pending {
+            self.release_oldest(released);
+        }
+    }
+
+    /// Releases all events older than the window, `now` is the current boot time
+    pub fn release(&mut self, now: Duration, released: &mut Vec<Event>) {
+        let until = now.saturating_sub(self.window);
+
+        while let Some(Reverse(pending)) = self.pending.peek() {
+            if pending.boot_time > until {
+                break;
+            }
+            self.release_oldest(released);
+        }
+    }
+
+    fn release_oldest(&mut self, released: &mut Vec<Event>) {
+        if let Some(Reverse(pending)) = self.pending.pop() {
+            self.released_until = pending.boot_time;
+            released.push(pending.event);
+        }
+    }
+
+    /// Events which arrived after the window since the last call, they were
+    /// released out of order
+    pub fn take_late_events(&mut self) -> u64 {
+        mem::take(&mut self.late_events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::events::{event::EventData, EventContext, LogEvent};
+
+    use super::*;
+
+    /// The pid tells the events apart
+    fn record(millis: u64) -> DecodedRecord {
+        DecodedRecord {
+            boot_time: Duration::from_millis(millis),
+            event: Event {
+                event_data: Some(EventData::Log(LogEvent {
+                    context: Some(EventContext {
+                        pid: millis as u32,
+                        ..Default::default()
+                    }),
+                    log_event_data: None,
+                })),
+                sequence: 0,
+            },
+        }
+    }
+
+    fn millis(events: &[Event]) -> Vec<u64> {
+        events
+            .iter()
+            .map(|event| match &event.event_data {
+                Some(EventData::Log(log)) => log.context.as_ref().unwrap().pid.into(),
+                _ => panic!("expected a log event"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn releases_in_timestamp_order_after_the_window() {
+        let mut buffer = ReorderBuffer::new(Duration::from_millis(10), usize::MAX);
+        let mut released = Vec::new();
+
+        for millis in [3, 1, 2, 12] {
+            buffer.push(record(millis), &mut released);
+        }
+        assert!(released.is_empty());
+
+        buffer.release(Duration::from_millis(13), &mut released);
+        assert_eq!(millis(&released), vec![1, 2, 3]);
+
+        buffer.release(Duration::from_millis(22), &mut released);
+        assert_eq!(millis(&released), vec![1, 2, 3, 12]);
+        assert_eq!(buffer.take_late_events(), 0);
+    }
+
+    #[test]
+    fn equal_timestamps_keep_their_arrival_order() {
+        let mut buffer = ReorderBuffer::new(Duration::ZERO, usize::MAX);
+        let mut released = Vec::new();
+
+        let first = record(1);
+        let mut second = record(1);
+        if let Some(EventData::Log(log)) = &mut second.event.event_data {
+            log.context.as_mut().unwrap().pid = 2;
+        }
+        buffer.push(first.clone(), &mut released);
+        buffer.push(second.clone(), &mut released);
+        buffer.release(Duration::from_millis(1), &mut released);
+
+        assert_eq!(released, vec![first.event, second.event]);
+    }
+
+    #[test]
+    fn late_events_are_counted_and_released() {
+        let mut buffer = ReorderBuffer::new(Duration::from_millis(10), usize::MAX);
+        let mut released = Vec::new();
+
+        buffer.push(record(5), &mut released);
+        buffer.release(Duration::from_millis(15), &mut released);
+
+        buffer.push(record(4), &mut released);
+        assert_eq!(millis(&released), vec![5, 4]);
+        assert_eq!(buffer.take_late_events(), 1);
+        assert_eq!(buffer.take_late_events(), 0);
+    }
+
+    #[test]
+    fn releases_the_oldest_events_once_full() {
+        let mut buffer = ReorderBuffer::new(Duration::from_secs(1), 2);
+        let mut released = Vec::new();
+
+        for millis in [3, 1, 2] {
+            buffer.push(record(millis), &mut released);
+        }
+        assert_eq!(millis(&released), vec![1]);
+
+        // Older than the early released event
+        buffer.push(record(0), &mut released);
+        assert_eq!(millis(&released), vec![1, 0]);
+        assert_eq!(buffer.take_late_events(), 1);
+    }
+}
@@ -3,29 +3,55 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::io;
+use std::{io, mem};
 
 use ractor::{cast, Actor, ActorRef};
-use shared::events::Event;
 use tokio::io::unix::AsyncFd;
+use tracing::warn;
 
-use super::IntoEvent;
-use crate::registry::{OwnedRingBuf, RegistryGuard, RegistryItem};
+use super::{
+    event_dispatcher::EventDispatcherMsg,
+    record::{self, RecordError},
+};
+use crate::{
+    constants::EVENT_BATCH_SIZE,
+    registry::{OwnedRingBuf, RegistryGuard, RegistryItem},
+};
 
 pub struct RingBufCollector;
 
 pub struct RingBufCollectorState {
     map: AsyncFd<RegistryGuard<OwnedRingBuf>>,
-    event_actor: ActorRef<Event>,
+    event_actor: ActorRef<EventDispatcherMsg>,
+    skipped_records: SkippedRecords,
+}
+
+#[derive(Default)]
+struct SkippedRecords(u64);
+
+impl SkippedRecords {
+    fn count(&mut self, error: RecordError) {
+        self.0 += 1;
+        // A mismatching eBPF object makes every record invalid, so only a few are logged
+        if self.0.is_power_of_two() {
+            warn!(
+                "Skipped {} malformed records, the latest: {}",
+                self.0, error
+            );
+        }
+    }
 }
 
 pub struct RingBufCollectorArguments {
     item: RegistryItem<OwnedRingBuf>,
-    event_actor: ActorRef<Event>,
+    event_actor: ActorRef<EventDispatcherMsg>,
 }
 
 impl RingBufCollectorArguments {
-    pub fn new(item: RegistryItem<OwnedRingBuf>, event_actor: ActorRef<Event>) -> Self {
+    pub fn new(
+        item: RegistryItem<OwnedRingBuf>,
+        event_actor: ActorRef<EventDispatcherMsg>,
+    ) -> Self {
         Self { item, event_actor }
     }
 }
@@ -37,6 +63,7 @@ impl TryFrom<RingBufCollectorArguments> for RingBufCollectorState {
         Ok(Self {
             map: AsyncFd::new(value.item.take())?,
             event_actor: value.event_actor,
+            skipped_records: SkippedRecords::default(),
         })
     }
 }
@@ -64,8 +91,22 @@ impl Actor for RingBufCollector {
         let mut guard = state.map.readable_mut().await?;
         let inner = guard.get_inner_mut();
 
-        while let Some(item) = inner.next().map(IntoEvent::into_event) {
-            cast!(state.event_actor, item)?;
+        // Everything which is ready is read at once, in batches of bounded size
+        let mut events = Vec::new();
+        while let Some(item) = inner.next() {
+            match record::decode(&item) {
+                Ok(record) => events.push(record),
+                Err(error) => state.skipped_records.count(error),
+            }
+            if events.len() >= EVENT_BATCH_SIZE {
+                cast!(
+                    state.event_actor,
+                    EventDispatcherMsg::Events(mem::take(&mut events))
+                )?;
+            }
+        }
+        if !events.is_empty() {
+            cast!(state.event_actor, EventDispatcherMsg::Events(events))?;
         }
 
         guard.clear_ready();
@@ -0,0 +1,685 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, LazyLock, Mutex,
+    },
+    task::{ready, Context, Poll},
+    time::{Duration, SystemTime},
+};
+
+use async_broadcast::{broadcast, Receiver, Sender, TrySendError};
+use shared::events::{
+    event::EventData, BackpressurePolicy, Event, EventBatch, StreamOptions, StreamStatus,
+};
+use thiserror::Error;
+use tokio_stream::Stream;
+use tonic::Status;
+
+use crate::constants::{
+    DEFAULT_BATCHED_STREAM_CAPACITY, DEFAULT_STREAM_BLOCK_TIMEOUT, DEFAULT_STREAM_CAPACITY,
+};
+
+/// Sent in every stream status, the start time of the daemon tells its instances apart
+static DAEMON_INSTANCE: LazyLock<u64> = LazyLock::new(|| {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+});
+
+#[derive(Debug, Error)]
+pub enum SubscribeError {
+    #[error("unknown backpressure policy {0}")]
+    UnknownBackpressurePolicy(i32),
+}
+
+impl From<SubscribeError> for Status {
+    fn from(err: SubscribeError) -> Self {
+        Status::invalid_argument(err.to_string())
+    }
+}
+
+/// A message of a stream, drops are counted in events
+pub trait StreamMessage: Clone + Send + Sync + Unpin + 'static {
+    const DEFAULT_CAPACITY: u32;
+
+    fn event_count(&self) -> u64;
+
+    fn status(status: StreamStatus) -> Self;
+
+    /// The part of the message after the sequence number, if any
+    fn resume_after(self, sequence: u64) -> Option<Self>;
+}
+
+impl StreamMessage for Event {
+    const DEFAULT_CAPACITY: u32 = DEFAULT_STREAM_CAPACITY;
+
+    fn event_count(&self) -> u64 {
+        1
+    }
+
+    fn status(status: StreamStatus) -> Self {
+        Event {
+            event_data: Some(EventData::StreamStatus(status)),
+            sequence: 0,
+        }
+    }
+
+    fn resume_after(self, sequence: u64) -> Option<Self> {
+        (self.sequence > sequence).then_some(self)
+    }
+}
+
+impl StreamMessage for EventBatch {
+    const DEFAULT_CAPACITY: u32 = DEFAULT_BATCHED_STREAM_CAPACITY;
+
+    fn event_count(&self) -> u64 {
+        self.events.len() as u64
+    }
+
+    fn status(status: StreamStatus) -> Self {
+        EventBatch {
+            events: vec![Event::status(status)],
+        }
+    }
+
+    fn resume_after(mut self, sequence: u64) -> Option<Self> {
+        self.events.retain(|event| event.sequence > sequence);
+        (!self.events.is_empty()).then_some(self)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    dropped_events: AtomicU64,
+    disconnected: AtomicBool,
+    /// A blocking subscription timed out and drops new messages until its
+    /// client took all pending ones
+    overflowed: AtomicBool,
+}
+
+#[derive(Clone)]
+struct Subscriber<T> {
+    tx: Sender<T>,
+    policy: BackpressurePolicy,
+    block_timeout: Duration,
+    counters: Arc<Counters>,
+}
+
+impl<T: StreamMessage> Subscriber<T> {
+    fn drop_message(&self, message: &T) {
+        self.counters
+            .dropped_events
+            .fetch_add(message.event_count(), Ordering::Relaxed);
+    }
+
+    /// Returns the message if the subscriber waits for its client to take it
+    fn try_send(&self, message: T) -> Option<T> {
+        match self.policy {
+            BackpressurePolicy::Unspecified | BackpressurePolicy::DropOldest => {
+                if let Ok(Some(oldest)) = self.tx.try_broadcast(message) {
+                    self.drop_message(&oldest);
+                }
+            }
+            BackpressurePolicy::DropNewest => {
+                if let Err(TrySendError::Full(newest)) = self.tx.try_broadcast(message) {
+                    self.drop_message(&newest);
+                }
+            }
+            BackpressurePolicy::Block => {
+                // A stalled client holds up the collector only once, not for every message
+                if self.counters.overflowed.load(Ordering::Relaxed) {
+                    if !self.tx.is_empty() {
+                        if let Err(TrySendError::Full(newest)) = self.tx.try_broadcast(message) {
+                            self.drop_message(&newest);
+                        }
+                        return None;
+                    }
+                    self.counters.overflowed.store(false, Ordering::Relaxed);
+                }
+
+                if let Err(TrySendError::Full(message)) = self.tx.try_broadcast(message) {
+                    return Some(message);
+                }
+            }
+            BackpressurePolicy::Disconnect => {
+                if let Err(TrySendError::Full(newest)) = self.tx.try_broadcast(message) {
+                    self.drop_message(&newest);
+                    self.counters.disconnected.store(true, Ordering::Relaxed);
+                    // The client still gets the pending messages before the status
+                    self.tx.close();
+                }
+            }
+        }
+        None
+    }
+
+    async fn send_blocking(&self, message: T) {
+        let event_count = message.event_count();
+        let sent = tokio::time::timeout(self.block_timeout, self.tx.broadcast(message));
+        if sent.await.is_err() {
+            self.counters
+                .dropped_events
+                .fetch_add(event_count, Ordering::Relaxed);
+            self.counters.overflowed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The latest messages, up to a number of events
+struct ReplayBuffer<T> {
+    messages: VecDeque<T>,
+    events: u64,
+    capacity: u64,
+}
+
+impl<T: StreamMessage> ReplayBuffer<T> {
+    fn push(&mut self, message: &T) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.events += message.event_count();
+        self.messages.push_back(message.clone());
+        while self.events > self.capacity {
+            let Some(oldest) = self.messages.pop_front() else {
+                break;
+            };
+            self.events -= oldest.event_count();
+        }
+    }
+
+    fn after(&self, sequence: u64) -> VecDeque<T> {
+        self.messages
+            .iter()
+            .filter_map(|message| message.clone().resume_after(sequence))
+            .collect()
+    }
+}
+
+struct SubscriptionsState<T> {
+    subscribers: Vec<Subscriber<T>>,
+    /// Shares the lock with the subscribers, so a resumed stream gets every
+    /// message exactly once, either from the buffer or directly
+    replay: ReplayBuffer<T>,
+}
+
+/// The streams of all clients for one kind of message, each with its own
+/// capacity and backpressure policy
+pub struct Subscriptions<T> {
+    state: Mutex<SubscriptionsState<T>>,
+    /// Shared by all streams, counted since the daemon was started
+    late_events: Arc<AtomicU64>,
+}
+
+impl<T> Default for Subscriptions<T> {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<T> Subscriptions<T> {
+    /// Keeps the messages of the latest `replay_capacity` events for resumed streams
+    pub fn new(replay_capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(SubscriptionsState {
+                subscribers: Vec::new(),
+                replay: ReplayBuffer {
+                    messages: VecDeque::new(),
+                    events: 0,
+                    capacity: replay_capacity as u64,
+                },
+            }),
+            late_events: Arc::default(),
+        }
+    }
+}
+
+impl<T: StreamMessage> Subscriptions<T> {
+    pub fn subscribe(
+        &self,
+        options: &StreamOptions,
+    ) -> Result<SubscriptionStream<T>, SubscribeError> {
+        let policy = match BackpressurePolicy::try_from(options.backpressure_policy) {
+            Ok(BackpressurePolicy::Unspecified) => BackpressurePolicy::DropOldest,
+            Ok(policy) => policy,
+            Err(_) => {
+                return Err(SubscribeError::UnknownBackpressurePolicy(
+                    options.backpressure_policy,
+                ))
+            }
+        };
+
+        let capacity = match options.capacity {
+            0 => T::DEFAULT_CAPACITY,
+            capacity => capacity,
+        };
+        let block_timeout = match options.block_timeout_ms {
+            0 => DEFAULT_STREAM_BLOCK_TIMEOUT,
+            millis => Duration::from_millis(millis.into()),
+        };
+
+        let (mut tx, rx) = broadcast(capacity as usize);
+        tx.set_overflow(policy == BackpressurePolicy::DropOldest);
+
+        let counters = Arc::new(Counters::default());
+        let mut state = self.state.lock().unwrap();
+        let replay = options
+            .resume_after
+            .map(|sequence| state.replay.after(sequence))
+            .unwrap_or_default();
+        state.subscribers.push(Subscriber {
+            tx,
+            policy,
+            block_timeout,
+            counters: counters.clone(),
+        });
+
+        Ok(SubscriptionStream {
+            replay,
+            rx,
+            policy,
+            capacity,
+            counters,
+            late_events: self.late_events.clone(),
+            late_events_before: self.late_events.load(Ordering::Relaxed),
+            reported: None,
+        })
+    }
+
+    /// Events which were sent out of timestamp order
+    pub fn count_late_events(&self, count: u64) {
+        self.late_events.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Sends the message to every subscription according to its policy, waits
+    /// only for full blocking subscribers once the lock is released
+    pub async fn publish(&self, message: &T) {
+        let blocked = {
+            let mut state = self.state.lock().unwrap();
+            state.replay.push(message);
+
+            let mut blocked = Vec::new();
+            for subscriber in &state.subscribers {
+                if let Some(message) = subscriber.try_send(message.clone()) {
+                    blocked.push((subscriber.clone(), message));
+                }
+            }
+
+            // Streams of disconnected clients are closed once their receiver is dropped
+            state
+                .subscribers
+                .retain(|subscriber| !subscriber.tx.is_closed());
+            blocked
+        };
+
+        for (subscriber, message) in blocked {
+            subscriber.send_blocking(message).await;
+        }
+    }
+}
+
+/// The stream of a single client, reports its status whenever events were dropped
+/// or arrived late
+pub struct SubscriptionStream<T> {
+    /// Buffered messages for a resumed stream, sent before the new ones
+    replay: VecDeque<T>,
+    rx: Receiver<T>,
+    policy: BackpressurePolicy,
+    capacity: u32,
+    counters: Arc<Counters>,
+    late_events: Arc<AtomicU64>,
+    late_events_before: u64,
+    /// Dropped and late events of the latest status
+    reported: Option<(u64, u64)>,
+}
+
+impl<T: StreamMessage> Stream for SubscriptionStream<T> {
+    type Item = Result<T, Status>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        let dropped_events = this.counters.dropped_events.load(Ordering::Relaxed);
+        let late_events = this.late_events.load(Ordering::Relaxed) - this.late_events_before;
+        if this.reported != Some((dropped_events, late_events)) {
+            this.reported = Some((dropped_events, late_events));
+            return Poll::Ready(Some(Ok(T::status(StreamStatus {
+                backpressure_policy: this.policy.into(),
+                capacity: this.capacity,
+                dropped_events,
+                late_events,
+                daemon_instance: *DAEMON_INSTANCE,
+            }))));
+        }
+
+        if let Some(message) = this.replay.pop_front() {
+            return Poll::Ready(Some(Ok(message)));
+        }
+
+        match ready!(Pin::new(&mut this.rx).poll_next(cx)) {
+            Some(message) => Poll::Ready(Some(Ok(message))),
+            None if this.counters.disconnected.swap(false, Ordering::Relaxed) => {
+                Poll::Ready(Some(Err(Status::resource_exhausted(format!(
+                    "stream was disconnected, the client fell behind by more than {} messages \
+                     and dropped {dropped_events} events",
+                    this.capacity
+                )))))
+            }
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use tokio_stream::StreamExt;
+
+    use super::*;
+
+    fn options(policy: BackpressurePolicy) -> StreamOptions {
+        StreamOptions {
+            backpressure_policy: policy.into(),
+            capacity: 2,
+            block_timeout_ms: 10,
+            resume_after: None,
+        }
+    }
+
+    fn batch(len: usize) -> EventBatch {
+        EventBatch {
+            events: vec![Event::default(); len],
+        }
+    }
+
+    async fn next(stream: &mut SubscriptionStream<EventBatch>) -> Result<EventBatch, Status> {
+        stream.next().await.unwrap()
+    }
+
+    async fn status(stream: &mut SubscriptionStream<EventBatch>) -> StreamStatus {
+        let batch = next(stream).await.unwrap();
+        match batch.events.as_slice() {
+            [Event {
+                event_data: Some(EventData::StreamStatus(status)),
+                ..
+            }] => *status,
+            other => panic!("expected a stream status, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_the_freshest() {
+        let subscriptions = Subscriptions::default();
+        let mut stream = subscriptions
+            .subscribe(&options(BackpressurePolicy::Unspecified))
+            .unwrap();
+
+        assert_eq!(
+            status(&mut stream).await,
+            StreamStatus {
+                backpressure_policy: BackpressurePolicy::DropOldest.into(),
+                capacity: 2,
+                dropped_events: 0,
+                late_events: 0,
+                daemon_instance: *DAEMON_INSTANCE,
+            }
+        );
+
+        for len in 1..=3 {
+            subscriptions.publish(&batch(len)).await;
+        }
+
+        assert_eq!(status(&mut stream).await.dropped_events, 1);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 2);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn drop_newest_keeps_the_first() {
+        let subscriptions = Subscriptions::default();
+        let mut stream = subscriptions
+            .subscribe(&options(BackpressurePolicy::DropNewest))
+            .unwrap();
+        status(&mut stream).await;
+
+        for len in 1..=3 {
+            subscriptions.publish(&batch(len)).await;
+        }
+
+        assert_eq!(status(&mut stream).await.dropped_events, 3);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 1);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_the_client() {
+        let subscriptions = Arc::new(Subscriptions::default());
+        let mut stream = subscriptions
+            .subscribe(&StreamOptions {
+                block_timeout_ms: 60_000,
+                ..options(BackpressurePolicy::Block)
+            })
+            .unwrap();
+        status(&mut stream).await;
+
+        let publisher = tokio::spawn({
+            let subscriptions = subscriptions.clone();
+            async move {
+                for len in 1..=4 {
+                    subscriptions.publish(&batch(len)).await;
+                }
+            }
+        });
+
+        for len in 1..=4 {
+            assert_eq!(next(&mut stream).await.unwrap().events.len(), len);
+        }
+        publisher.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn block_drops_after_the_timeout() {
+        let subscriptions = Subscriptions::default();
+        let mut stream = subscriptions
+            .subscribe(&options(BackpressurePolicy::Block))
+            .unwrap();
+        status(&mut stream).await;
+
+        for len in 1..=3 {
+            subscriptions.publish(&batch(len)).await;
+        }
+
+        assert_eq!(status(&mut stream).await.dropped_events, 3);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn block_drops_until_the_client_caught_up() {
+        let subscriptions = Subscriptions::default();
+        let mut stream = subscriptions
+            .subscribe(&options(BackpressurePolicy::Block))
+            .unwrap();
+        status(&mut stream).await;
+
+        for len in 1..=4 {
+            subscriptions.publish(&batch(len)).await;
+        }
+        assert_eq!(status(&mut stream).await.dropped_events, 7);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 1);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 2);
+
+        // Waits for the client again once it took all pending messages
+        for len in 5..=7 {
+            subscriptions.publish(&batch(len)).await;
+        }
+        assert_eq!(status(&mut stream).await.dropped_events, 14);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn blocked_clients_do_not_hold_up_the_others() {
+        let subscriptions = Subscriptions::default();
+        let blocked = subscriptions
+            .subscribe(&StreamOptions {
+                block_timeout_ms: 100,
+                ..options(BackpressurePolicy::Block)
+            })
+            .unwrap();
+        let mut stream = subscriptions
+            .subscribe(&options(BackpressurePolicy::DropOldest))
+            .unwrap();
+        status(&mut stream).await;
+
+        let start = Instant::now();
+        for len in 1..=10 {
+            subscriptions.publish(&batch(len)).await;
+        }
+        // Without a budget every message after the first two would wait
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        assert_eq!(status(&mut stream).await.dropped_events, 36);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 9);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 10);
+        drop(blocked);
+    }
+
+    #[tokio::test]
+    async fn disconnect_ends_the_stream() {
+        let subscriptions = Subscriptions::default();
+        let mut stream = subscriptions
+            .subscribe(&options(BackpressurePolicy::Disconnect))
+            .unwrap();
+        status(&mut stream).await;
+
+        for len in 1..=3 {
+            subscriptions.publish(&batch(len)).await;
+        }
+        assert!(subscriptions.state.lock().unwrap().subscribers.is_empty());
+
+        assert_eq!(status(&mut stream).await.dropped_events, 3);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 1);
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 2);
+        let error = next(&mut stream).await.unwrap_err();
+        assert_eq!(error.code(), tonic::Code::ResourceExhausted);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn late_events_are_counted_from_the_subscription() {
+        let subscriptions = Subscriptions::default();
+        subscriptions.count_late_events(5);
+
+        let mut stream = subscriptions
+            .subscribe(&options(BackpressurePolicy::DropOldest))
+            .unwrap();
+        assert_eq!(status(&mut stream).await.late_events, 0);
+
+        subscriptions.count_late_events(2);
+        subscriptions.publish(&batch(1)).await;
+
+        let status = status(&mut stream).await;
+        assert_eq!((status.dropped_events, status.late_events), (0, 2));
+        assert_eq!(next(&mut stream).await.unwrap().events.len(), 1);
+    }
+
+    fn sequenced_batch(sequences: impl IntoIterator<Item = u64>) -> EventBatch {
+        EventBatch {
+            events: sequences
+                .into_iter()
+                .map(|sequence| Event {
+                    sequence,
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    fn sequences(batch: EventBatch) -> Vec<u64> {
+        batch.events.iter().map(|event| event.sequence).collect()
+    }
+
+    #[tokio::test]
+    async fn resumed_streams_get_the_buffered_events_first() {
+        let subscriptions = Subscriptions::new(4);
+        subscriptions.publish(&sequenced_batch(1..=2)).await;
+        subscriptions.publish(&sequenced_batch(3..=4)).await;
+        subscriptions.publish(&sequenced_batch(5..=6)).await;
+
+        let mut stream = subscriptions
+            .subscribe(&StreamOptions {
+                resume_after: Some(2),
+                ..options(BackpressurePolicy::DropOldest)
+            })
+            .unwrap();
+        subscriptions.publish(&sequenced_batch(7..=8)).await;
+
+        status(&mut stream).await;
+        assert_eq!(sequences(next(&mut stream).await.unwrap()), vec![3, 4]);
+        assert_eq!(sequences(next(&mut stream).await.unwrap()), vec![5, 6]);
+        assert_eq!(sequences(next(&mut stream).await.unwrap()), vec![7, 8]);
+    }
+
+    #[tokio::test]
+    async fn replay_keeps_the_latest_events() {
+        let subscriptions = Subscriptions::new(3);
+        subscriptions.publish(&sequenced_batch(1..=2)).await;
+        subscriptions.publish(&sequenced_batch(3..=4)).await;
+
+        // The first batch no longer fits, the stream misses event 2
+        let mut stream = subscriptions
+            .subscribe(&StreamOptions {
+                resume_after: Some(1),
+                ..options(BackpressurePolicy::DropOldest)
+            })
+            .unwrap();
+
+        status(&mut stream).await;
+        assert_eq!(sequences(next(&mut stream).await.unwrap()), vec![3, 4]);
+
+        // Without resuming only new events are sent
+        let mut fresh = subscriptions
+            .subscribe(&options(BackpressurePolicy::DropOldest))
+            .unwrap();
+        subscriptions.publish(&sequenced_batch([5])).await;
+
+        status(&mut fresh).await;
+        assert_eq!(sequences(next(&mut fresh).await.unwrap()), vec![5]);
+    }
+
+    #[tokio::test]
+    async fn dropped_clients_are_removed() {
+        let subscriptions = Subscriptions::default();
+        let stream = subscriptions
+            .subscribe(&options(BackpressurePolicy::DropOldest))
+            .unwrap();
+        drop(stream);
+
+        subscriptions.publish(&batch(1)).await;
+        assert!(subscriptions.state.lock().unwrap().subscribers.is_empty());
+    }
+
+    #[test]
+    fn unknown_policies_are_rejected() {
+        let subscriptions = Subscriptions::<Event>::default();
+        let options = StreamOptions {
+            backpressure_policy: 42,
+            ..Default::default()
+        };
+        let Err(error) = subscriptions.subscribe(&options) else {
+            panic!("unknown policy was accepted");
+        };
+        assert!(matches!(
+            error,
+            SubscribeError::UnknownBackpressurePolicy(42)
+        ));
+        assert_eq!(Status::from(error).code(), tonic::Code::InvalidArgument);
+    }
+}
@@ -3,16 +3,22 @@
 //
 // SPDX-License-Identifier: MIT
 
+use std::{sync::Arc, time::Duration};
+
 use ractor::{Actor, ActorCell, ActorProcessingErr, ActorRef, SupervisionEvent};
-use shared::events::Event;
-use tonic::Status;
+use shared::events::{Event, EventBatch};
 use tracing::error;
 
 use crate::{
     collector::{
-        event_dispatcher::{EventDispatcher, EventDispatcherState},
+        event_dispatcher::{EventDispatcher, EventDispatcherArguments, EventDispatcherMsg},
+        flight_recorder::FlightRecorder,
+        journal::Journal,
         ring_buf::{RingBufCollector, RingBufCollectorArguments},
+        subscriptions::Subscriptions,
+        time_series_store::TimeSeriesStore,
     },
+    features::GlobFilters,
     registry::{EbpfEventRegistry, OwnedRingBuf, RegistryItem},
 };
 
@@ -20,21 +26,44 @@ pub struct CollectorSupervisor;
 
 pub struct CollectorSupervisorState {
     registry: EbpfEventRegistry,
-    event_colletor: ActorCell,
-    events: ActorRef<Event>,
+    /// One collector for each ring buffer, in the order of the registry
+    event_collectors: Vec<ActorCell>,
+    events: ActorRef<EventDispatcherMsg>,
 }
 
 pub struct CollectorSupervisorArguments {
     registry: EbpfEventRegistry,
-    sender: async_broadcast::Sender<Result<Event, Status>>,
+    subscriptions: Arc<Subscriptions<Event>>,
+    batch_subscriptions: Arc<Subscriptions<EventBatch>>,
+    reorder_window: Duration,
+    flight_recorder: Option<Arc<FlightRecorder>>,
+    journal: Option<Arc<Journal>>,
+    time_series: Arc<TimeSeriesStore>,
+    globs: Arc<GlobFilters>,
 }
 
 impl CollectorSupervisorArguments {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         registry: EbpfEventRegistry,
-        sender: async_broadcast::Sender<Result<Event, Status>>,
+        subscriptions: Arc<Subscriptions<Event>>,
+        batch_subscriptions: Arc<Subscriptions<EventBatch>>,
+        reorder_window: Duration,
+        flight_recorder: Option<Arc<FlightRecorder>>,
+        journal: Option<Arc<Journal>>,
+        time_series: Arc<TimeSeriesStore>,
+        globs: Arc<GlobFilters>,
     ) -> Self {
-        Self { registry, sender }
+        Self {
+            registry,
+            subscriptions,
+            batch_subscriptions,
+            reorder_window,
+            flight_recorder,
+            journal,
+            time_series,
+            globs,
+        }
     }
 }
 
@@ -51,21 +80,28 @@ impl Actor for CollectorSupervisor {
         let (events, _) = Actor::spawn_linked(
             None,
             EventDispatcher,
-            EventDispatcherState::new(args.sender),
+            EventDispatcherArguments::new(
+                args.subscriptions,
+                args.batch_subscriptions,
+                args.reorder_window,
+                args.flight_recorder,
+                args.journal,
+                args.time_series,
+                args.globs,
+            ),
             myself.get_cell(),
         )
         .await?;
-        let event_colletor = start_collector(
-            args.registry.events.clone(),
-            events.clone(),
-            myself.get_cell(),
-        )
-        .await?
-        .get_cell();
+        let mut event_collectors = Vec::new();
+        for ring_buf in &args.registry.ring_bufs {
+            let collector =
+                start_collector(ring_buf.clone(), events.clone(), myself.get_cell()).await?;
+            event_collectors.push(collector.get_cell());
+        }
 
         Ok(CollectorSupervisorState {
             registry: args.registry.clone(),
-            event_colletor,
+            event_collectors,
             events,
         })
     }
@@ -77,10 +113,15 @@ impl Actor for CollectorSupervisor {
         state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
         if let SupervisionEvent::ActorFailed(actor_cell, error) = message {
-            if actor_cell == state.event_colletor {
+            let failed = state
+                .event_collectors
+                .iter()
+                .position(|collector| *collector == actor_cell);
+
+            if let Some(i) = failed {
                 error!("Collector {:?} failed with {:?}", actor_cell, error);
-                state.event_colletor = start_collector(
-                    state.registry.events.clone(),
+                state.event_collectors[i] = start_collector(
+                    state.registry.ring_bufs[i].clone(),
                     state.events.clone(),
                     myself.get_cell(),
                 )
@@ -101,7 +142,7 @@ impl Actor for CollectorSupervisor {
 
 async fn start_collector(
     item: RegistryItem<OwnedRingBuf>,
-    event_actor: ActorRef<Event>,
+    event_actor: ActorRef<EventDispatcherMsg>,
     supervisor: ActorCell,
 ) -> Result<ActorRef<()>, ActorProcessingErr>
 where
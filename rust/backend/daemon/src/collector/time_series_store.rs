@@ -0,0 +1,474 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use shared::events::{
+    event::EventData, log_event::LogEventData, time_series_event::EventKind, Event, EventSeries,
+    EventSeriesList, SeriesPoint, TimeSeriesQuery,
+};
+use thiserror::Error;
+use tonic::Status;
+
+use super::{clock, timestamp};
+
+#[derive(Debug, Error)]
+pub enum TimeSeriesError {
+    #[error("resolution of {requested}s is not kept, available are {available:?}")]
+    UnknownResolution { requested: u32, available: Vec<u64> },
+    #[error("no resolutions are kept")]
+    NoResolutions,
+}
+
+impl From<TimeSeriesError> for Status {
+    fn from(err: TimeSeriesError) -> Self {
+        match err {
+            TimeSeriesError::UnknownResolution { .. } => Status::invalid_argument(err.to_string()),
+            TimeSeriesError::NoResolutions => Status::failed_precondition(err.to_string()),
+        }
+    }
+}
+
+/// Width of the buckets of a resolution and how long they are kept
+#[derive(Clone, Copy, Debug)]
+pub struct Resolution {
+    pub width: Duration,
+    pub retention: Duration,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Aggregate {
+    count: u64,
+    sum: u64,
+}
+
+type Key = (u32, EventKind);
+
+/// Events of processes which no longer fit into a series are counted for this pid
+const OTHER_PID: u32 = 0;
+
+/// Buckets by their start in seconds since the unix epoch, only keys with
+/// events are stored.
+///
+/// A bucket is dropped once the retention has passed since it was created,
+/// measured with `CLOCK_BOOTTIME`. So an event with a timestamp far in the
+/// future does not drop the history, and events after the wall clock was set
+/// back are still counted.
+struct Series {
+    resolution: Resolution,
+    buckets: BTreeMap<u64, HashMap<Key, Aggregate>>,
+    /// Boot time at the creation of each bucket and its start, oldest first
+    created: VecDeque<(Duration, u64)>,
+    /// Share of each bucket in the keys of the series
+    max_keys_per_bucket: usize,
+}
+
+impl Series {
+    fn width_secs(&self) -> u64 {
+        self.resolution.width.as_secs().max(1)
+    }
+
+    /// Drops the buckets created longer than the retention before `now`, a boot time
+    fn expire(&mut self, now: Duration) {
+        while let Some((created, start)) = self.created.front() {
+            if *created + self.resolution.retention >= now {
+                break;
+            }
+
+            self.buckets.remove(start);
+            self.created.pop_front();
+        }
+    }
+
+    fn add(&mut self, now: Duration, at: Duration, key: Key, sum: u64) {
+        self.expire(now);
+
+        let width = self.width_secs();
+        let start = at.as_secs() / width * width;
+        if !self.buckets.contains_key(&start) {
+            self.created.push_back((now, start));
+        }
+
+        let bucket = self.buckets.entry(start).or_default();
+        let key = if bucket.contains_key(&key) || bucket.len() < self.max_keys_per_bucket {
+            key
+        } else {
+            (OTHER_PID, key.1)
+        };
+        let aggregate = bucket.entry(key).or_default();
+        aggregate.count += 1;
+        aggregate.sum += sum;
+    }
+}
+
+fn max_keys_per_bucket(resolution: Resolution, max_keys: usize) -> usize {
+    let width = resolution.width.as_secs().max(1);
+    let buckets = (resolution.retention.as_secs() / width).max(1);
+    (max_keys / buckets as usize).max(1)
+}
+
+/// The value summed up for an event besides counting it
+fn value(event: &Event) -> Option<(Key, u64)> {
+    let Some(EventData::Log(log)) = &event.event_data else {
+        return None;
+    };
+    let pid = log.context.as_ref()?.pid;
+
+    let sum = match log.log_event_data.as_ref()? {
+        LogEventData::Write(write) => write.bytes_written,
+        LogEventData::Blocking(blocking) => blocking
+            .duration
+            .map_or(0, |duration| Duration::from(duration).as_nanos() as u64),
+        LogEventData::GarbageCollect(gc) => gc.duration_ns,
+        _ => 0,
+    };
+
+    Some(((pid, EventKind::from(event)), sum))
+}
+
+/// Counts and sums of the events of each process and kind, kept in several
+/// resolutions so coarse history can be kept much longer than fine history.
+///
+/// Each resolution keeps at most `max_keys` pairs of process and kind, split
+/// evenly among the buckets of its retention. Once the share of a bucket is
+/// used up, the events of further processes in it are counted for pid 0, which
+/// adds at most one key per kind and bucket.
+#[derive(Default)]
+pub struct TimeSeriesStore {
+    series: Mutex<Vec<Series>>,
+}
+
+impl TimeSeriesStore {
+    /// The resolutions can be in any order
+    pub fn new(resolutions: &[Resolution], max_keys: usize) -> Self {
+        let mut series: Vec<_> = resolutions
+            .iter()
+            .map(|resolution| Series {
+                resolution: *resolution,
+                buckets: BTreeMap::new(),
+                created: VecDeque::new(),
+                max_keys_per_bucket: max_keys_per_bucket(*resolution, max_keys),
+            })
+            .collect();
+        series.sort_by_key(|series| series.resolution.width);
+
+        Self {
+            series: Mutex::new(series),
+        }
+    }
+
+    pub fn record(&self, event: &Event) {
+        self.record_at(event, clock::boot_time());
+    }
+
+    /// `now` is the current boot time
+    fn record_at(&self, event: &Event, now: Duration) {
+        let (Some(at), Some((key, sum))) = (timestamp(event), value(event)) else {
+            return;
+        };
+
+        for series in self.series.lock().unwrap().iter_mut() {
+            series.add(now, at, key, sum);
+        }
+    }
+
+    /// Without a resolution, the finest one which still keeps the start of the
+    /// range is used
+    pub fn query(&self, query: &TimeSeriesQuery) -> Result<EventSeriesList, TimeSeriesError> {
+        let mut all_series = self.series.lock().unwrap();
+        let boot_time = clock::boot_time();
+        for series in all_series.iter_mut() {
+            series.expire(boot_time);
+        }
+
+        let series = if query.resolution_secs != 0 {
+            all_series
+                .iter()
+                .find(|series| series.resolution.width.as_secs() == query.resolution_secs as u64)
+                .ok_or_else(|| TimeSeriesError::UnknownResolution {
+                    requested: query.resolution_secs,
+                    available: all_series
+                        .iter()
+                        .map(|series| series.resolution.width.as_secs())
+                        .collect(),
+                })?
+        } else {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default();
+            let start = query.start.map_or(now, Duration::from);
+            all_series
+                .iter()
+                .find(|series| now.saturating_sub(series.resolution.retention) <= start)
+                .or(all_series.last())
+                .ok_or(TimeSeriesError::NoResolutions)?
+        };
+
+        let width = series.width_secs();
+        let start = query
+            .start
+            .map_or(0, |start| Duration::from(start).as_secs() / width * width);
+        let end = query
+            .end
+            .map_or(u64::MAX, |end| Duration::from(end).as_secs());
+
+        let mut points: BTreeMap<Key, Vec<SeriesPoint>> = BTreeMap::new();
+        for (bucket_start, bucket) in series.buckets.range(start..end) {
+            for ((pid, kind), aggregate) in bucket {
+                if !query.kinds.is_empty() && !query.kinds.contains(&(*kind as i32)) {
+                    continue;
+                }
+                if !query.pids.is_empty() && !query.pids.contains(pid) {
+                    continue;
+                }
+
+                points.entry((*pid, *kind)).or_default().push(SeriesPoint {
+                    start: Some(Duration::from_secs(*bucket_start).into()),
+                    count: aggregate.count,
+                    sum: aggregate.sum,
+                });
+            }
+        }
+
+        Ok(EventSeriesList {
+            resolution_secs: width as u32,
+            series: points
+                .into_iter()
+                .map(|((pid, kind), points)| EventSeries {
+                    pid,
+                    event_kind: kind.into(),
+                    points,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::events::{BlockingEvent, EventContext, LogEvent, WriteEvent};
+
+    use super::*;
+
+    const SECOND: Resolution = Resolution {
+        width: Duration::from_secs(1),
+        retention: Duration::from_secs(10),
+    };
+    const MINUTE: Resolution = Resolution {
+        width: Duration::from_secs(60),
+        retention: Duration::from_secs(3600),
+    };
+
+    fn event(secs: u64, pid: u32, data: LogEventData) -> Event {
+        Event {
+            event_data: Some(EventData::Log(LogEvent {
+                context: Some(EventContext {
+                    pid,
+                    timestamp: Some(Duration::from_secs(secs).into()),
+                    ..Default::default()
+                }),
+                log_event_data: Some(data),
+            })),
+            sequence: 0,
+        }
+    }
+
+    fn write(secs: u64, pid: u32, bytes_written: u64) -> Event {
+        event(
+            secs,
+            pid,
+            LogEventData::Write(WriteEvent {
+                bytes_written,
+                ..Default::default()
+            }),
+        )
+    }
+
+    /// Records the event `secs` seconds after the current boot time
+    fn record_at(store: &TimeSeriesStore, event: Event, secs: u64) {
+        store.record_at(&event, clock::boot_time() + Duration::from_secs(secs));
+    }
+
+    fn points(list: &EventSeriesList) -> Vec<(u32, u64, u64, u64)> {
+        list.series
+            .iter()
+            .flat_map(|series| {
+                series.points.iter().map(|point| {
+                    let start = Duration::from(point.start.unwrap()).as_secs();
+                    (series.pid, start, point.count, point.sum)
+                })
+            })
+            .collect()
+    }
+
+    fn query(resolution_secs: u32) -> TimeSeriesQuery {
+        TimeSeriesQuery {
+            resolution_secs,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn aggregates_per_resolution() {
+        let store = TimeSeriesStore::new(&[MINUTE, SECOND], usize::MAX);
+        store.record(&write(60, 1, 10));
+        store.record(&write(61, 1, 20));
+        store.record(&write(61, 1, 30));
+
+        let seconds = store.query(&query(1)).unwrap();
+        assert_eq!(seconds.resolution_secs, 1);
+        assert_eq!(points(&seconds), vec![(1, 60, 1, 10), (1, 61, 2, 50)]);
+
+        let minutes = store.query(&query(60)).unwrap();
+        assert_eq!(points(&minutes), vec![(1, 60, 3, 60)]);
+    }
+
+    #[test]
+    fn drops_buckets_after_their_retention() {
+        let store = TimeSeriesStore::new(&[SECOND, MINUTE], usize::MAX);
+        record_at(&store, write(100, 1, 1), 100);
+        record_at(&store, write(120, 1, 1), 120);
+
+        assert_eq!(
+            points(&store.query(&query(1)).unwrap()),
+            vec![(1, 120, 1, 1)]
+        );
+        assert_eq!(
+            points(&store.query(&query(60)).unwrap()),
+            vec![(1, 60, 1, 1), (1, 120, 1, 1)]
+        );
+    }
+
+    #[test]
+    fn expires_by_the_daemon_time_not_the_event_time() {
+        let store = TimeSeriesStore::new(&[SECOND], usize::MAX);
+        record_at(&store, write(100, 1, 1), 0);
+        record_at(&store, write(1_000_000, 1, 1), 1);
+        // The wall clock was set back
+        record_at(&store, write(50, 1, 1), 5);
+        record_at(&store, write(101, 1, 1), 6);
+
+        let starts = |store: &TimeSeriesStore| -> Vec<_> {
+            points(&store.query(&query(1)).unwrap())
+                .into_iter()
+                .map(|(_, start, _, _)| start)
+                .collect()
+        };
+        assert_eq!(starts(&store), vec![50, 100, 101, 1_000_000]);
+
+        record_at(&store, write(102, 1, 1), 12);
+        assert_eq!(starts(&store), vec![50, 101, 102]);
+    }
+
+    #[test]
+    fn counts_further_processes_as_other_once_full() {
+        // Two keys for each of the ten buckets
+        let store = TimeSeriesStore::new(&[SECOND], 20);
+        for pid in [1, 2, 3, 4] {
+            record_at(&store, write(1, pid, 1), 1);
+        }
+        // Processes which already have a key keep it
+        record_at(&store, write(1, 1, 1), 1);
+        // Another bucket has keys of its own
+        record_at(&store, write(2, 5, 1), 1);
+
+        assert_eq!(
+            points(&store.query(&query(1)).unwrap()),
+            vec![(0, 1, 2, 2), (1, 1, 2, 2), (2, 1, 1, 1), (5, 2, 1, 1)]
+        );
+    }
+
+    #[test]
+    fn processes_keep_their_series_in_every_bucket() {
+        let hundred_seconds = Resolution {
+            width: Duration::from_secs(1),
+            retention: Duration::from_secs(100),
+        };
+        let store = TimeSeriesStore::new(&[hundred_seconds], 200);
+
+        for secs in 0..100 {
+            // Only the first two processes of each bucket have a key
+            for pid in [1000 + secs as u32, 1, 2000 + secs as u32, 3000] {
+                record_at(&store, write(secs, pid, 1), 0);
+            }
+        }
+
+        let list = store.query(&query(1)).unwrap();
+        let count = |pid| {
+            list.series
+                .iter()
+                .find(|series| series.pid == pid)
+                .map_or(0, |series| series.points.len())
+        };
+        assert_eq!(count(1), 100);
+        assert_eq!(count(OTHER_PID), 100);
+        assert_eq!(count(3000), 0);
+    }
+
+    #[test]
+    fn filters_by_time_kind_and_pid() {
+        let store = TimeSeriesStore::new(&[SECOND], usize::MAX);
+        store.record(&write(1, 1, 1));
+        store.record(&write(2, 2, 1));
+        store.record(&write(3, 1, 1));
+        store.record(&event(
+            2,
+            1,
+            LogEventData::Blocking(BlockingEvent {
+                duration: Some(Duration::from_millis(5).into()),
+                ..Default::default()
+            }),
+        ));
+
+        let list = store
+            .query(&TimeSeriesQuery {
+                start: Some(Duration::from_secs(2).into()),
+                end: Some(Duration::from_secs(3).into()),
+                resolution_secs: 1,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(points(&list), vec![(1, 2, 1, 5_000_000), (2, 2, 1, 1)]);
+
+        let list = store
+            .query(&TimeSeriesQuery {
+                kinds: vec![EventKind::Write as i32],
+                pids: vec![1],
+                ..query(1)
+            })
+            .unwrap();
+        assert_eq!(points(&list), vec![(1, 1, 1, 1), (1, 3, 1, 1)]);
+    }
+
+    #[test]
+    fn picks_the_finest_resolution_which_keeps_the_start() {
+        let store = TimeSeriesStore::new(&[SECOND, MINUTE], usize::MAX);
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+
+        let recent = TimeSeriesQuery {
+            start: Some((now - Duration::from_secs(5)).into()),
+            ..Default::default()
+        };
+        assert_eq!(store.query(&recent).unwrap().resolution_secs, 1);
+
+        let older = TimeSeriesQuery {
+            start: Some((now - Duration::from_secs(60)).into()),
+            ..Default::default()
+        };
+        assert_eq!(store.query(&older).unwrap().resolution_secs, 60);
+
+        let error = store.query(&query(10)).unwrap_err();
+        assert!(matches!(
+            error,
+            TimeSeriesError::UnknownResolution { requested: 10, .. }
+        ));
+        assert_eq!(Status::from(error).code(), tonic::Code::InvalidArgument);
+    }
+}
@@ -17,6 +17,78 @@ pub const ZIOFA_EBPF_PATH: &str = "/sys/fs/bpf/ziofa";
 pub const _DEFAULT_TIMEFRAME: time::Duration = time::Duration::from_secs(1);
 pub const TIMESERIES_LENGTH: usize = 40;
 pub const INDEX_PATH: &str = "/data/local/tmp/index";
+pub const PACKAGES_LIST_PATH: &str = "/data/system/packages.list";
+pub const CGROUP_V2_PATH: &str = "/sys/fs/cgroup";
+
+// Used for syscalls without a matching threshold rule, in nanoseconds
+pub const DEFAULT_BLOCKING_THRESHOLD: u64 = 32_000_000;
+
+// Sizes of the kernel caches for the context of threads and processes
+pub const DEFAULT_TASK_INFO_CACHE_SIZE: u32 = 10240;
+pub const DEFAULT_PROCESS_INFO_CACHE_SIZE: u32 = 1024;
+
+// Number of entries of each filter map in the kernel
+pub const DEFAULT_FILTER_CAPACITY: u32 = 256;
+
+// Events are sent to clients of batched streams once a batch is full
+// or the time window has passed
+pub const EVENT_BATCH_SIZE: usize = 256;
+pub const EVENT_BATCH_WINDOW: time::Duration = time::Duration::from_millis(50);
+
+// Pending messages of a stream subscription which does not set a capacity
+pub const DEFAULT_STREAM_CAPACITY: u32 = 8192;
+pub const DEFAULT_BATCHED_STREAM_CAPACITY: u32 = 64;
+// Events kept for clients which resume their stream after a reconnect
+pub const DEFAULT_REPLAY_BUFFER_SIZE: usize = 16384;
+// Longest time the collector waits for a blocking subscription per message
+pub const DEFAULT_STREAM_BLOCK_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+// Upper bound of the encoded events kept by the flight recorder, and the
+// directory of its dumps
+pub const DEFAULT_FLIGHT_RECORDER_SIZE: usize = 64 * 1024 * 1024;
+pub const FLIGHT_RECORDER_DUMP_PATH: &str = "/data/local/tmp/ziofa/dumps";
+// Dumps kept in the dump directory, the oldest are removed first
+pub const DEFAULT_MAX_DUMPS: usize = 16;
+// A triggered dump waits this long, at most half the window, so it also covers
+// the events after the incident
+pub const FLIGHT_RECORDER_DUMP_DELAY: time::Duration = time::Duration::from_secs(5);
+
+// Events held by the reorder window at most, the oldest one is sent early
+// once there are more
+pub const REORDER_BUFFER_MAX_EVENTS: usize = 65536;
+
+// Limits of the journal on disk and of each of its segments
+pub const DEFAULT_JOURNAL_MAX_SIZE: u64 = 1024 * 1024 * 1024;
+pub const DEFAULT_JOURNAL_RETENTION_HOURS: u64 = 72;
+pub const JOURNAL_SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+pub const JOURNAL_SEGMENT_DURATION: time::Duration = time::Duration::from_secs(600);
+// Events are written to the journal at most this long after the reorder window
+// and the batch window, e.g. when the journal writer falls behind
+pub const JOURNAL_DELAY_MARGIN: time::Duration = time::Duration::from_secs(10);
+// Batches of events waiting to be written to the journal, further batches are
+// dropped
+pub const JOURNAL_WRITER_QUEUE_SIZE: usize = 256;
+// Events read from the journal ahead of the client of a query
+pub const JOURNAL_QUERY_BUFFER_SIZE: usize = 1024;
+
+// Width of the buckets of the time series of events and how long they are kept
+pub const TIME_SERIES_RESOLUTIONS: [(time::Duration, time::Duration); 3] = [
+    (time::Duration::from_secs(1), time::Duration::from_secs(3600)),
+    (time::Duration::from_secs(10), time::Duration::from_secs(24 * 3600)),
+    (time::Duration::from_secs(60), time::Duration::from_secs(7 * 24 * 3600)),
+];
+// Pairs of process and kind kept by each resolution of the time series, split
+// evenly among its buckets, further processes are counted for pid 0. An entry
+// takes about 32 bytes.
+pub const TIME_SERIES_MAX_KEYS: usize = 1 << 20;
+
+// Event timestamps are converted with an offset to the wall clock, which is measured
+// again after this time to follow changes of the wall clock
+pub const CLOCK_CALIBRATION_INTERVAL: time::Duration = time::Duration::from_secs(10);
+
+// Sizes of the ring buffers for events in bytes
+pub const DEFAULT_RING_BUF_SIZE: u32 = 8192 * 1024;
+pub const DEFAULT_PRIORITY_RING_BUF_SIZE: u32 = 1024 * 1024;
 
 // Update via downloading the submodules in rust/garbage-collection
 // and running `cargo run --bin parser --features cli`
@@ -5,16 +5,20 @@
 //
 // SPDX-License-Identifier: MIT
 
+use std::{collections::HashMap, iter};
+
 use aya::{
     programs::{raw_trace_point::RawTracePointLink, RawTracePoint},
     EbpfError,
 };
+use ebpf_types::BlockingThresholdKey;
 use ractor::ActorRef;
 use shared::config::BlockingConfig;
 
 use crate::{
-    features::Feature,
-    registry::{EbpfRegistry, RegistryGuard},
+    constants::DEFAULT_BLOCKING_THRESHOLD,
+    features::{Feature, HashMapUpdate},
+    registry::{EbpfRegistry, OwnedHashMap, RegistryGuard},
     symbols::actors::SymbolActorMsg,
 };
 
@@ -23,6 +27,8 @@ pub struct BlockingFeature {
     sys_exit_blocking: RegistryGuard<RawTracePoint>,
     sys_enter_blocking_link: Option<RawTracePointLink>,
     sys_exit_blocking_link: Option<RawTracePointLink>,
+    thresholds: RegistryGuard<OwnedHashMap<BlockingThresholdKey, u64>>,
+    ignored_syscalls: RegistryGuard<OwnedHashMap<u32, u8>>,
 }
 
 impl BlockingFeature {
@@ -32,6 +38,8 @@ impl BlockingFeature {
             sys_exit_blocking: registry.program.sys_exit_blocking.take(),
             sys_enter_blocking_link: None,
             sys_exit_blocking_link: None,
+            thresholds: registry.config.blocking_thresholds.take(),
+            ignored_syscalls: registry.config.blocking_ignored_syscalls.take(),
         }
     }
 
@@ -53,6 +61,40 @@ impl BlockingFeature {
         let _ = self.sys_enter_blocking_link.take();
         let _ = self.sys_exit_blocking_link.take();
     }
+
+    fn update_thresholds(&mut self, config: &BlockingConfig) -> Result<(), EbpfError> {
+        self.thresholds.update(threshold_rules(config))?;
+        self.ignored_syscalls.update(
+            config
+                .ignored_syscalls
+                .iter()
+                .map(|syscall_id| (*syscall_id, 1))
+                .collect(),
+        )
+    }
+}
+
+/// The global threshold is stored as the rule matching any pid and any syscall.
+fn threshold_rules(config: &BlockingConfig) -> HashMap<BlockingThresholdKey, u64> {
+    let global = (
+        BlockingThresholdKey::new(
+            BlockingThresholdKey::ANY_PID,
+            BlockingThresholdKey::ANY_SYSCALL,
+        ),
+        config.threshold.unwrap_or(DEFAULT_BLOCKING_THRESHOLD),
+    );
+
+    let rules = config.threshold_rules.iter().map(|rule| {
+        (
+            BlockingThresholdKey::new(
+                rule.pid.unwrap_or(BlockingThresholdKey::ANY_PID),
+                rule.syscall_id.unwrap_or(BlockingThresholdKey::ANY_SYSCALL),
+            ),
+            rule.threshold,
+        )
+    });
+
+    iter::once(global).chain(rules).collect()
 }
 
 impl Feature for BlockingFeature {
@@ -62,7 +104,8 @@ impl Feature for BlockingFeature {
     }
 
     async fn apply(&mut self, config: &Option<Self::Config>) -> Result<(), EbpfError> {
-        if config.is_some() {
+        if let Some(config) = config {
+            self.update_thresholds(config)?;
             self.attach()?;
         } else {
             self.detach();
@@ -70,3 +113,51 @@ impl Feature for BlockingFeature {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use shared::config::BlockingThresholdRule;
+
+    use super::*;
+
+    #[test]
+    fn threshold_rules_with_wildcards() {
+        let config = BlockingConfig {
+            threshold: None,
+            threshold_rules: vec![
+                BlockingThresholdRule {
+                    syscall_id: Some(74),
+                    pid: None,
+                    threshold: 1_000_000,
+                },
+                BlockingThresholdRule {
+                    syscall_id: Some(74),
+                    pid: Some(1234),
+                    threshold: 2_000_000,
+                },
+                BlockingThresholdRule {
+                    syscall_id: None,
+                    pid: Some(1234),
+                    threshold: 3_000_000,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let rules = threshold_rules(&config);
+
+        let any_pid = BlockingThresholdKey::ANY_PID;
+        let any_syscall = BlockingThresholdKey::ANY_SYSCALL;
+        assert_eq!(rules.len(), 4);
+        assert_eq!(
+            rules[&BlockingThresholdKey::new(any_pid, any_syscall)],
+            DEFAULT_BLOCKING_THRESHOLD
+        );
+        assert_eq!(rules[&BlockingThresholdKey::new(any_pid, 74)], 1_000_000);
+        assert_eq!(rules[&BlockingThresholdKey::new(1234, 74)], 2_000_000);
+        assert_eq!(
+            rules[&BlockingThresholdKey::new(1234, any_syscall)],
+            3_000_000
+        );
+    }
+}
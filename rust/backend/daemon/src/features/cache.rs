@@ -0,0 +1,49 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use aya::{
+    programs::{raw_trace_point::RawTracePointLink, RawTracePoint},
+    EbpfError,
+};
+
+use crate::registry::{EbpfRegistry, RegistryGuard};
+
+/// Keeps the cached thread and process context in the kernel up to date.
+/// This is not a feature, every feature relies on the cache, so it is
+/// attached for the whole lifetime of the daemon.
+pub struct CacheEviction {
+    _evict_cache_exec: RegistryGuard<RawTracePoint>,
+    _evict_cache_exit: RegistryGuard<RawTracePoint>,
+    _evict_cache_rename: RegistryGuard<RawTracePoint>,
+    _links: [RawTracePointLink; 3],
+}
+
+impl CacheEviction {
+    pub fn attach(registry: &EbpfRegistry) -> Result<Self, EbpfError> {
+        let mut evict_cache_exec = registry.program.evict_cache_exec.take();
+        let mut evict_cache_exit = registry.program.evict_cache_exit.take();
+        let mut evict_cache_rename = registry.program.evict_cache_rename.take();
+
+        let links = [
+            attach(&mut evict_cache_exec, "sched_process_exec")?,
+            attach(&mut evict_cache_exit, "sched_process_exit")?,
+            attach(&mut evict_cache_rename, "task_rename")?,
+        ];
+
+        Ok(Self {
+            _evict_cache_exec: evict_cache_exec,
+            _evict_cache_exit: evict_cache_exit,
+            _evict_cache_rename: evict_cache_rename,
+            _links: links,
+        })
+    }
+}
+
+pub(super) fn attach(
+    program: &mut RegistryGuard<RawTracePoint>,
+    tracepoint: &str,
+) -> Result<RawTracePointLink, EbpfError> {
+    let link_id = program.attach(tracepoint)?;
+    Ok(program.take_link(link_id)?)
+}
@@ -0,0 +1,59 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use aya::{
+    programs::{raw_trace_point::RawTracePointLink, RawTracePoint},
+    EbpfError,
+};
+use ractor::ActorRef;
+use shared::config::CrashConfig;
+
+use crate::{
+    features::Feature,
+    registry::{EbpfRegistry, RegistryGuard},
+    symbols::actors::SymbolActorMsg,
+};
+
+pub struct CrashFeature {
+    trace_crash: RegistryGuard<RawTracePoint>,
+    trace_crash_link: Option<RawTracePointLink>,
+}
+
+impl CrashFeature {
+    fn create(registry: &EbpfRegistry) -> Self {
+        Self {
+            trace_crash: registry.program.trace_crash.take(),
+            trace_crash_link: None,
+        }
+    }
+
+    fn attach(&mut self) -> Result<(), EbpfError> {
+        if self.trace_crash_link.is_none() {
+            let link_id = self.trace_crash.attach("signal_deliver")?;
+            self.trace_crash_link = Some(self.trace_crash.take_link(link_id)?);
+        }
+
+        Ok(())
+    }
+
+    fn detach(&mut self) {
+        let _ = self.trace_crash_link.take();
+    }
+}
+
+impl Feature for CrashFeature {
+    type Config = CrashConfig;
+    fn init(registry: &EbpfRegistry, _: Option<ActorRef<SymbolActorMsg>>) -> Self {
+        CrashFeature::create(registry)
+    }
+
+    async fn apply(&mut self, config: &Option<Self::Config>) -> Result<(), EbpfError> {
+        if config.is_some() {
+            self.attach()?;
+        } else {
+            self.detach();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use aya::{
+    programs::{raw_trace_point::RawTracePointLink, RawTracePoint},
+    EbpfError,
+};
+
+use super::cache::attach;
+use crate::registry::{EbpfRegistry, RegistryGuard};
+
+/// Adds new processes to the pid filter if their parent is in it and removes
+/// them on exit. Which event kinds follow children is part of the filter
+/// configuration, so this is attached for the whole lifetime of the daemon.
+pub struct FollowChildren {
+    _follow_children_fork: RegistryGuard<RawTracePoint>,
+    _follow_children_exit: RegistryGuard<RawTracePoint>,
+    _links: [RawTracePointLink; 2],
+}
+
+impl FollowChildren {
+    pub fn attach(registry: &EbpfRegistry) -> Result<Self, EbpfError> {
+        let mut follow_children_fork = registry.program.follow_children_fork.take();
+        let mut follow_children_exit = registry.program.follow_children_exit.take();
+
+        let links = [
+            attach(&mut follow_children_fork, "sched_process_fork")?,
+            attach(&mut follow_children_exit, "sched_process_exit")?,
+        ];
+
+        Ok(Self {
+            _follow_children_fork: follow_children_fork,
+            _follow_children_exit: follow_children_exit,
+            _links: links,
+        })
+    }
+}
@@ -0,0 +1,286 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ffi::CStr,
+    fs,
+    sync::RwLock,
+};
+
+use shared::events::{event::EventData, time_series_event::EventKind, Event};
+
+/// The strings of an event which string filters match
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StringField {
+    Comm,
+    ProcessComm,
+    ExePath,
+    Cmdline,
+}
+
+impl StringField {
+    const ALL: [StringField; 4] = [
+        StringField::Comm,
+        StringField::ProcessComm,
+        StringField::ExePath,
+        StringField::Cmdline,
+    ];
+
+    /// Exe path and cmdline are not part of the event and are read from procfs,
+    /// they are gone with the process.
+    fn value<'a>(&self, pid: u32, event: &'a Event) -> Option<Cow<'a, str>> {
+        let Some(EventData::Log(log)) = &event.event_data else {
+            return None;
+        };
+        let context = log.context.as_ref()?;
+
+        match self {
+            StringField::Comm => Some(Cow::Borrowed(&context.thread_comm)),
+            StringField::ProcessComm => Some(Cow::Borrowed(&context.process_comm)),
+            StringField::ExePath => fs::read_link(format!("/proc/{pid}/exe"))
+                .ok()
+                .map(|path| Cow::Owned(path.to_string_lossy().to_string())),
+            // The kernel sees the first argument, the rest is separated by NULs
+            StringField::Cmdline => fs::read(format!("/proc/{pid}/cmdline"))
+                .ok()
+                .map(|cmdline| {
+                    let first = CStr::from_bytes_until_nul(&cmdline)
+                        .map(CStr::to_bytes)
+                        .unwrap_or(&cmdline);
+                    Cow::Owned(String::from_utf8_lossy(first).to_string())
+                }),
+        }
+    }
+}
+
+/// `?` matches a single character and `*` any number of characters, including none.
+fn glob_matches(glob: &str, value: &str) -> bool {
+    let glob = glob.chars().collect::<Vec<_>>();
+    let value = value.chars().collect::<Vec<_>>();
+    let (mut g, mut v) = (0, 0);
+    // The latest `*` and the position in the value it is matched up to
+    let mut star = None;
+
+    while v < value.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, v));
+                g += 1;
+            }
+            Some(&c) if c == '?' || c == value[v] => {
+                g += 1;
+                v += 1;
+            }
+            _ => {
+                let Some((star_g, star_v)) = star else {
+                    return false;
+                };
+                star = Some((star_g, star_v + 1));
+                g = star_g + 1;
+                v = star_v + 1;
+            }
+        }
+    }
+
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+#[derive(Debug)]
+struct Glob {
+    pattern: String,
+    /// Bytes before the first wildcard, looked up in the kernel like a prefix
+    prefix_len: usize,
+    matches: bool,
+}
+
+impl Glob {
+    fn prefix(&self) -> &str {
+        &self.pattern[..self.prefix_len]
+    }
+}
+
+/// The entries of one string filter for one event kind
+#[derive(Debug, Default)]
+pub struct FieldGlobs {
+    exact: HashSet<String>,
+    prefixes: Vec<String>,
+    globs: Vec<Glob>,
+}
+
+impl FieldGlobs {
+    pub fn add_exact(&mut self, value: &str) {
+        self.exact.insert(value.to_owned());
+    }
+
+    pub fn add_prefix(&mut self, prefix: &str) {
+        self.prefixes.push(prefix.to_owned());
+    }
+
+    pub fn add_glob(&mut self, pattern: &str, prefix_len: usize, matches: bool) {
+        self.globs.push(Glob {
+            pattern: pattern.to_owned(),
+            prefix_len,
+            matches,
+        });
+    }
+
+    fn has_globs(&self) -> bool {
+        !self.globs.is_empty()
+    }
+
+    /// The kernel already decided by the exact entries and the prefixes, this only drops
+    /// values matching a `not_match` glob and values which passed the kernel for the
+    /// literal prefix of a `match` glob without matching it.
+    fn keeps(&self, value: &str) -> bool {
+        if self.exact.contains(value) {
+            return true;
+        }
+
+        let mut matching = self
+            .globs
+            .iter()
+            .filter(|glob| glob_matches(&glob.pattern, value))
+            .peekable();
+        if matching.peek().is_some() {
+            return matching.all(|glob| glob.matches);
+        }
+
+        let longest_glob = self
+            .globs
+            .iter()
+            .filter(|glob| glob.matches && value.starts_with(glob.prefix()))
+            .map(|glob| glob.prefix_len)
+            .max();
+        let longest_prefix = self
+            .prefixes
+            .iter()
+            .filter(|prefix| value.starts_with(prefix.as_str()))
+            .map(String::len)
+            .max();
+
+        // Prefixes keep their entry in the kernel if a glob has the same literal prefix
+        match (longest_glob, longest_prefix) {
+            (Some(glob), prefix) => prefix.is_some_and(|prefix| prefix >= glob),
+            (None, _) => true,
+        }
+    }
+}
+
+/// Globs of the string filters which the kernel can only look up by their
+/// literal prefix, the daemon checks the whole glob for the events passing the kernel.
+#[derive(Debug, Default)]
+pub struct GlobFilters(RwLock<HashMap<(EventKind, StringField), FieldGlobs>>);
+
+impl GlobFilters {
+    /// Only filters with globs are kept
+    pub fn replace(&self, mut filters: HashMap<(EventKind, StringField), FieldGlobs>) {
+        filters.retain(|_, globs| globs.has_globs());
+        *self.0.write().unwrap() = filters;
+    }
+
+    pub fn keeps(&self, event: &Event) -> bool {
+        let filters = self.0.read().unwrap();
+        if filters.is_empty() {
+            return true;
+        }
+
+        let Some(EventData::Log(log)) = &event.event_data else {
+            return true;
+        };
+        let kind = EventKind::from(log);
+        let pid = log.context.as_ref().map_or(0, |context| context.pid);
+
+        StringField::ALL.into_iter().all(|field| {
+            let Some(globs) = filters.get(&(kind, field)) else {
+                return true;
+            };
+            field
+                .value(pid, event)
+                .is_none_or(|value| globs.keeps(&value))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::events::{log_event::LogEventData, EventContext, LogEvent, WriteEvent};
+
+    use super::*;
+
+    fn field_globs() -> FieldGlobs {
+        let mut globs = FieldGlobs::default();
+        globs.add_exact("com.example.debug");
+        globs.add_prefix("com.example.lib");
+        globs.add_glob("com.*.app", 4, true);
+        globs.add_glob("com.example.*?test", 12, false);
+        globs
+    }
+
+    #[test]
+    fn globs_match_single_and_many_characters() {
+        assert!(glob_matches("com.*.app", "com.example.app"));
+        assert!(glob_matches("com.*.app", "com..app"));
+        assert!(!glob_matches("com.*.app", "com.example.apps"));
+        assert!(glob_matches("lib?.so", "libc.so"));
+        assert!(!glob_matches("lib?.so", "lib.so"));
+        assert!(glob_matches("*a*b?", "xxaxxbbc"));
+        assert!(!glob_matches("*a*b?", "xxaxxb"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("a**", "a"));
+    }
+
+    #[test]
+    fn keeps_values_the_kernel_decided() {
+        let globs = field_globs();
+
+        assert!(globs.keeps("com.example.debug"));
+        assert!(globs.keeps("com.example.library"));
+        assert!(globs.keeps("org.example"));
+    }
+
+    #[test]
+    fn drops_values_only_passing_for_a_literal_prefix() {
+        let globs = field_globs();
+
+        assert!(globs.keeps("com.example.app"));
+        assert!(!globs.keeps("com.example.apps"));
+        assert!(!globs.keeps("com.example.unittest"));
+    }
+
+    #[test]
+    fn keeps_all_events_without_globs() {
+        let filters = GlobFilters::default();
+        filters.replace(HashMap::from([(
+            (EventKind::Write, StringField::Comm),
+            FieldGlobs::default(),
+        )]));
+
+        assert!(filters.keeps(&Event::default()));
+    }
+
+    #[test]
+    fn checks_the_string_of_the_event_kind() {
+        let filters = GlobFilters::default();
+        filters.replace(HashMap::from([(
+            (EventKind::Write, StringField::Comm),
+            field_globs(),
+        )]));
+
+        let write = |comm: &str| Event {
+            event_data: Some(EventData::Log(LogEvent {
+                context: Some(EventContext {
+                    thread_comm: comm.to_owned(),
+                    ..Default::default()
+                }),
+                log_event_data: Some(LogEventData::Write(WriteEvent::default())),
+            })),
+            sequence: 1,
+        };
+
+        assert!(filters.keeps(&write("com.example.app")));
+        assert!(!filters.keeps(&write("com.example.apps")));
+    }
+}
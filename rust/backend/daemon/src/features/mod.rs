@@ -6,9 +6,14 @@
 // SPDX-License-Identifier: MIT
 
 mod blocking;
+mod cache;
+mod crash;
 mod file_descriptor_change;
+mod follow_children;
 mod garbage_collect;
+mod globs;
 mod jni_references;
+mod process;
 mod signal;
 mod write;
 
@@ -17,25 +22,102 @@ use std::{
     collections::{HashMap, HashSet},
     fmt::Debug,
     hash::Hash,
+    io,
+    path::Path,
     process::id,
+    sync::Arc,
 };
 
-use aya::{EbpfError, Pod};
+use aya::{
+    maps::{lpm_trie::Key, IterableMap, MapError},
+    EbpfError, Pod,
+};
 use blocking::BlockingFeature;
-use ebpf_types::{Equality, EventKind, Filter, FilterConfig, MissingBehavior};
+use cache::CacheEviction;
+use crash::CrashFeature;
+use ebpf_types::{
+    Combination, Equality, EventKind, Filter, FilterConfig, MissingBehavior, RateLimit,
+    SuppressedEvents, MAX_PREFIX_FILTER_LEN,
+};
 use file_descriptor_change::FileDescriptorChangeFeature;
+use follow_children::FollowChildren;
 use garbage_collect::GarbageCollectFeature;
+pub use globs::GlobFilters;
+use globs::{FieldGlobs, StringField};
 use jni_references::JniReferencesFeatures;
+use process::ProcessFeature;
+use procfs::ProcError;
 use ractor::ActorRef;
-use shared::config::{Configuration, StringFilter, UInt32Filter};
+use shared::{
+    config::{Configuration, FilterCombination, StringFilter, UInt32Filter, UidFilter},
+    events::{self, time_series_event, SuppressedEventCount},
+};
 use signal::SignalFeature;
+use thiserror::Error;
 use write::WriteFeature;
 
 use crate::{
-    registry::{EbpfRegistry, OwnedArray, OwnedHashMap, RegistryGuard},
+    cgroup::cgroup_id,
+    constants::{CGROUP_V2_PATH, PACKAGES_LIST_PATH},
+    ebpf_utils::EbpfErrorWrapper,
+    packages::PackageList,
+    procfs_utils::ProcessTree,
+    registry::{
+        EbpfRegistry, OwnedArray, OwnedHashMap, OwnedLpmTrie, OwnedPerCpuArray, RegistryGuard,
+    },
     symbols::actors::SymbolActorMsg,
 };
 
+#[derive(Debug, Error)]
+pub enum FeatureError {
+    #[error(transparent)]
+    EbpfError(#[from] EbpfError),
+    #[error("prefix of `{pattern}` is longer than {max_len} bytes")]
+    PrefixTooLong { pattern: String, max_len: usize },
+    #[error("signal {0} is out of range, signals are numbered from 1 to 64")]
+    InvalidSignal(u32),
+    #[error("unknown package `{0}`")]
+    UnknownPackage(String),
+    #[error("failed to read the package list")]
+    PackageList(#[source] io::Error),
+    #[error("patterns are not supported for cgroups, got `{0}`")]
+    CgroupPattern(String),
+    #[error("failed to resolve cgroup `{path}`")]
+    Cgroup {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error(
+        "`{filter}` needs {entries} entries but its map holds {capacity}, \
+         start the daemon with a larger --id-filter-capacity or --string-filter-capacity"
+    )]
+    FilterCapacity {
+        filter: &'static str,
+        entries: usize,
+        capacity: u32,
+    },
+    #[error("failed to read the process tree")]
+    ProcessTree(#[source] ProcError),
+}
+
+impl From<MapError> for FeatureError {
+    fn from(err: MapError) -> Self {
+        Self::EbpfError(err.into())
+    }
+}
+
+impl From<FeatureError> for tonic::Status {
+    fn from(err: FeatureError) -> Self {
+        match err {
+            FeatureError::EbpfError(err) => EbpfErrorWrapper::from(err).into(),
+            FeatureError::PackageList(err) => Self::internal(err.to_string()),
+            FeatureError::ProcessTree(err) => Self::internal(err.to_string()),
+            err => Self::invalid_argument(err.to_string()),
+        }
+    }
+}
+
 pub trait Feature {
     type Config;
 
@@ -50,27 +132,41 @@ pub struct Features {
     jni_reference_feature: JniReferencesFeatures,
     garbage_collect_feature: GarbageCollectFeature,
     file_descriptor_change_feature: FileDescriptorChangeFeature,
+    process_feature: ProcessFeature,
+    crash_feature: CrashFeature,
+    _cache_eviction: CacheEviction,
+    _follow_children: FollowChildren,
     pid_filter: RegistryGuard<OwnedHashMap<u32, Equality>>,
+    pid_filter_follow: RegistryGuard<OwnedArray<u64>>,
+    uid_filter: RegistryGuard<OwnedHashMap<u32, Equality>>,
+    cgroup_filter: RegistryGuard<OwnedHashMap<u64, Equality>>,
     comm_filter: RegistryGuard<OwnedHashMap<[u8; 16], Equality>>,
+    process_comm_filter: RegistryGuard<OwnedHashMap<[u8; 16], Equality>>,
     exe_path_filter: RegistryGuard<OwnedHashMap<[u8; 4096], Equality>>,
     cmdline_filter: RegistryGuard<OwnedHashMap<[u8; 256], Equality>>,
+    comm_prefix_filter: RegistryGuard<OwnedLpmTrie<[u8; 16], Equality>>,
+    process_comm_prefix_filter: RegistryGuard<OwnedLpmTrie<[u8; 16], Equality>>,
+    exe_path_prefix_filter: RegistryGuard<OwnedLpmTrie<[u8; MAX_PREFIX_FILTER_LEN], Equality>>,
+    cmdline_prefix_filter: RegistryGuard<OwnedLpmTrie<[u8; MAX_PREFIX_FILTER_LEN], Equality>>,
     filter_config: RegistryGuard<OwnedArray<FilterConfig>>,
-    blocking_threshold: RegistryGuard<OwnedArray<u64>>,
+    rate_limits: RegistryGuard<OwnedArray<RateLimit>>,
+    suppressed_events: RegistryGuard<OwnedPerCpuArray<SuppressedEvents>>,
     config: RegistryGuard<OwnedArray<u32>>,
+    globs: Arc<GlobFilters>,
 }
 
-macro_rules! extract_filter_from_config {
-    ($config:ident, $kind:path) => {
-        ($config.as_ref().and_then(|c| c.filter.as_ref()), $kind)
+macro_rules! extract_from_config {
+    ($config:ident, $field:ident, $kind:path) => {
+        ($config.as_ref().and_then(|c| c.$field.as_ref()), $kind)
     };
 }
 
-macro_rules! extract_filters {
-    ($($config:ident: $kind:path),*) => {
+macro_rules! extract_from_configs {
+    ($field:ident => $($config:ident: $kind:path),*) => {
         {
             [
                 $(
-                    extract_filter_from_config!($config, $kind),
+                    extract_from_config!($config, $field, $kind),
                 )*
             ]
             .into_iter()
@@ -79,19 +175,32 @@ macro_rules! extract_filters {
     };
 }
 
-macro_rules! update_eq_filters {
-    ($filter:expr, $kind:expr, $conf:expr => $({$field:ident, $eqs:ident, $transform:expr}),*) => {
+/// Fills the equality maps of each configured filter,
+/// unconfigured filters get the given default.
+macro_rules! update_filters {
+    ($filter:expr, $conf:expr, $unconfigured:expr => $({$field:ident, |$f:ident| $update:expr}),* $(,)?) => {
         $(
-            if let Some(f) = &$filter.$field {
-                f.equalities(&mut $eqs, $kind, $transform);
-                $conf.$field = Some(Filter {
-                    missing_behavior: f.get_missing_behavior(),
-                });
-            } else {
-                $conf.$field = Some(Filter {
-                    missing_behavior: MissingBehavior::NotMatch,
-                });
-            }
+            $conf.$field = match &$filter.$field {
+                Some($f) => {
+                    $update;
+                    Some(Filter {
+                        missing_behavior: $f.get_missing_behavior(),
+                    })
+                }
+                None => $unconfigured,
+            };
+        )*
+    };
+}
+
+/// All maps are checked against their capacity before any of them is changed.
+macro_rules! update_filter_maps {
+    ($self:expr => $($map:ident: $eqs:expr),* $(,)?) => {
+        $(
+            check_capacity(stringify!($map), $eqs.len(), $self.$map.capacity()?)?;
+        )*
+        $(
+            $self.$map.update($eqs)?;
         )*
     };
 }
@@ -108,6 +217,7 @@ impl Features {
     pub fn init_all_features(
         registry: &EbpfRegistry,
         symbol_actor_ref: ActorRef<SymbolActorMsg>,
+        globs: Arc<GlobFilters>,
     ) -> Self {
         let mut this = Self {
             blocking_feature: BlockingFeature::init(registry, None),
@@ -116,22 +226,35 @@ impl Features {
             jni_reference_feature: JniReferencesFeatures::init(registry, Some(symbol_actor_ref)),
             garbage_collect_feature: GarbageCollectFeature::init(registry, None),
             file_descriptor_change_feature: FileDescriptorChangeFeature::init(registry, None),
+            process_feature: ProcessFeature::init(registry, None),
+            crash_feature: CrashFeature::init(registry, None),
+            _cache_eviction: CacheEviction::attach(registry).unwrap(),
+            _follow_children: FollowChildren::attach(registry).unwrap(),
             pid_filter: registry.config.pid_filter.take(),
+            pid_filter_follow: registry.config.pid_filter_follow.take(),
+            uid_filter: registry.config.uid_filter.take(),
+            cgroup_filter: registry.config.cgroup_filter.take(),
             comm_filter: registry.config.comm_filter.take(),
+            process_comm_filter: registry.config.process_comm_filter.take(),
             exe_path_filter: registry.config.exe_path_filter.take(),
             cmdline_filter: registry.config.cmdline_filter.take(),
+            comm_prefix_filter: registry.config.comm_prefix_filter.take(),
+            process_comm_prefix_filter: registry.config.process_comm_prefix_filter.take(),
+            exe_path_prefix_filter: registry.config.exe_path_prefix_filter.take(),
+            cmdline_prefix_filter: registry.config.cmdline_prefix_filter.take(),
             filter_config: registry.config.filter_config.take(),
-            blocking_threshold: registry.config.global_blocking_threshold.take(),
+            rate_limits: registry.config.rate_limits.take(),
+            suppressed_events: registry.config.suppressed_events.take(),
             config: registry.config.config.take(),
+            globs,
         };
 
         this.config.set(0, id(), 0).unwrap();
-        this.blocking_threshold.set(0, 32_000_000, 0).unwrap();
 
         this
     }
 
-    pub async fn update_from_config(&mut self, config: &Configuration) -> Result<(), EbpfError> {
+    pub async fn update_from_config(&mut self, config: &Configuration) -> Result<(), FeatureError> {
         // Destructure the configuration.
         let Configuration {
             write_config,
@@ -140,49 +263,133 @@ impl Features {
             signal_config,
             file_descriptor_change_config,
             garbage_collect_config,
+            process_config,
+            crash_config,
             ..
         } = config;
 
-        let configs = extract_filters! {
+        if let Some(signal_config) = signal_config {
+            signal::check_signals(signal_config)?;
+        }
+
+        let configs = extract_from_configs! {filter =>
             write_config: EventKind::Write,
             blocking_config: EventKind::Blocking,
             jni_references_config: EventKind::JniReferences,
             signal_config: EventKind::Signal,
             file_descriptor_change_config: EventKind::FileDescriptorChange,
-            garbage_collect_config: EventKind::GarbageCollect
+            garbage_collect_config: EventKind::GarbageCollect,
+            process_config: EventKind::ProcessLifecycle,
+            crash_config: EventKind::Crash
+        };
+
+        let rate_limit_configs = extract_from_configs! {rate_limit =>
+            write_config: EventKind::Write,
+            blocking_config: EventKind::Blocking,
+            jni_references_config: EventKind::JniReferences,
+            signal_config: EventKind::Signal,
+            file_descriptor_change_config: EventKind::FileDescriptorChange,
+            garbage_collect_config: EventKind::GarbageCollect,
+            process_config: EventKind::ProcessLifecycle,
+            crash_config: EventKind::Crash
         };
 
         // Create the various equality maps.
         let mut pid_eqs = HashMap::new();
+        let mut uid_eqs = HashMap::new();
+        let mut cgroup_eqs = HashMap::new();
         let mut comm_eqs = HashMap::new();
+        let mut process_comm_eqs = HashMap::new();
         let mut exe_path_eqs = HashMap::new();
         let mut cmdline_eqs = HashMap::new();
+        let mut comm_prefix_eqs = HashMap::new();
+        let mut process_comm_prefix_eqs = HashMap::new();
+        let mut exe_path_prefix_eqs = HashMap::new();
+        let mut cmdline_prefix_eqs = HashMap::new();
+        // Checked by the daemon, the kernel only knows the literal prefixes
+        let mut globs = HashMap::new();
 
         let mut filter_config = [FilterConfig::default(); EventKind::MAX as usize];
 
+        // Only read when a filter refers to packages by name.
+        let mut packages = None;
+        // Only read when a pid filter follows children.
+        let mut process_tree = None;
+        let mut pid_filter_follow = 0;
+
         // Update all filter maps and configuration fields.
         for (filter, kind) in configs {
-            update_eq_filters! {filter, kind, filter_config[kind as usize] =>
-                {pid_filter, pid_eqs, |pid| *pid},
-                {comm_filter, comm_eqs, |comm| comm.zero_extend()},
-                {exe_path_filter, exe_path_eqs, |exe_path| exe_path.zero_extend()},
-                {cmdline_filter, cmdline_eqs, |cmdline| cmdline.zero_extend()}
+            // Processes forked before the filter was set are looked up once,
+            // explicitly listed pids take precedence over their ancestors.
+            if let Some(f) = filter.pid_filter.as_ref().filter(|f| f.follow_children) {
+                f.descendant_equalities(&mut pid_eqs, kind, &mut process_tree)?;
+                pid_filter_follow |= 1 << kind as u64;
+            }
+
+            let combination = filter.combination();
+            let unconfigured = unconfigured_filter(combination);
+            let config = &mut filter_config[kind as usize];
+            config.combination = Some(ebpf_combination(combination));
+
+            let proto_kind = proto_event_kind(kind);
+            update_filters! {filter, config, unconfigured =>
+                {pid_filter, |f| f.equalities(&mut pid_eqs, kind, |pid| *pid)},
+                {uid_filter, |f| f.uid_equalities(&mut uid_eqs, kind, &mut packages)?},
+                {cgroup_filter, |f| {
+                    f.cgroup_equalities(&mut cgroup_eqs, kind, CGROUP_V2_PATH)?
+                }},
+                {comm_filter, |f| {
+                    let globs = globs.entry((proto_kind, StringField::Comm)).or_default();
+                    f.string_equalities(&mut comm_eqs, &mut comm_prefix_eqs, globs, kind)?
+                }},
+                {process_comm_filter, |f| {
+                    let globs = globs.entry((proto_kind, StringField::ProcessComm)).or_default();
+                    f.string_equalities(
+                        &mut process_comm_eqs,
+                        &mut process_comm_prefix_eqs,
+                        globs,
+                        kind,
+                    )?
+                }},
+                {exe_path_filter, |f| {
+                    let globs = globs.entry((proto_kind, StringField::ExePath)).or_default();
+                    f.string_equalities(&mut exe_path_eqs, &mut exe_path_prefix_eqs, globs, kind)?
+                }},
+                {cmdline_filter, |f| {
+                    let globs = globs.entry((proto_kind, StringField::Cmdline)).or_default();
+                    f.string_equalities(&mut cmdline_eqs, &mut cmdline_prefix_eqs, globs, kind)?
+                }},
             };
         }
 
-        // Update the blocking threshold if needed.
-        if let Some(threshold) = blocking_config.as_ref().and_then(|c| c.threshold) {
-            self.blocking_threshold.set(0, threshold, 0)?;
+        // Update all equality maps and the filter configuration.
+        update_filter_maps! {self =>
+            pid_filter: pid_eqs,
+            uid_filter: uid_eqs,
+            cgroup_filter: cgroup_eqs,
+            comm_filter: comm_eqs,
+            process_comm_filter: process_comm_eqs,
+            exe_path_filter: exe_path_eqs,
+            cmdline_filter: cmdline_eqs,
+            comm_prefix_filter: comm_prefix_eqs,
+            process_comm_prefix_filter: process_comm_prefix_eqs,
+            exe_path_prefix_filter: exe_path_prefix_eqs,
+            cmdline_prefix_filter: cmdline_prefix_eqs,
         }
-
-        // Update the filter configuration and all equality maps.
+        self.globs.replace(globs);
+        self.pid_filter_follow.set(0, pid_filter_follow, 0)?;
         for (i, config) in filter_config.iter().enumerate() {
             self.filter_config.set(i as u32, *config, 0)?;
         }
-        self.pid_filter.update(pid_eqs)?;
-        self.comm_filter.update(comm_eqs)?;
-        self.exe_path_filter.update(exe_path_eqs)?;
-        self.cmdline_filter.update(cmdline_eqs)?;
+
+        // Event kinds without a rate limit submit every event.
+        let mut rate_limits = [RateLimit::default(); EventKind::MAX as usize];
+        for (rate_limit, kind) in rate_limit_configs {
+            rate_limits[kind as usize] = ebpf_rate_limit(rate_limit);
+        }
+        for (i, rate_limit) in rate_limits.iter().enumerate() {
+            self.rate_limits.set(i as u32, *rate_limit, 0)?;
+        }
 
         // Apply each feature configuration.
 
@@ -192,12 +399,75 @@ impl Features {
             jni_reference_feature: jni_references_config,
             signal_feature: signal_config,
             garbage_collect_feature: garbage_collect_config,
-            file_descriptor_change_feature: file_descriptor_change_config
+            file_descriptor_change_feature: file_descriptor_change_config,
+            process_feature: process_config,
+            crash_feature: crash_config
         }
 
         Ok(())
     }
+
+    /// Counts since the programs were loaded, summed up over all cpus
+    pub fn suppressed_events(&self) -> Result<events::SuppressedEvents, FeatureError> {
+        let counts = EventKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let per_cpu = self.suppressed_events.get(&(kind as u32), 0)?;
+                let rate_limit = self.rate_limits.get(&(kind as u32), 0)?;
+
+                Ok(SuppressedEventCount {
+                    event_kind: proto_event_kind(kind).into(),
+                    sampled: per_cpu.iter().map(|count| count.sampled).sum(),
+                    rate_limited: per_cpu.iter().map(|count| count.rate_limited).sum(),
+                    sample_every: rate_limit.sample_every.max(1),
+                })
+            })
+            .collect::<Result<_, MapError>>()?;
+
+        Ok(events::SuppressedEvents { counts })
+    }
+}
+
+fn ebpf_rate_limit(rate_limit: &shared::config::RateLimit) -> RateLimit {
+    RateLimit {
+        sample_every: rate_limit.sample_every,
+        events_per_second: rate_limit.events_per_second,
+        burst: rate_limit.burst,
+    }
+}
+
+fn proto_event_kind(kind: EventKind) -> time_series_event::EventKind {
+    match kind {
+        EventKind::Write => time_series_event::EventKind::Write,
+        EventKind::Blocking => time_series_event::EventKind::Blocking,
+        EventKind::Signal => time_series_event::EventKind::Signal,
+        EventKind::GarbageCollect => time_series_event::EventKind::GarbageCollect,
+        EventKind::FileDescriptorChange => time_series_event::EventKind::FileDescriptorChange,
+        EventKind::JniReferences => time_series_event::EventKind::JniReferences,
+        EventKind::ProcessLifecycle => time_series_event::EventKind::Process,
+        EventKind::Crash => time_series_event::EventKind::Crash,
+        EventKind::MAX => time_series_event::EventKind::Undefined,
+    }
 }
+
+/// With `Any` an unconfigured filter rejects events nothing else accepts,
+/// with `All` it does not take part and must not reject anything.
+fn unconfigured_filter(combination: FilterCombination) -> Option<Filter> {
+    match combination {
+        FilterCombination::All => None,
+        FilterCombination::Any | FilterCombination::Unspecified => Some(Filter {
+            missing_behavior: MissingBehavior::NotMatch,
+        }),
+    }
+}
+
+fn ebpf_combination(combination: FilterCombination) -> Combination {
+    match combination {
+        FilterCombination::All => Combination::All,
+        FilterCombination::Any | FilterCombination::Unspecified => Combination::Any,
+    }
+}
+
 enum Entry<T> {
     Match(T),
     NotMatch(T),
@@ -230,16 +500,7 @@ trait FilterExt {
         F: Fn(&Self::Values) -> K,
     {
         for entry in self.entries() {
-            let ent = dest.entry(key_fn(entry.value())).or_insert(Equality {
-                eq_for_event_kind: 0,
-                used_for_event_kind: 0,
-            });
-
-            ent.used_for_event_kind |= 1 << kind as u32;
-            match entry {
-                Entry::Match(_) => ent.eq_for_event_kind |= 1 << kind as u32,
-                Entry::NotMatch(_) => ent.eq_for_event_kind &= 0 << kind as u32,
-            }
+            insert_equality(dest, key_fn(entry.value()), kind, &entry);
         }
     }
 
@@ -270,6 +531,244 @@ impl FilterExt for UInt32Filter {
     }
 }
 
+impl FilterExt for UidFilter {
+    type Values = u32;
+
+    fn matches(&self) -> impl Iterator<Item = Self::Values> {
+        self.r#match.clone().into_iter()
+    }
+
+    fn not_matches(&self) -> impl Iterator<Item = Self::Values> {
+        self.not_match.clone().into_iter()
+    }
+
+    fn missing_behavior_raw(&self) -> i32 {
+        self.missing_behavior
+    }
+}
+
+trait PidFilterExt {
+    /// Descendants of each pid get the same entry as the pid itself,
+    /// the process tree is read on first use.
+    fn descendant_equalities(
+        &self,
+        dest: &mut HashMap<u32, Equality>,
+        kind: EventKind,
+        process_tree: &mut Option<ProcessTree>,
+    ) -> Result<(), FeatureError>;
+}
+
+impl PidFilterExt for UInt32Filter {
+    fn descendant_equalities(
+        &self,
+        dest: &mut HashMap<u32, Equality>,
+        kind: EventKind,
+        process_tree: &mut Option<ProcessTree>,
+    ) -> Result<(), FeatureError> {
+        let process_tree = match process_tree {
+            Some(process_tree) => process_tree,
+            None => process_tree.insert(ProcessTree::read().map_err(FeatureError::ProcessTree)?),
+        };
+
+        for entry in self.entries() {
+            for pid in process_tree.descendants(*entry.value()) {
+                insert_equality(dest, pid, kind, &entry);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+trait UidFilterExt {
+    /// Package names are resolved to their uids, the package list is read on first use.
+    fn uid_equalities(
+        &self,
+        dest: &mut HashMap<u32, Equality>,
+        kind: EventKind,
+        packages: &mut Option<PackageList>,
+    ) -> Result<(), FeatureError>;
+}
+
+impl UidFilterExt for UidFilter {
+    fn uid_equalities(
+        &self,
+        dest: &mut HashMap<u32, Equality>,
+        kind: EventKind,
+        packages: &mut Option<PackageList>,
+    ) -> Result<(), FeatureError> {
+        self.equalities(dest, kind, |uid| *uid);
+
+        if self.match_packages.is_empty() && self.not_match_packages.is_empty() {
+            return Ok(());
+        }
+
+        let packages = match packages {
+            Some(packages) => packages,
+            None => packages
+                .insert(PackageList::read(PACKAGES_LIST_PATH).map_err(FeatureError::PackageList)?),
+        };
+
+        let entries = self
+            .match_packages
+            .iter()
+            .map(Entry::Match)
+            .chain(self.not_match_packages.iter().map(Entry::NotMatch));
+
+        for entry in entries {
+            let uid = packages
+                .uid(entry.value())
+                .ok_or_else(|| FeatureError::UnknownPackage(entry.value().to_string()))?;
+            insert_equality(dest, uid, kind, &entry);
+        }
+
+        Ok(())
+    }
+}
+
+fn insert_equality<K: Eq + Hash, T>(
+    dest: &mut HashMap<K, Equality>,
+    key: K,
+    kind: EventKind,
+    entry: &Entry<T>,
+) {
+    let ent = dest.entry(key).or_insert(Equality {
+        eq_for_event_kind: 0,
+        used_for_event_kind: 0,
+    });
+
+    ent.used_for_event_kind |= 1 << kind as u32;
+    match entry {
+        Entry::Match(_) => ent.eq_for_event_kind |= 1 << kind as u32,
+        Entry::NotMatch(_) => ent.eq_for_event_kind &= !(1 << kind as u32),
+    }
+}
+
+/// A string in a `StringFilter`, a trailing `*` matches any suffix, e.g. `com.example.*`.
+/// Other globs, e.g. `com.*.app` or `lib?.so`, are looked up in the kernel by the
+/// literal prefix before their first wildcard, the daemon checks the whole glob.
+#[derive(Debug, PartialEq, Eq)]
+enum StringPattern<'a> {
+    Exact(&'a str),
+    Prefix(&'a str),
+    Glob { prefix: &'a str, glob: &'a str },
+}
+
+impl<'a> StringPattern<'a> {
+    fn parse(pattern: &'a str) -> Self {
+        match pattern.find(['*', '?']) {
+            None => StringPattern::Exact(pattern),
+            Some(i) if &pattern[i..] == "*" => StringPattern::Prefix(&pattern[..i]),
+            Some(i) => StringPattern::Glob {
+                prefix: &pattern[..i],
+                glob: pattern,
+            },
+        }
+    }
+}
+
+trait StringFilterExt {
+    /// Exact patterns are zero extended, prefix patterns are keyed by their length in bits
+    /// as expected by the LPM trie. Globs are added to `globs` along with the other entries
+    /// they are checked against.
+    fn string_equalities<const N: usize, const P: usize>(
+        &self,
+        exact: &mut HashMap<[u8; N], Equality>,
+        prefix: &mut HashMap<(u32, [u8; P]), Equality>,
+        globs: &mut FieldGlobs,
+        kind: EventKind,
+    ) -> Result<(), FeatureError>;
+}
+
+impl StringFilterExt for StringFilter {
+    fn string_equalities<const N: usize, const P: usize>(
+        &self,
+        exact: &mut HashMap<[u8; N], Equality>,
+        prefix: &mut HashMap<(u32, [u8; P]), Equality>,
+        globs: &mut FieldGlobs,
+        kind: EventKind,
+    ) -> Result<(), FeatureError> {
+        let prefix_key = |value: &str| ((value.len() * 8) as u32, value.zero_extend());
+        let mut glob_prefixes = Vec::new();
+
+        for entry in self.entries() {
+            match StringPattern::parse(entry.value()) {
+                StringPattern::Exact(value) => {
+                    globs.add_exact(value);
+                    insert_equality(exact, value.zero_extend(), kind, &entry)
+                }
+                StringPattern::Prefix(value) | StringPattern::Glob { prefix: value, .. }
+                    if value.len() > P =>
+                {
+                    return Err(FeatureError::PrefixTooLong {
+                        pattern: entry.value().clone(),
+                        max_len: P,
+                    })
+                }
+                StringPattern::Prefix(value) => {
+                    globs.add_prefix(value);
+                    insert_equality(prefix, prefix_key(value), kind, &entry)
+                }
+                StringPattern::Glob { prefix, glob } => {
+                    let matches = matches!(entry, Entry::Match(_));
+                    globs.add_glob(glob, prefix.len(), matches);
+                    // Values matching a `not_match` glob are dropped by the daemon
+                    if matches {
+                        glob_prefixes.push(prefix.to_owned());
+                    }
+                }
+            }
+        }
+
+        // Lets values with the literal prefix through to the daemon, prefix entries
+        // of the filter take precedence
+        let mask = 1 << kind as u64;
+        for value in glob_prefixes {
+            let key = prefix_key(&value);
+            if prefix
+                .get(&key)
+                .is_none_or(|eq| eq.used_for_event_kind & mask == 0)
+            {
+                insert_equality(prefix, key, kind, &Entry::Match(()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+trait CgroupFilterExt {
+    /// Paths are resolved to cgroup ids below `root`.
+    fn cgroup_equalities(
+        &self,
+        dest: &mut HashMap<u64, Equality>,
+        kind: EventKind,
+        root: impl AsRef<Path>,
+    ) -> Result<(), FeatureError>;
+}
+
+impl CgroupFilterExt for StringFilter {
+    fn cgroup_equalities(
+        &self,
+        dest: &mut HashMap<u64, Equality>,
+        kind: EventKind,
+        root: impl AsRef<Path>,
+    ) -> Result<(), FeatureError> {
+        for entry in self.entries() {
+            let StringPattern::Exact(path) = StringPattern::parse(entry.value()) else {
+                return Err(FeatureError::CgroupPattern(entry.value().clone()));
+            };
+            let id = cgroup_id(&root, path).map_err(|source| FeatureError::Cgroup {
+                path: path.to_owned(),
+                source,
+            })?;
+            insert_equality(dest, id, kind, &entry);
+        }
+
+        Ok(())
+    }
+}
+
 impl FilterExt for StringFilter {
     type Values = String;
 
@@ -288,6 +787,20 @@ impl FilterExt for StringFilter {
 
 trait HashMapUpdate<K: Eq + Hash, V> {
     fn update(&mut self, other: HashMap<K, V>) -> Result<(), EbpfError>;
+    /// The maximum number of entries, set when the map was loaded
+    fn capacity(&self) -> Result<u32, MapError>;
+}
+
+fn check_capacity(filter: &'static str, entries: usize, capacity: u32) -> Result<(), FeatureError> {
+    if entries > capacity as usize {
+        return Err(FeatureError::FilterCapacity {
+            filter,
+            entries,
+            capacity,
+        });
+    }
+
+    Ok(())
 }
 
 impl<K: Eq + Hash + Pod + Debug, V: Pod + Debug> HashMapUpdate<K, V> for OwnedHashMap<K, V> {
@@ -309,6 +822,32 @@ impl<K: Eq + Hash + Pod + Debug, V: Pod + Debug> HashMapUpdate<K, V> for OwnedHa
 
         Ok(())
     }
+
+    fn capacity(&self) -> Result<u32, MapError> {
+        Ok(self.map().info()?.max_entries())
+    }
+}
+
+impl<K: Eq + Hash + Pod, V: Pod> HashMapUpdate<(u32, K), V> for OwnedLpmTrie<K, V> {
+    fn update(&mut self, other: HashMap<(u32, K), V>) -> Result<(), EbpfError> {
+        let old_keys = self.keys().collect::<Result<Vec<_>, _>>()?;
+
+        for key in old_keys {
+            if !other.contains_key(&(key.prefix_len(), key.data())) {
+                self.remove(&key)?;
+            }
+        }
+
+        for ((prefix_len, data), eq) in other {
+            self.insert(&Key::new(prefix_len, data), eq, 0)?;
+        }
+
+        Ok(())
+    }
+
+    fn capacity(&self) -> Result<u32, MapError> {
+        Ok(self.map().info()?.max_entries())
+    }
 }
 
 trait ZeroExtend {
@@ -327,3 +866,256 @@ where
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_patterns() {
+        assert_eq!(
+            StringPattern::parse("com.example"),
+            StringPattern::Exact("com.example")
+        );
+        assert_eq!(
+            StringPattern::parse("com.example.*"),
+            StringPattern::Prefix("com.example.")
+        );
+        assert_eq!(StringPattern::parse("*"), StringPattern::Prefix(""));
+        assert_eq!(
+            StringPattern::parse("com.*.example"),
+            StringPattern::Glob {
+                prefix: "com.",
+                glob: "com.*.example"
+            }
+        );
+        assert_eq!(
+            StringPattern::parse("com.example?"),
+            StringPattern::Glob {
+                prefix: "com.example",
+                glob: "com.example?"
+            }
+        );
+    }
+
+    #[test]
+    fn string_equalities_split_exact_and_prefix() {
+        let filter = StringFilter {
+            missing_behavior: shared::config::MissingBehavior::NotMatch.into(),
+            r#match: vec![
+                "/data/app/*".to_owned(),
+                "/system/bin/app_process64".to_owned(),
+            ],
+            not_match: vec!["/data/app/com.example*".to_owned()],
+        };
+
+        let mut exact = HashMap::<[u8; 32], Equality>::new();
+        let mut prefix = HashMap::<(u32, [u8; 32]), Equality>::new();
+        filter
+            .string_equalities(
+                &mut exact,
+                &mut prefix,
+                &mut FieldGlobs::default(),
+                EventKind::Write,
+            )
+            .unwrap();
+
+        let write = 1 << EventKind::Write as u64;
+        assert_eq!(exact.len(), 1);
+        assert_eq!(
+            exact[&"/system/bin/app_process64".zero_extend()].eq_for_event_kind,
+            write
+        );
+        assert_eq!(prefix.len(), 2);
+        let data_app = &prefix[&(10 * 8, "/data/app/".zero_extend())];
+        assert_eq!(data_app.eq_for_event_kind, write);
+        assert_eq!(data_app.used_for_event_kind, write);
+        let example = &prefix[&(21 * 8, "/data/app/com.example".zero_extend())];
+        assert_eq!(example.eq_for_event_kind, 0);
+        assert_eq!(example.used_for_event_kind, write);
+    }
+
+    #[test]
+    fn string_equalities_look_up_globs_by_their_literal_prefix() {
+        let filter = StringFilter {
+            r#match: vec!["/data/*/base.apk".to_owned(), "/system/lib?.so".to_owned()],
+            not_match: vec!["/data/*".to_owned(), "/vendor/*.so".to_owned()],
+            ..Default::default()
+        };
+
+        let mut prefix = HashMap::<(u32, [u8; 32]), Equality>::new();
+        filter
+            .string_equalities(
+                &mut HashMap::<[u8; 32], Equality>::new(),
+                &mut prefix,
+                &mut FieldGlobs::default(),
+                EventKind::Write,
+            )
+            .unwrap();
+
+        let write = 1 << EventKind::Write as u64;
+        // Values matching the `not_match` glob are dropped by the daemon
+        assert_eq!(prefix.len(), 2);
+        // The prefix entry is not replaced by the glob
+        let data = &prefix[&(6 * 8, "/data/".zero_extend())];
+        assert_eq!(data.eq_for_event_kind, 0);
+        assert_eq!(data.used_for_event_kind, write);
+        assert_eq!(
+            prefix[&(11 * 8, "/system/lib".zero_extend())].eq_for_event_kind,
+            write
+        );
+
+        let filter = StringFilter {
+            r#match: vec![format!("{}?", "a".repeat(17))],
+            ..Default::default()
+        };
+        let result = filter.string_equalities(
+            &mut HashMap::<[u8; 16], Equality>::new(),
+            &mut HashMap::<(u32, [u8; 16]), Equality>::new(),
+            &mut FieldGlobs::default(),
+            EventKind::Write,
+        );
+        assert!(matches!(result, Err(FeatureError::PrefixTooLong { .. })));
+    }
+
+    #[test]
+    fn not_match_keeps_the_matches_of_other_kinds() {
+        let matching = UInt32Filter {
+            r#match: vec![1],
+            ..Default::default()
+        };
+        let not_matching = UInt32Filter {
+            not_match: vec![1],
+            ..Default::default()
+        };
+
+        let mut pids = HashMap::new();
+        matching.equalities(&mut pids, EventKind::Write, |pid| *pid);
+        not_matching.equalities(&mut pids, EventKind::Blocking, |pid| *pid);
+
+        let write = 1 << EventKind::Write as u64;
+        let blocking = 1 << EventKind::Blocking as u64;
+        assert_eq!(pids[&1].eq_for_event_kind, write);
+        assert_eq!(pids[&1].used_for_event_kind, write | blocking);
+    }
+
+    #[test]
+    fn string_equalities_reject_long_prefix() {
+        let filter = StringFilter {
+            r#match: vec![format!("{}*", "a".repeat(17))],
+            ..Default::default()
+        };
+
+        let result = filter.string_equalities(
+            &mut HashMap::<[u8; 16], Equality>::new(),
+            &mut HashMap::<(u32, [u8; 16]), Equality>::new(),
+            &mut FieldGlobs::default(),
+            EventKind::Write,
+        );
+        assert!(matches!(result, Err(FeatureError::PrefixTooLong { .. })));
+    }
+
+    #[test]
+    fn uid_equalities_resolve_packages() {
+        let filter = UidFilter {
+            r#match: vec![1000],
+            match_packages: vec!["com.example.app".to_owned()],
+            not_match_packages: vec!["com.example.other".to_owned()],
+            ..Default::default()
+        };
+        let mut packages = Some(PackageList::parse(
+            "com.example.app 10123 0 /data/user/0/com.example.app default 3003\n\
+             com.example.other 10124 0 /data/user/0/com.example.other default 3003\n",
+        ));
+
+        let mut eqs = HashMap::new();
+        filter
+            .uid_equalities(&mut eqs, EventKind::Write, &mut packages)
+            .unwrap();
+
+        let write = 1 << EventKind::Write as u64;
+        assert_eq!(eqs.len(), 3);
+        assert_eq!(eqs[&1000].eq_for_event_kind, write);
+        assert_eq!(eqs[&10123].eq_for_event_kind, write);
+        assert_eq!(eqs[&10124].eq_for_event_kind, 0);
+        assert_eq!(eqs[&10124].used_for_event_kind, write);
+
+        let filter = UidFilter {
+            match_packages: vec!["com.example.missing".to_owned()],
+            ..Default::default()
+        };
+        let result = filter.uid_equalities(&mut eqs, EventKind::Write, &mut packages);
+        assert!(matches!(result, Err(FeatureError::UnknownPackage(_))));
+    }
+
+    #[test]
+    fn descendant_equalities_inherit_entry() {
+        let filter = UInt32Filter {
+            r#match: vec![10],
+            not_match: vec![20],
+            follow_children: true,
+            ..Default::default()
+        };
+        let mut process_tree = Some(ProcessTree::from_parents([(11, 10), (12, 11), (21, 20)]));
+
+        let mut eqs = HashMap::new();
+        filter
+            .descendant_equalities(&mut eqs, EventKind::Write, &mut process_tree)
+            .unwrap();
+
+        let write = 1 << EventKind::Write as u64;
+        assert_eq!(eqs.len(), 3);
+        assert_eq!(eqs[&11].eq_for_event_kind, write);
+        assert_eq!(eqs[&12].eq_for_event_kind, write);
+        assert_eq!(eqs[&21].eq_for_event_kind, 0);
+        assert_eq!(eqs[&21].used_for_event_kind, write);
+    }
+
+    #[test]
+    fn cgroup_equalities_resolve_paths() {
+        let root = std::env::temp_dir().join(format!("ziofa-features-{}", id()));
+        std::fs::create_dir_all(root.join("uid_10123")).unwrap();
+
+        let filter = StringFilter {
+            r#match: vec!["/uid_10123".to_owned()],
+            not_match: vec!["/".to_owned()],
+            ..Default::default()
+        };
+        let mut eqs = HashMap::new();
+        let result = filter.cgroup_equalities(&mut eqs, EventKind::Write, &root);
+
+        let filter = StringFilter {
+            r#match: vec!["/uid_*".to_owned()],
+            ..Default::default()
+        };
+        let pattern = filter.cgroup_equalities(&mut HashMap::new(), EventKind::Write, &root);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        result.unwrap();
+        let write = 1 << EventKind::Write as u64;
+        assert_eq!(eqs.len(), 2);
+        assert_eq!(
+            eqs.values().map(|eq| eq.eq_for_event_kind).sum::<u64>(),
+            write
+        );
+        assert!(matches!(pattern, Err(FeatureError::CgroupPattern(_))));
+    }
+
+    #[test]
+    fn capacity_is_validated() {
+        assert!(check_capacity("pid_filter", 256, 256).is_ok());
+        let err = check_capacity("pid_filter", 257, 256).unwrap_err();
+        assert!(matches!(
+            err,
+            FeatureError::FilterCapacity {
+                filter: "pid_filter",
+                entries: 257,
+                capacity: 256
+            }
+        ));
+        assert_eq!(
+            tonic::Status::from(err).code(),
+            tonic::Code::InvalidArgument
+        );
+    }
+}
@@ -0,0 +1,79 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use aya::{
+    programs::{raw_trace_point::RawTracePointLink, RawTracePoint},
+    EbpfError,
+};
+use ractor::ActorRef;
+use shared::config::ProcessConfig;
+
+use crate::{
+    features::Feature,
+    registry::{EbpfRegistry, RegistryGuard},
+    symbols::actors::SymbolActorMsg,
+};
+
+pub struct ProcessFeature {
+    trace_process_fork: RegistryGuard<RawTracePoint>,
+    trace_process_exec: RegistryGuard<RawTracePoint>,
+    trace_process_exit: RegistryGuard<RawTracePoint>,
+    trace_process_fork_link: Option<RawTracePointLink>,
+    trace_process_exec_link: Option<RawTracePointLink>,
+    trace_process_exit_link: Option<RawTracePointLink>,
+}
+
+impl ProcessFeature {
+    fn create(registry: &EbpfRegistry) -> Self {
+        Self {
+            trace_process_fork: registry.program.trace_process_fork.take(),
+            trace_process_exec: registry.program.trace_process_exec.take(),
+            trace_process_exit: registry.program.trace_process_exit.take(),
+            trace_process_fork_link: None,
+            trace_process_exec_link: None,
+            trace_process_exit_link: None,
+        }
+    }
+
+    fn attach(&mut self) -> Result<(), EbpfError> {
+        if self.trace_process_fork_link.is_none() {
+            let link_id = self.trace_process_fork.attach("sched_process_fork")?;
+            self.trace_process_fork_link = Some(self.trace_process_fork.take_link(link_id)?);
+        }
+
+        if self.trace_process_exec_link.is_none() {
+            let link_id = self.trace_process_exec.attach("sched_process_exec")?;
+            self.trace_process_exec_link = Some(self.trace_process_exec.take_link(link_id)?);
+        }
+
+        if self.trace_process_exit_link.is_none() {
+            let link_id = self.trace_process_exit.attach("sched_process_exit")?;
+            self.trace_process_exit_link = Some(self.trace_process_exit.take_link(link_id)?);
+        }
+
+        Ok(())
+    }
+
+    fn detach(&mut self) {
+        let _ = self.trace_process_fork_link.take();
+        let _ = self.trace_process_exec_link.take();
+        let _ = self.trace_process_exit_link.take();
+    }
+}
+
+impl Feature for ProcessFeature {
+    type Config = ProcessConfig;
+    fn init(registry: &EbpfRegistry, _: Option<ActorRef<SymbolActorMsg>>) -> Self {
+        ProcessFeature::create(registry)
+    }
+
+    async fn apply(&mut self, config: &Option<Self::Config>) -> Result<(), EbpfError> {
+        if config.is_some() {
+            self.attach()?;
+        } else {
+            self.detach();
+        }
+        Ok(())
+    }
+}
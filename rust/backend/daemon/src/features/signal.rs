@@ -6,21 +6,28 @@ use aya::{
     programs::{raw_trace_point::RawTracePointLink, RawTracePoint},
     EbpfError,
 };
+use ebpf_types::{signal_mask_bit, SIGNAL_MAX};
 use ractor::ActorRef;
 use shared::config::SignalConfig;
 
 use crate::{
-    features::Feature,
-    registry::{EbpfRegistry, RegistryGuard},
+    features::{Feature, FeatureError},
+    registry::{EbpfRegistry, OwnedArray, RegistryGuard},
     symbols::actors::SymbolActorMsg,
 };
 
 pub struct SignalFeature {
     sys_enter_signal: RegistryGuard<RawTracePoint>,
     sys_exit_signal: RegistryGuard<RawTracePoint>,
+    trace_signal_generate: RegistryGuard<RawTracePoint>,
+    trace_signal_deliver: RegistryGuard<RawTracePoint>,
 
     sys_enter_signal_link: Option<RawTracePointLink>,
     sys_exit_signal_link: Option<RawTracePointLink>,
+    trace_signal_generate_link: Option<RawTracePointLink>,
+    trace_signal_deliver_link: Option<RawTracePointLink>,
+
+    deny_mask: RegistryGuard<OwnedArray<u64>>,
 }
 
 impl SignalFeature {
@@ -28,12 +35,17 @@ impl SignalFeature {
         Self {
             sys_enter_signal: registry.program.sys_enter_signal.take(),
             sys_exit_signal: registry.program.sys_exit_signal.take(),
+            trace_signal_generate: registry.program.trace_signal_generate.take(),
+            trace_signal_deliver: registry.program.trace_signal_deliver.take(),
             sys_enter_signal_link: None,
             sys_exit_signal_link: None,
+            trace_signal_generate_link: None,
+            trace_signal_deliver_link: None,
+            deny_mask: registry.config.signal_deny_mask.take(),
         }
     }
 
-    fn attach(&mut self) -> Result<(), EbpfError> {
+    fn attach(&mut self, config: &SignalConfig) -> Result<(), EbpfError> {
         if self.sys_enter_signal_link.is_none() {
             let link_id = self.sys_enter_signal.attach("sys_enter")?;
             self.sys_enter_signal_link = Some(self.sys_enter_signal.take_link(link_id)?);
@@ -44,12 +56,28 @@ impl SignalFeature {
             self.sys_exit_signal_link = Some(self.sys_exit_signal.take_link(link_id)?);
         }
 
+        if !config.trace_generate {
+            let _ = self.trace_signal_generate_link.take();
+        } else if self.trace_signal_generate_link.is_none() {
+            let link_id = self.trace_signal_generate.attach("signal_generate")?;
+            self.trace_signal_generate_link = Some(self.trace_signal_generate.take_link(link_id)?);
+        }
+
+        if !config.trace_deliver {
+            let _ = self.trace_signal_deliver_link.take();
+        } else if self.trace_signal_deliver_link.is_none() {
+            let link_id = self.trace_signal_deliver.attach("signal_deliver")?;
+            self.trace_signal_deliver_link = Some(self.trace_signal_deliver.take_link(link_id)?);
+        }
+
         Ok(())
     }
 
     fn detach(&mut self) {
         let _ = self.sys_enter_signal_link.take();
         let _ = self.sys_exit_signal_link.take();
+        let _ = self.trace_signal_generate_link.take();
+        let _ = self.trace_signal_deliver_link.take();
     }
 }
 
@@ -60,11 +88,96 @@ impl Feature for SignalFeature {
     }
 
     async fn apply(&mut self, config: &Option<Self::Config>) -> Result<(), EbpfError> {
-        if config.is_some() {
-            self.attach()?;
+        if let Some(config) = config {
+            let (deny_mask, deny_others) = deny_mask(config);
+            self.deny_mask.set(0, deny_mask, 0)?;
+            self.deny_mask.set(1, deny_others as u64, 0)?;
+            self.attach(config)?;
         } else {
             self.detach();
         }
         Ok(())
     }
 }
+
+/// Signal numbers outside of `1..=SIGNAL_MAX` have no bit in the deny mask.
+pub fn check_signals(config: &SignalConfig) -> Result<(), FeatureError> {
+    match config
+        .allowed_signals
+        .iter()
+        .chain(&config.denied_signals)
+        .find(|signal| !(1..=SIGNAL_MAX).contains(signal))
+    {
+        Some(signal) => Err(FeatureError::InvalidSignal(*signal)),
+        None => Ok(()),
+    }
+}
+
+/// An allow list denies every signal which is not on it, this includes
+/// signal 0 and invalid numbers which have no bit in the mask.
+fn deny_mask(config: &SignalConfig) -> (u64, bool) {
+    let mask = |signals: &[u32]| {
+        signals
+            .iter()
+            .fold(0, |mask, signal| mask | signal_mask_bit(*signal))
+    };
+
+    let denied = mask(&config.denied_signals);
+    if config.allowed_signals.is_empty() {
+        (denied, false)
+    } else {
+        (!mask(&config.allowed_signals) | denied, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deny_mask_from_lists() {
+        let sigkill = signal_mask_bit(9);
+        let sigquit = signal_mask_bit(3);
+
+        assert_eq!(deny_mask(&SignalConfig::default()), (0, false));
+
+        let deny_quit = SignalConfig {
+            denied_signals: vec![3],
+            ..Default::default()
+        };
+        assert_eq!(deny_mask(&deny_quit), (sigquit, false));
+
+        let only_kill = SignalConfig {
+            allowed_signals: vec![9],
+            ..Default::default()
+        };
+        assert_eq!(deny_mask(&only_kill), (!sigkill, true));
+
+        let contradicting = SignalConfig {
+            allowed_signals: vec![9],
+            denied_signals: vec![9],
+            ..Default::default()
+        };
+        assert_eq!(deny_mask(&contradicting), (u64::MAX, true));
+    }
+
+    #[test]
+    fn rejects_signals_without_a_bit() {
+        for signal in [0, SIGNAL_MAX + 1] {
+            let config = SignalConfig {
+                denied_signals: vec![9, signal],
+                ..Default::default()
+            };
+            assert!(matches!(
+                check_signals(&config),
+                Err(FeatureError::InvalidSignal(invalid)) if invalid == signal
+            ));
+        }
+
+        let config = SignalConfig {
+            allowed_signals: vec![1, SIGNAL_MAX],
+            ..Default::default()
+        };
+        assert!(check_signals(&config).is_ok());
+    }
+}
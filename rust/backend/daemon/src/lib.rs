@@ -4,12 +4,14 @@
 //
 // SPDX-License-Identifier: MIT
 
+mod cgroup;
 mod collector;
 mod constants;
 mod ebpf_utils;
 mod features;
 mod filesystem;
 mod helpers;
+mod packages;
 mod procfs_utils;
 mod registry;
 mod server;
@@ -18,5 +20,5 @@ mod symbols;
 pub async fn run_server() {
     helpers::bump_rlimit();
 
-    server::serve_forever_socket().await;
+    server::serve_forever_socket(&registry::LoadOptions::default()).await;
 }
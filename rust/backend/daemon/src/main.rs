@@ -4,20 +4,31 @@
 //
 // SPDX-License-Identifier: MIT
 
+use clap::Parser;
 use tracing_subscriber::EnvFilter;
+mod cgroup;
 mod collector;
 mod constants;
 mod ebpf_utils;
 mod features;
 mod filesystem;
 mod helpers;
+mod packages;
 mod procfs_utils;
 mod registry;
 mod server;
 mod symbols;
 
+#[derive(Debug, Parser)]
+struct Args {
+    #[command(flatten)]
+    load_options: registry::LoadOptions,
+}
+
 #[tokio::main]
 async fn main() {
+    let args = Args::parse();
+
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env())
         .pretty()
@@ -26,5 +37,5 @@ async fn main() {
     // apparently needed...
     helpers::bump_rlimit();
 
-    server::serve_forever_socket().await;
+    server::serve_forever_socket(&args.load_options).await;
 }
@@ -0,0 +1,70 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{collections::HashMap, fs, io, path::Path};
+
+/// Uids of the installed packages as listed by the package manager.
+///
+/// Each line of the list starts with the package name followed by its uid,
+/// e.g. `com.example.app 10123 0 /data/user/0/com.example.app ...`.
+/// The uids are those of the primary user.
+#[derive(Debug, Default)]
+pub struct PackageList {
+    uids: HashMap<String, u32>,
+}
+
+impl PackageList {
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Lines which do not start with a name and a uid are skipped
+    pub fn parse(content: &str) -> Self {
+        let uids = content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let name = fields.next()?;
+                let uid = fields.next()?.parse().ok()?;
+                Some((name.to_owned(), uid))
+            })
+            .collect();
+
+        Self { uids }
+    }
+
+    pub fn uid(&self, package: &str) -> Option<u32> {
+        self.uids.get(package).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> PackageList {
+        PackageList::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/packages.list"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn resolves_packages() {
+        let packages = fixture();
+        assert_eq!(packages.uid("com.android.providers.telephony"), Some(1001));
+        assert_eq!(packages.uid("com.google.android.gms"), Some(10120));
+        assert_eq!(packages.uid("de.amosproj3.ziofa"), Some(10195));
+        assert_eq!(packages.uid("com.example.missing"), None);
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let packages = fixture();
+        assert_eq!(packages.uid("com.example.broken"), None);
+        assert_eq!(packages.uid("com.example.invalid"), None);
+        assert_eq!(packages.uids.len(), 3);
+    }
+}
@@ -2,6 +2,8 @@
 //
 // SPDX-License-Identifier: MIT
 
+use std::collections::{HashMap, HashSet};
+
 use procfs::{process::all_processes, ProcError};
 use shared::processes::{
     process::{Cmd, CmdlineData},
@@ -50,3 +52,77 @@ pub fn list_processes() -> Result<ProcessList, ProcError> {
         ProcessList { processes }
     })
 }
+
+/// Children of each process, read from the parent pids in procfs
+#[derive(Debug, Default)]
+pub struct ProcessTree {
+    children: HashMap<u32, Vec<u32>>,
+}
+
+impl ProcessTree {
+    pub fn read() -> Result<Self, ProcError> {
+        let parents = all_processes()?
+            .filter_map(|process| {
+                let stat = process.ok()?.stat().ok()?;
+                Some((
+                    u32::try_from(stat.pid).ok()?,
+                    u32::try_from(stat.ppid).ok()?,
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Self::from_parents(parents))
+    }
+
+    /// Takes pairs of a pid and its parent pid
+    pub fn from_parents(parents: impl IntoIterator<Item = (u32, u32)>) -> Self {
+        let mut children = HashMap::<u32, Vec<u32>>::new();
+        for (pid, ppid) in parents {
+            children.entry(ppid).or_default().push(pid);
+        }
+
+        Self { children }
+    }
+
+    /// All transitive children of `pid`, not including `pid` itself
+    pub fn descendants(&self, pid: u32) -> Vec<u32> {
+        // procfs is not read atomically, a reused pid could form a cycle
+        let mut visited = HashSet::from([pid]);
+        let mut pending = vec![pid];
+
+        while let Some(pid) = pending.pop() {
+            let children = self.children.get(&pid).into_iter().flatten();
+            for &child in children {
+                if visited.insert(child) {
+                    pending.push(child);
+                }
+            }
+        }
+
+        visited.remove(&pid);
+        visited.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descendants_are_transitive() {
+        let tree = ProcessTree::from_parents([(1, 0), (10, 1), (11, 10), (12, 11), (20, 1)]);
+
+        let mut descendants = tree.descendants(10);
+        descendants.sort();
+        assert_eq!(descendants, [11, 12]);
+        assert_eq!(tree.descendants(1).len(), 4);
+        assert!(tree.descendants(12).is_empty());
+    }
+
+    #[test]
+    fn descendants_terminate_on_cycles() {
+        let tree = ProcessTree::from_parents([(10, 11), (11, 10)]);
+
+        assert_eq!(tree.descendants(10), [11]);
+    }
+}
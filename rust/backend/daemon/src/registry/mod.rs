@@ -3,27 +3,312 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::fs::{create_dir_all, remove_dir_all};
+use std::{
+    fs::{create_dir_all, remove_dir_all},
+    path::PathBuf,
+    time::Duration,
+};
 
-use crate::constants::{GC_HEAP_META_JSON, ZIOFA_EBPF_PATH};
+use crate::{
+    collector::{FlightRecorderOptions, JournalOptions, TriggerRule},
+    constants::{
+        DEFAULT_FILTER_CAPACITY, DEFAULT_FLIGHT_RECORDER_SIZE, DEFAULT_JOURNAL_MAX_SIZE,
+        DEFAULT_JOURNAL_RETENTION_HOURS, DEFAULT_MAX_DUMPS, DEFAULT_PRIORITY_RING_BUF_SIZE,
+        DEFAULT_PROCESS_INFO_CACHE_SIZE, DEFAULT_REPLAY_BUFFER_SIZE, DEFAULT_RING_BUF_SIZE,
+        DEFAULT_TASK_INFO_CACHE_SIZE, EVENT_BATCH_WINDOW, FLIGHT_RECORDER_DUMP_DELAY,
+        FLIGHT_RECORDER_DUMP_PATH, GC_HEAP_META_JSON, JOURNAL_DELAY_MARGIN,
+        JOURNAL_SEGMENT_DURATION, JOURNAL_SEGMENT_SIZE, ZIOFA_EBPF_PATH,
+    },
+};
 
 mod pinning;
 mod single_owner;
 
 use aya::{
-    maps::{Array, HashMap, MapData, MapError, RingBuf},
+    maps::{Array, HashMap, LpmTrie, MapData, MapError, PerCpuArray, RingBuf},
     programs::{ProbeKind, ProgramError, RawTracePoint, UProbe},
     Btf, EbpfError, EbpfLoader,
 };
 use aya_log::EbpfLogger;
-use ebpf_types::{Equality, FilterConfig};
+use clap::ValueEnum;
+use ebpf_types::{
+    BlockingThresholdKey, Equality, EventKind, FilterConfig, RateLimit, RingBufRoute,
+    SuppressedEvents, MAX_PREFIX_FILTER_LEN,
+};
 use garbage_collection::{btf::apply_to_btf, HeapMetadata};
 use pinning::{LoadAndPin, PinMap, TryMapFromPin};
+use procfs::page_size;
 pub use single_owner::{RegistryGuard, RegistryItem};
 
 pub type OwnedRingBuf = RingBuf<MapData>;
 pub type OwnedHashMap<K, V> = HashMap<MapData, K, V>;
 pub type OwnedArray<V> = Array<MapData, V>;
+pub type OwnedLpmTrie<K, V> = LpmTrie<MapData, K, V>;
+pub type OwnedPerCpuArray<V> = PerCpuArray<MapData, V>;
+
+/// Options which are fixed once the ebpf programs are loaded
+#[derive(Debug, Clone, clap::Args)]
+pub struct LoadOptions {
+    /// Number of threads whose comm is cached in the kernel
+    #[arg(long, default_value_t = DEFAULT_TASK_INFO_CACHE_SIZE)]
+    pub task_info_cache_size: u32,
+    /// Number of processes whose exe path and cmdline are cached in the kernel
+    #[arg(long, default_value_t = DEFAULT_PROCESS_INFO_CACHE_SIZE)]
+    pub process_info_cache_size: u32,
+    /// Number of entries of each of the pid, uid and cgroup filters
+    #[arg(long, default_value_t = DEFAULT_FILTER_CAPACITY)]
+    pub id_filter_capacity: u32,
+    /// Number of entries of each of the comm, exe path and cmdline filters,
+    /// exact and prefix patterns are counted separately
+    #[arg(long, default_value_t = DEFAULT_FILTER_CAPACITY)]
+    pub string_filter_capacity: u32,
+    /// Size in bytes of the shared ring buffer and of each dedicated one,
+    /// rounded up to a power of two number of pages
+    #[arg(
+        long,
+        default_value_t = DEFAULT_RING_BUF_SIZE,
+        value_parser = parse_ring_buf_size
+    )]
+    pub ring_buf_size: u32,
+    /// Event kinds with a ring buffer of their own, e.g. `write,blocking`
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub dedicated_ring_bufs: Vec<EventKindArg>,
+    /// Event kinds which share a separate ring buffer, so they are never
+    /// crowded out by high volume kinds. A dedicated ring buffer takes precedence.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub priority_events: Vec<EventKindArg>,
+    /// Size in bytes of the ring buffer of the priority events
+    #[arg(
+        long,
+        default_value_t = DEFAULT_PRIORITY_RING_BUF_SIZE,
+        value_parser = parse_ring_buf_size
+    )]
+    pub priority_ring_buf_size: u32,
+    /// Milliseconds the collector holds events to send them in the order of
+    /// their timestamps, 0 sends them in the order they were read
+    #[arg(long, default_value_t = 0)]
+    pub reorder_window_ms: u64,
+    /// Number of the latest events kept for streams which resume after a
+    /// reconnect, 0 disables resuming
+    #[arg(long, default_value_t = DEFAULT_REPLAY_BUFFER_SIZE)]
+    pub replay_buffer_size: usize,
+    /// Seconds of events the flight recorder keeps in memory, whether they are
+    /// streamed or not, 0 disables it
+    #[arg(long, default_value_t = 0)]
+    pub flight_recorder_secs: u64,
+    /// Upper bound in bytes of the encoded events of the flight recorder
+    #[arg(long, default_value_t = DEFAULT_FLIGHT_RECORDER_SIZE)]
+    pub flight_recorder_size: usize,
+    /// Directory of the dumps of the flight recorder
+    #[arg(long, default_value = FLIGHT_RECORDER_DUMP_PATH)]
+    pub dump_dir: PathBuf,
+    /// Number of dumps kept in the dump directory, the oldest are removed
+    /// first, 0 keeps all of them
+    #[arg(long, default_value_t = DEFAULT_MAX_DUMPS)]
+    pub max_dumps: usize,
+    /// Dumps the flight recorder when a blocking syscall takes longer
+    /// than this many milliseconds
+    #[arg(long)]
+    pub dump_on_blocking_ms: Option<u64>,
+    /// Dumps the flight recorder when a process crashes
+    #[arg(long)]
+    pub dump_on_crash: bool,
+    /// Directory of the journal which keeps events on disk for later queries,
+    /// events are not kept without it
+    #[arg(long)]
+    pub journal_dir: Option<PathBuf>,
+    /// Upper bound in bytes of the journal, its oldest events are removed first
+    #[arg(long, default_value_t = DEFAULT_JOURNAL_MAX_SIZE)]
+    pub journal_max_size: u64,
+    /// Hours after which events are removed from the journal
+    #[arg(long, default_value_t = DEFAULT_JOURNAL_RETENTION_HOURS)]
+    pub journal_retention_hours: u64,
+}
+
+impl LoadOptions {
+    pub fn reorder_window(&self) -> Duration {
+        Duration::from_millis(self.reorder_window_ms)
+    }
+
+    pub fn flight_recorder(&self) -> Option<FlightRecorderOptions> {
+        if self.flight_recorder_secs == 0 {
+            return None;
+        }
+
+        let triggers = self
+            .dump_on_blocking_ms
+            .map(|millis| TriggerRule::BlockingAbove(Duration::from_millis(millis)))
+            .into_iter()
+            .chain(self.dump_on_crash.then_some(TriggerRule::Crash))
+            .collect();
+
+        Some(FlightRecorderOptions {
+            window: Duration::from_secs(self.flight_recorder_secs),
+            max_bytes: self.flight_recorder_size,
+            dump_dir: self.dump_dir.clone(),
+            max_dumps: self.max_dumps,
+            dump_delay: FLIGHT_RECORDER_DUMP_DELAY,
+            triggers,
+        })
+    }
+
+    pub fn journal(&self) -> Option<JournalOptions> {
+        Some(JournalOptions {
+            dir: self.journal_dir.clone()?,
+            max_bytes: self.journal_max_size,
+            max_age: Duration::from_secs(self.journal_retention_hours * 3600),
+            segment_size: JOURNAL_SEGMENT_SIZE,
+            segment_duration: JOURNAL_SEGMENT_DURATION,
+            // Events are held by the reorder window, then by the batch
+            max_delay: self
+                .reorder_window()
+                .saturating_add(EVENT_BATCH_WINDOW + JOURNAL_DELAY_MARGIN),
+        })
+    }
+
+    fn ring_buf_route(&self, kind: EventKindArg) -> RingBufRoute {
+        if self.dedicated_ring_bufs.contains(&kind) {
+            RingBufRoute::Dedicated
+        } else if self.priority_events.contains(&kind) {
+            RingBufRoute::Priority
+        } else {
+            RingBufRoute::Shared
+        }
+    }
+
+    /// All ring buffers of the kernel, whether they receive events or not
+    fn ring_bufs(&self) -> Vec<RingBufOptions> {
+        let uses = |route| {
+            EventKindArg::value_variants()
+                .iter()
+                .any(|kind| self.ring_buf_route(*kind) == route)
+        };
+        let dedicated = EventKindArg::value_variants().iter().map(|kind| {
+            let used = self.ring_buf_route(*kind) == RingBufRoute::Dedicated;
+            RingBufOptions::new(kind.ring_buf(), self.ring_buf_size, used)
+        });
+
+        [
+            RingBufOptions::new("EVENTS", self.ring_buf_size, true),
+            RingBufOptions::new(
+                "PRIORITY_EVENTS",
+                self.priority_ring_buf_size,
+                uses(RingBufRoute::Priority),
+            ),
+        ]
+        .into_iter()
+        .chain(dedicated)
+        .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct RingBufOptions {
+    name: &'static str,
+    byte_size: u32,
+    used: bool,
+}
+
+impl RingBufOptions {
+    /// Unused ring buffers only take a single page
+    fn new(name: &'static str, size: u32, used: bool) -> Self {
+        let size = if used { size } else { 0 };
+        Self {
+            name,
+            // Larger sizes are rejected when parsing the options
+            byte_size: ring_buf_byte_size(size).unwrap_or(MAX_RING_BUF_SIZE),
+            used,
+        }
+    }
+}
+
+/// The largest power of two which fits the size of a ring buffer
+const MAX_RING_BUF_SIZE: u32 = 1 << 31;
+
+/// The kernel only accepts a power of two multiple of the page size, none
+/// if the size cannot be rounded up to one
+fn ring_buf_byte_size(size: u32) -> Option<u32> {
+    size.max(page_size() as u32).checked_next_power_of_two()
+}
+
+fn parse_ring_buf_size(arg: &str) -> Result<u32, String> {
+    let size = arg.parse().map_err(|error| format!("{error}"))?;
+    match ring_buf_byte_size(size) {
+        Some(_) => Ok(size),
+        None => Err(format!(
+            "{size} bytes cannot be rounded up to a power of two, \
+             at most {MAX_RING_BUF_SIZE} bytes are supported"
+        )),
+    }
+}
+
+/// Event kinds as named on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EventKindArg {
+    Write,
+    Blocking,
+    Signal,
+    GarbageCollect,
+    FileDescriptorChange,
+    JniReferences,
+    Process,
+    Crash,
+}
+
+impl EventKindArg {
+    fn ebpf(self) -> EventKind {
+        match self {
+            EventKindArg::Write => EventKind::Write,
+            EventKindArg::Blocking => EventKind::Blocking,
+            EventKindArg::Signal => EventKind::Signal,
+            EventKindArg::GarbageCollect => EventKind::GarbageCollect,
+            EventKindArg::FileDescriptorChange => EventKind::FileDescriptorChange,
+            EventKindArg::JniReferences => EventKind::JniReferences,
+            EventKindArg::Process => EventKind::ProcessLifecycle,
+            EventKindArg::Crash => EventKind::Crash,
+        }
+    }
+
+    /// Name of the dedicated ring buffer in the kernel
+    fn ring_buf(self) -> &'static str {
+        match self {
+            EventKindArg::Write => "WRITE_EVENTS",
+            EventKindArg::Blocking => "BLOCKING_EVENTS",
+            EventKindArg::Signal => "SIGNAL_EVENTS",
+            EventKindArg::GarbageCollect => "GARBAGE_COLLECT_EVENTS",
+            EventKindArg::FileDescriptorChange => "FILE_DESCRIPTOR_CHANGE_EVENTS",
+            EventKindArg::JniReferences => "JNI_REFERENCES_EVENTS",
+            EventKindArg::Process => "PROCESS_LIFECYCLE_EVENTS",
+            EventKindArg::Crash => "CRASH_EVENTS",
+        }
+    }
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            task_info_cache_size: DEFAULT_TASK_INFO_CACHE_SIZE,
+            process_info_cache_size: DEFAULT_PROCESS_INFO_CACHE_SIZE,
+            id_filter_capacity: DEFAULT_FILTER_CAPACITY,
+            string_filter_capacity: DEFAULT_FILTER_CAPACITY,
+            ring_buf_size: DEFAULT_RING_BUF_SIZE,
+            dedicated_ring_bufs: Vec::new(),
+            priority_events: Vec::new(),
+            priority_ring_buf_size: DEFAULT_PRIORITY_RING_BUF_SIZE,
+            reorder_window_ms: 0,
+            replay_buffer_size: DEFAULT_REPLAY_BUFFER_SIZE,
+            flight_recorder_secs: 0,
+            flight_recorder_size: DEFAULT_FLIGHT_RECORDER_SIZE,
+            dump_dir: PathBuf::from(FLIGHT_RECORDER_DUMP_PATH),
+            max_dumps: DEFAULT_MAX_DUMPS,
+            dump_on_blocking_ms: None,
+            dump_on_crash: false,
+            journal_dir: None,
+            journal_max_size: DEFAULT_JOURNAL_MAX_SIZE,
+            journal_retention_hours: DEFAULT_JOURNAL_RETENTION_HOURS,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct EbpfRegistry {
@@ -35,17 +320,30 @@ pub struct EbpfRegistry {
 #[derive(Clone)]
 pub struct EbpfConfigRegistry {
     pub pid_filter: RegistryItem<OwnedHashMap<u32, Equality>>,
+    pub pid_filter_follow: RegistryItem<OwnedArray<u64>>,
+    pub uid_filter: RegistryItem<OwnedHashMap<u32, Equality>>,
+    pub cgroup_filter: RegistryItem<OwnedHashMap<u64, Equality>>,
     pub comm_filter: RegistryItem<OwnedHashMap<[u8; 16], Equality>>,
+    pub process_comm_filter: RegistryItem<OwnedHashMap<[u8; 16], Equality>>,
+    pub comm_prefix_filter: RegistryItem<OwnedLpmTrie<[u8; 16], Equality>>,
+    pub exe_path_prefix_filter: RegistryItem<OwnedLpmTrie<[u8; MAX_PREFIX_FILTER_LEN], Equality>>,
+    pub cmdline_prefix_filter: RegistryItem<OwnedLpmTrie<[u8; MAX_PREFIX_FILTER_LEN], Equality>>,
+    pub process_comm_prefix_filter: RegistryItem<OwnedLpmTrie<[u8; 16], Equality>>,
     pub exe_path_filter: RegistryItem<OwnedHashMap<[u8; 4096], Equality>>,
     pub cmdline_filter: RegistryItem<OwnedHashMap<[u8; 256], Equality>>,
-    pub global_blocking_threshold: RegistryItem<OwnedArray<u64>>,
+    pub blocking_thresholds: RegistryItem<OwnedHashMap<BlockingThresholdKey, u64>>,
+    pub blocking_ignored_syscalls: RegistryItem<OwnedHashMap<u32, u8>>,
+    pub signal_deny_mask: RegistryItem<OwnedArray<u64>>,
+    pub rate_limits: RegistryItem<OwnedArray<RateLimit>>,
+    pub suppressed_events: RegistryItem<OwnedPerCpuArray<SuppressedEvents>>,
     pub filter_config: RegistryItem<OwnedArray<FilterConfig>>,
     pub config: RegistryItem<OwnedArray<u32>>,
 }
 
 #[derive(Clone)]
 pub struct EbpfEventRegistry {
-    pub events: RegistryItem<OwnedRingBuf>,
+    /// Only the ring buffers which receive events
+    pub ring_bufs: Vec<RegistryItem<OwnedRingBuf>>,
 }
 
 #[derive(Clone)]
@@ -56,6 +354,17 @@ pub struct EbpfProgramRegistry {
     pub sys_exit_blocking: RegistryItem<RawTracePoint>,
     pub sys_enter_signal: RegistryItem<RawTracePoint>,
     pub sys_exit_signal: RegistryItem<RawTracePoint>,
+    pub trace_signal_generate: RegistryItem<RawTracePoint>,
+    pub trace_signal_deliver: RegistryItem<RawTracePoint>,
+    pub trace_process_fork: RegistryItem<RawTracePoint>,
+    pub trace_process_exec: RegistryItem<RawTracePoint>,
+    pub trace_process_exit: RegistryItem<RawTracePoint>,
+    pub trace_crash: RegistryItem<RawTracePoint>,
+    pub evict_cache_exec: RegistryItem<RawTracePoint>,
+    pub evict_cache_exit: RegistryItem<RawTracePoint>,
+    pub evict_cache_rename: RegistryItem<RawTracePoint>,
+    pub follow_children_fork: RegistryItem<RawTracePoint>,
+    pub follow_children_exit: RegistryItem<RawTracePoint>,
     pub sys_enter_fdtracking: RegistryItem<RawTracePoint>,
     pub sys_exit_fdtracking: RegistryItem<RawTracePoint>,
     pub trace_jni_add_local: RegistryItem<UProbe>,
@@ -67,10 +376,10 @@ pub struct EbpfProgramRegistry {
 }
 
 impl EbpfRegistry {
-    fn from_pin() -> Result<Self, EbpfError> {
+    fn from_pin(options: &LoadOptions) -> Result<Self, EbpfError> {
         Ok(Self {
             config: EbpfConfigRegistry::from_pin()?,
-            event: EbpfEventRegistry::from_pin()?,
+            event: EbpfEventRegistry::from_pin(options)?,
             program: EbpfProgramRegistry::from_pin()?,
         })
     }
@@ -80,8 +389,31 @@ impl EbpfConfigRegistry {
     fn from_pin() -> Result<Self, MapError> {
         Ok(Self {
             pid_filter: HashMap::<_, u32, Equality>::try_from_pin(path("PID_FILTER"))?.into(),
+            pid_filter_follow: Array::<_, u64>::try_from_pin(path("PID_FILTER_FOLLOW"))?.into(),
+            uid_filter: HashMap::<_, u32, Equality>::try_from_pin(path("UID_FILTER"))?.into(),
+            cgroup_filter: HashMap::<_, u64, Equality>::try_from_pin(path("CGROUP_FILTER"))?.into(),
             comm_filter: HashMap::<_, [u8; 16], Equality>::try_from_pin(path("COMM_FILTER"))?
                 .into(),
+            process_comm_filter: HashMap::<_, [u8; 16], Equality>::try_from_pin(path(
+                "PROCESS_COMM_FILTER",
+            ))?
+            .into(),
+            comm_prefix_filter: LpmTrie::<_, [u8; 16], Equality>::try_from_pin(path(
+                "COMM_PREFIX_FILTER",
+            ))?
+            .into(),
+            exe_path_prefix_filter: LpmTrie::<_, [u8; MAX_PREFIX_FILTER_LEN], Equality>::try_from_pin(
+                path("EXE_PATH_PREFIX_FILTER"),
+            )?
+            .into(),
+            cmdline_prefix_filter: LpmTrie::<_, [u8; MAX_PREFIX_FILTER_LEN], Equality>::try_from_pin(
+                path("CMDLINE_PREFIX_FILTER"),
+            )?
+            .into(),
+            process_comm_prefix_filter: LpmTrie::<_, [u8; 16], Equality>::try_from_pin(path(
+                "PROCESS_COMM_PREFIX_FILTER",
+            ))?
+            .into(),
             exe_path_filter: HashMap::<_, [u8; 4096], Equality>::try_from_pin(path(
                 "EXE_PATH_FILTER",
             ))?
@@ -90,8 +422,18 @@ impl EbpfConfigRegistry {
                 "CMDLINE_FILTER",
             ))?
             .into(),
-            global_blocking_threshold: Array::<_, u64>::try_from_pin(path(
-                "GLOBAL_BLOCKING_THRESHOLD",
+            blocking_thresholds: HashMap::<_, BlockingThresholdKey, u64>::try_from_pin(path(
+                "BLOCKING_THRESHOLDS",
+            ))?
+            .into(),
+            blocking_ignored_syscalls: HashMap::<_, u32, u8>::try_from_pin(path(
+                "BLOCKING_IGNORED_SYSCALLS",
+            ))?
+            .into(),
+            signal_deny_mask: Array::<_, u64>::try_from_pin(path("SIGNAL_DENY_MASK"))?.into(),
+            rate_limits: Array::<_, RateLimit>::try_from_pin(path("RATE_LIMITS"))?.into(),
+            suppressed_events: PerCpuArray::<_, SuppressedEvents>::try_from_pin(path(
+                "SUPPRESSED_EVENTS",
             ))?
             .into(),
             filter_config: Array::<_, FilterConfig>::try_from_pin(path("FILTER_CONFIG"))?.into(),
@@ -101,10 +443,15 @@ impl EbpfConfigRegistry {
 }
 
 impl EbpfEventRegistry {
-    fn from_pin() -> Result<Self, MapError> {
-        Ok(Self {
-            events: RingBuf::try_from_pin(path("EVENTS"))?.into(),
-        })
+    fn from_pin(options: &LoadOptions) -> Result<Self, MapError> {
+        let ring_bufs = options
+            .ring_bufs()
+            .into_iter()
+            .filter(|ring_buf| ring_buf.used)
+            .map(|ring_buf| Ok(RingBuf::try_from_pin(path(ring_buf.name))?.into()))
+            .collect::<Result<_, MapError>>()?;
+
+        Ok(Self { ring_bufs })
     }
 }
 
@@ -117,6 +464,17 @@ impl EbpfProgramRegistry {
             sys_exit_blocking: RawTracePoint::from_pin(path("sys_exit_blocking"))?.into(),
             sys_enter_signal: RawTracePoint::from_pin(path("sys_enter_signal"))?.into(),
             sys_exit_signal: RawTracePoint::from_pin(path("sys_exit_signal"))?.into(),
+            trace_signal_generate: RawTracePoint::from_pin(path("trace_signal_generate"))?.into(),
+            trace_signal_deliver: RawTracePoint::from_pin(path("trace_signal_deliver"))?.into(),
+            trace_process_fork: RawTracePoint::from_pin(path("trace_process_fork"))?.into(),
+            trace_process_exec: RawTracePoint::from_pin(path("trace_process_exec"))?.into(),
+            trace_process_exit: RawTracePoint::from_pin(path("trace_process_exit"))?.into(),
+            trace_crash: RawTracePoint::from_pin(path("trace_crash"))?.into(),
+            evict_cache_exec: RawTracePoint::from_pin(path("evict_cache_exec"))?.into(),
+            evict_cache_exit: RawTracePoint::from_pin(path("evict_cache_exit"))?.into(),
+            evict_cache_rename: RawTracePoint::from_pin(path("evict_cache_rename"))?.into(),
+            follow_children_fork: RawTracePoint::from_pin(path("follow_children_fork"))?.into(),
+            follow_children_exit: RawTracePoint::from_pin(path("follow_children_exit"))?.into(),
             sys_enter_fdtracking: RawTracePoint::from_pin(path("sys_enter_fdtracking"))?.into(),
             sys_exit_fdtracking: RawTracePoint::from_pin(path("sys_exit_fdtracking"))?.into(),
             trace_jni_add_local: UProbe::from_pin(path("trace_jni_add_local"), ProbeKind::UProbe)?
@@ -139,7 +497,7 @@ impl EbpfProgramRegistry {
     }
 }
 
-pub fn load_and_pin() -> Result<EbpfRegistry, EbpfError> {
+pub fn load_and_pin(options: &LoadOptions) -> Result<EbpfRegistry, EbpfError> {
     // TODO: better map dir handling
     let _ = remove_dir_all(ZIOFA_EBPF_PATH);
     create_dir_all(ZIOFA_EBPF_PATH).unwrap();
@@ -150,9 +508,33 @@ pub fn load_and_pin() -> Result<EbpfRegistry, EbpfError> {
 
     apply_to_btf(&mut btf, &heap_meta)?;
 
-    let mut ebpf = EbpfLoader::default()
+    let mut loader = EbpfLoader::default();
+    loader
         .btf(Some(&btf))
         .map_pin_path(ZIOFA_EBPF_PATH)
+        .set_max_entries("TASK_INFO", options.task_info_cache_size)
+        .set_max_entries("PROCESS_INFO", options.process_info_cache_size);
+
+    for map in ["PID_FILTER", "UID_FILTER", "CGROUP_FILTER"] {
+        loader.set_max_entries(map, options.id_filter_capacity);
+    }
+    for map in [
+        "COMM_FILTER",
+        "PROCESS_COMM_FILTER",
+        "EXE_PATH_FILTER",
+        "CMDLINE_FILTER",
+        "COMM_PREFIX_FILTER",
+        "PROCESS_COMM_PREFIX_FILTER",
+        "EXE_PATH_PREFIX_FILTER",
+        "CMDLINE_PREFIX_FILTER",
+    ] {
+        loader.set_max_entries(map, options.string_filter_capacity);
+    }
+    for ring_buf in options.ring_bufs() {
+        loader.set_max_entries(ring_buf.name, ring_buf.byte_size);
+    }
+
+    let mut ebpf = loader
         .load(aya::include_bytes_aligned!(concat!(
             env!("OUT_DIR"),
             "/ebpf-refactored"
@@ -161,15 +543,35 @@ pub fn load_and_pin() -> Result<EbpfRegistry, EbpfError> {
 
     EbpfLogger::init(&mut ebpf).unwrap();
 
+    let mut ring_buf_routes =
+        Array::<_, RingBufRoute>::try_from(ebpf.map_mut("RING_BUF_ROUTES").unwrap())?;
+    for kind in EventKindArg::value_variants() {
+        ring_buf_routes.set(kind.ebpf() as u32, options.ring_buf_route(*kind), 0)?;
+    }
+
     ebpf.pin_map("PID_FILTER", ZIOFA_EBPF_PATH).unwrap();
+    ebpf.pin_map("PID_FILTER_FOLLOW", ZIOFA_EBPF_PATH).unwrap();
+    ebpf.pin_map("UID_FILTER", ZIOFA_EBPF_PATH).unwrap();
+    ebpf.pin_map("CGROUP_FILTER", ZIOFA_EBPF_PATH).unwrap();
     ebpf.pin_map("COMM_FILTER", ZIOFA_EBPF_PATH).unwrap();
+    ebpf.pin_map("PROCESS_COMM_FILTER", ZIOFA_EBPF_PATH).unwrap();
+    ebpf.pin_map("COMM_PREFIX_FILTER", ZIOFA_EBPF_PATH).unwrap();
+    ebpf.pin_map("EXE_PATH_PREFIX_FILTER", ZIOFA_EBPF_PATH).unwrap();
+    ebpf.pin_map("CMDLINE_PREFIX_FILTER", ZIOFA_EBPF_PATH).unwrap();
+    ebpf.pin_map("PROCESS_COMM_PREFIX_FILTER", ZIOFA_EBPF_PATH).unwrap();
     ebpf.pin_map("EXE_PATH_FILTER", ZIOFA_EBPF_PATH).unwrap();
     ebpf.pin_map("CMDLINE_FILTER", ZIOFA_EBPF_PATH).unwrap();
     ebpf.pin_map("FILTER_CONFIG", ZIOFA_EBPF_PATH).unwrap();
     ebpf.pin_map("CONFIG", ZIOFA_EBPF_PATH).unwrap();
-    ebpf.pin_map("EVENTS", ZIOFA_EBPF_PATH).unwrap();
-    ebpf.pin_map("GLOBAL_BLOCKING_THRESHOLD", ZIOFA_EBPF_PATH)
+    for ring_buf in options.ring_bufs().iter().filter(|ring_buf| ring_buf.used) {
+        ebpf.pin_map(ring_buf.name, ZIOFA_EBPF_PATH).unwrap();
+    }
+    ebpf.pin_map("BLOCKING_THRESHOLDS", ZIOFA_EBPF_PATH).unwrap();
+    ebpf.pin_map("BLOCKING_IGNORED_SYSCALLS", ZIOFA_EBPF_PATH)
         .unwrap();
+    ebpf.pin_map("SIGNAL_DENY_MASK", ZIOFA_EBPF_PATH).unwrap();
+    ebpf.pin_map("RATE_LIMITS", ZIOFA_EBPF_PATH).unwrap();
+    ebpf.pin_map("SUPPRESSED_EVENTS", ZIOFA_EBPF_PATH).unwrap();
 
     ebpf.load_and_pin::<RawTracePoint>("sys_enter_write", ZIOFA_EBPF_PATH)
         .unwrap();
@@ -183,6 +585,28 @@ pub fn load_and_pin() -> Result<EbpfRegistry, EbpfError> {
         .unwrap();
     ebpf.load_and_pin::<RawTracePoint>("sys_exit_signal", ZIOFA_EBPF_PATH)
         .unwrap();
+    ebpf.load_and_pin::<RawTracePoint>("trace_signal_generate", ZIOFA_EBPF_PATH)
+        .unwrap();
+    ebpf.load_and_pin::<RawTracePoint>("trace_signal_deliver", ZIOFA_EBPF_PATH)
+        .unwrap();
+    ebpf.load_and_pin::<RawTracePoint>("trace_process_fork", ZIOFA_EBPF_PATH)
+        .unwrap();
+    ebpf.load_and_pin::<RawTracePoint>("trace_process_exec", ZIOFA_EBPF_PATH)
+        .unwrap();
+    ebpf.load_and_pin::<RawTracePoint>("trace_process_exit", ZIOFA_EBPF_PATH)
+        .unwrap();
+    ebpf.load_and_pin::<RawTracePoint>("trace_crash", ZIOFA_EBPF_PATH)
+        .unwrap();
+    ebpf.load_and_pin::<RawTracePoint>("evict_cache_exec", ZIOFA_EBPF_PATH)
+        .unwrap();
+    ebpf.load_and_pin::<RawTracePoint>("evict_cache_exit", ZIOFA_EBPF_PATH)
+        .unwrap();
+    ebpf.load_and_pin::<RawTracePoint>("evict_cache_rename", ZIOFA_EBPF_PATH)
+        .unwrap();
+    ebpf.load_and_pin::<RawTracePoint>("follow_children_fork", ZIOFA_EBPF_PATH)
+        .unwrap();
+    ebpf.load_and_pin::<RawTracePoint>("follow_children_exit", ZIOFA_EBPF_PATH)
+        .unwrap();
     ebpf.load_and_pin::<RawTracePoint>("sys_enter_fdtracking", ZIOFA_EBPF_PATH)
         .unwrap();
     ebpf.load_and_pin::<RawTracePoint>("sys_exit_fdtracking", ZIOFA_EBPF_PATH)
@@ -200,9 +624,67 @@ pub fn load_and_pin() -> Result<EbpfRegistry, EbpfError> {
     ebpf.load_and_pin::<UProbe>("trace_gc_exit", ZIOFA_EBPF_PATH)
         .unwrap();
 
-    EbpfRegistry::from_pin()
+    EbpfRegistry::from_pin(options)
 }
 
 fn path(name: &str) -> String {
     format!("{ZIOFA_EBPF_PATH}/{name}")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_buf_sizes_are_pages() {
+        let page = page_size() as u32;
+
+        assert_eq!(ring_buf_byte_size(0), Some(page));
+        assert_eq!(ring_buf_byte_size(page + 1), Some(2 * page));
+        assert_eq!(
+            ring_buf_byte_size(DEFAULT_RING_BUF_SIZE),
+            Some(DEFAULT_RING_BUF_SIZE)
+        );
+        assert_eq!(ring_buf_byte_size(MAX_RING_BUF_SIZE + 1), None);
+    }
+
+    #[test]
+    fn rejects_ring_buf_sizes_beyond_the_largest_power_of_two() {
+        assert_eq!(
+            parse_ring_buf_size(&MAX_RING_BUF_SIZE.to_string()),
+            Ok(MAX_RING_BUF_SIZE)
+        );
+        assert!(parse_ring_buf_size(&(MAX_RING_BUF_SIZE + 1).to_string()).is_err());
+        assert!(parse_ring_buf_size("many").is_err());
+    }
+
+    #[test]
+    fn dedicated_ring_bufs_take_precedence() {
+        let options = LoadOptions {
+            dedicated_ring_bufs: vec![EventKindArg::Write],
+            priority_events: vec![EventKindArg::Write, EventKindArg::Crash],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.ring_buf_route(EventKindArg::Write),
+            RingBufRoute::Dedicated
+        );
+        assert_eq!(
+            options.ring_buf_route(EventKindArg::Crash),
+            RingBufRoute::Priority
+        );
+        assert_eq!(
+            options.ring_buf_route(EventKindArg::Signal),
+            RingBufRoute::Shared
+        );
+
+        let used = options
+            .ring_bufs()
+            .into_iter()
+            .filter(|ring_buf| ring_buf.used)
+            .map(|ring_buf| ring_buf.name)
+            .collect::<Vec<_>>();
+        assert_eq!(used, ["EVENTS", "PRIORITY_EVENTS", "WRITE_EVENTS"]);
+    }
+}
@@ -5,13 +5,13 @@
 use std::{io, path::Path};
 
 use aya::{
-    maps::{Array, HashMap, Map, MapData, MapError, RingBuf},
+    maps::{Array, HashMap, LpmTrie, Map, MapData, MapError, PerCpuArray, RingBuf},
     pin::PinError,
     programs::{KProbe, Program, ProgramError, RawTracePoint, TracePoint, UProbe},
     Ebpf, EbpfError, Pod,
 };
 
-use super::{OwnedArray, OwnedHashMap, OwnedRingBuf};
+use super::{OwnedArray, OwnedHashMap, OwnedLpmTrie, OwnedPerCpuArray, OwnedRingBuf};
 
 pub trait EbpfLoad {
     fn load(&mut self) -> Result<(), ProgramError>;
@@ -147,6 +147,15 @@ impl<K: Pod, V: Pod> TryMapFromPin for OwnedHashMap<K, V> {
     }
 }
 
+impl<K: Pod, V: Pod> TryMapFromPin for OwnedLpmTrie<K, V> {
+    fn try_from_pin<P: AsRef<Path>>(path: P) -> Result<Self, MapError>
+    where
+        Self: Sized,
+    {
+        LpmTrie::<_, K, V>::try_from(Map::LpmTrie(MapData::from_pin(path)?))
+    }
+}
+
 impl<V: Pod> TryMapFromPin for OwnedArray<V> {
     fn try_from_pin<P: AsRef<Path>>(path: P) -> Result<Self, MapError>
     where
@@ -155,3 +164,12 @@ impl<V: Pod> TryMapFromPin for OwnedArray<V> {
         Array::<_, V>::try_from(Map::Array(MapData::from_pin(path)?))
     }
 }
+
+impl<V: Pod> TryMapFromPin for OwnedPerCpuArray<V> {
+    fn try_from_pin<P: AsRef<Path>>(path: P) -> Result<Self, MapError>
+    where
+        Self: Sized,
+    {
+        PerCpuArray::<_, V>::try_from(Map::PerCpuArray(MapData::from_pin(path)?))
+    }
+}
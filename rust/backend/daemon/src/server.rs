@@ -7,11 +7,13 @@
 
 use std::sync::Arc;
 
-use async_broadcast::{broadcast, Receiver, Sender};
 use ractor::{call, Actor, ActorRef};
 use shared::{
     config::Configuration,
-    events::Event,
+    events::{
+        DumpFlightRecorderRequest, Event, EventBatch, EventQuery, EventSeriesList, FlightRecording,
+        StreamOptions, SuppressedEvents, TimeSeriesQuery,
+    },
     processes::ProcessList,
     symbols::{
         GetSymbolOffsetRequest, GetSymbolOffsetResponse, SearchSymbolsRequest,
@@ -19,17 +21,23 @@ use shared::{
     },
     ziofa::ziofa_server::{Ziofa, ZiofaServer},
 };
-use tokio::sync::Mutex;
+use tokio::{
+    sync::{mpsc, Mutex},
+    task::spawn_blocking,
+};
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{transport::Server, Request, Response, Status};
 
 use crate::{
-    collector::{CollectorSupervisor, CollectorSupervisorArguments},
+    collector::{
+        CollectorSupervisor, CollectorSupervisorArguments, FlightRecorder, Journal, Resolution,
+        SubscriptionStream, Subscriptions, TimeSeriesStore,
+    },
     constants,
-    ebpf_utils::EbpfErrorWrapper,
-    features::Features,
+    features::{Features, GlobFilters},
     filesystem::{ConfigurationStorage, NormalConfigurationStorage},
     procfs_utils::{list_processes, ProcErrorWrapper},
-    registry,
+    registry::{self, LoadOptions},
     symbols::actors::{GetOffsetRequest, SearchReq, SymbolActor, SymbolActorMsg},
 };
 
@@ -62,16 +70,35 @@ where
     }
 }
 
+#[derive(Default)]
 pub struct Channel {
-    tx: Sender<Result<Event, Status>>,
-    rx: Receiver<Result<Event, Status>>,
+    events: Arc<Subscriptions<Event>>,
+    batches: Arc<Subscriptions<EventBatch>>,
+    flight_recorder: Option<Arc<FlightRecorder>>,
+    journal: Option<Arc<Journal>>,
+    time_series: Arc<TimeSeriesStore>,
+    globs: Arc<GlobFilters>,
 }
 
 impl Channel {
-    pub fn new() -> Self {
-        let (mut tx, rx) = broadcast(8192);
-        tx.set_overflow(true);
-        Self { tx, rx }
+    /// Both kinds of streams can resume within the latest `replay_capacity` events
+    pub fn new(
+        replay_capacity: usize,
+        flight_recorder: Option<FlightRecorder>,
+        journal: Option<Journal>,
+    ) -> Self {
+        Self {
+            events: Arc::new(Subscriptions::new(replay_capacity)),
+            batches: Arc::new(Subscriptions::new(replay_capacity)),
+            flight_recorder: flight_recorder.map(Arc::new),
+            journal: journal.map(Arc::new),
+            time_series: Arc::new(TimeSeriesStore::new(
+                &constants::TIME_SERIES_RESOLUTIONS
+                    .map(|(width, retention)| Resolution { width, retention }),
+                constants::TIME_SERIES_MAX_KEYS,
+            )),
+            globs: Arc::default(),
+        }
     }
 }
 
@@ -108,21 +135,29 @@ where
         let mut features_guard = self.features.lock().await;
 
         // TODO: set config path
-        features_guard
-            .update_from_config(&config)
-            .await
-            .map_err(EbpfErrorWrapper::from)?;
+        features_guard.update_from_config(&config).await?;
 
         Ok(Response::new(()))
     }
 
-    type InitStreamStream = Receiver<Result<Event, Status>>;
+    type InitStreamStream = SubscriptionStream<Event>;
 
     async fn init_stream(
         &self,
-        _: Request<()>,
+        request: Request<StreamOptions>,
     ) -> Result<Response<Self::InitStreamStream>, Status> {
-        Ok(Response::new(self.channel.rx.clone()))
+        let stream = self.channel.events.subscribe(request.get_ref())?;
+        Ok(Response::new(stream))
+    }
+
+    type InitBatchedStreamStream = SubscriptionStream<EventBatch>;
+
+    async fn init_batched_stream(
+        &self,
+        request: Request<StreamOptions>,
+    ) -> Result<Response<Self::InitBatchedStreamStream>, Status> {
+        let stream = self.channel.batches.subscribe(request.get_ref())?;
+        Ok(Response::new(stream))
     }
 
     async fn index_symbols(&self, _: Request<()>) -> Result<Response<()>, Status> {
@@ -166,27 +201,116 @@ where
 
         Ok(Response::new(GetSymbolOffsetResponse { offset }))
     }
+
+    async fn get_suppressed_events(
+        &self,
+        _: Request<()>,
+    ) -> Result<Response<SuppressedEvents>, Status> {
+        let suppressed_events = self.features.lock().await.suppressed_events()?;
+        Ok(Response::new(suppressed_events))
+    }
+
+    async fn dump_flight_recorder(
+        &self,
+        request: Request<DumpFlightRecorderRequest>,
+    ) -> Result<Response<FlightRecording>, Status> {
+        let recorder = self.channel.flight_recorder.clone().ok_or_else(|| {
+            Status::failed_precondition("the daemon was started without a flight recorder")
+        })?;
+
+        if !request.get_ref().write_to_file {
+            // Copying the window can take a while
+            let events = spawn_blocking(move || recorder.snapshot())
+                .await
+                .map_err(|e| Status::from_error(Box::new(e)))?;
+            return Ok(Response::new(FlightRecording {
+                event_count: events.len() as u64,
+                events,
+                path: None,
+            }));
+        }
+
+        let (path, event_count) = spawn_blocking(move || recorder.dump("request"))
+            .await
+            .map_err(|e| Status::from_error(Box::new(e)))??;
+        Ok(Response::new(FlightRecording {
+            events: Vec::new(),
+            path: Some(path.to_string_lossy().into_owned()),
+            event_count: event_count as u64,
+        }))
+    }
+
+    type QueryEventsStream = ReceiverStream<Result<Event, Status>>;
+
+    async fn query_events(
+        &self,
+        request: Request<EventQuery>,
+    ) -> Result<Response<Self::QueryEventsStream>, Status> {
+        let journal = self.channel.journal.clone().ok_or_else(|| {
+            Status::failed_precondition("the daemon was started without a journal")
+        })?;
+        let query = request.into_inner();
+
+        // Reading stops once the client is gone
+        let (tx, rx) = mpsc::channel(constants::JOURNAL_QUERY_BUFFER_SIZE);
+        spawn_blocking(move || {
+            let result = journal.query(&query, |event| tx.blocking_send(Ok(event)).is_ok());
+            if let Err(error) = result {
+                let _ = tx.blocking_send(Err(error.into()));
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn query_time_series(
+        &self,
+        request: Request<TimeSeriesQuery>,
+    ) -> Result<Response<EventSeriesList>, Status> {
+        let series = self.channel.time_series.query(request.get_ref())?;
+        Ok(Response::new(series))
+    }
 }
 
-async fn setup() -> (
+async fn setup(
+    load_options: &LoadOptions,
+) -> (
     ActorRef<()>,
     ZiofaServer<ZiofaImpl<NormalConfigurationStorage>>,
 ) {
-    let registry = registry::load_and_pin().unwrap();
+    let registry = registry::load_and_pin(load_options).unwrap();
 
     let symbol_actor_ref = SymbolActor::spawn().await.unwrap();
 
-    let channel = Channel::new();
+    let channel = Channel::new(
+        load_options.replay_buffer_size,
+        load_options.flight_recorder().map(FlightRecorder::new),
+        load_options
+            .journal()
+            .map(Journal::open)
+            .transpose()
+            .expect("journal directory should be writable"),
+    );
     let (collector_ref, _) = Actor::spawn(
         None,
         CollectorSupervisor,
-        CollectorSupervisorArguments::new(registry.event.clone(), channel.tx.clone()),
+        CollectorSupervisorArguments::new(
+            registry.event.clone(),
+            channel.events.clone(),
+            channel.batches.clone(),
+            load_options.reorder_window(),
+            channel.flight_recorder.clone(),
+            channel.journal.clone(),
+            channel.time_series.clone(),
+            channel.globs.clone(),
+        ),
     )
     .await
     .unwrap();
     let channel = Arc::new(channel);
 
-    let features = Features::init_all_features(&registry, symbol_actor_ref.clone());
+    let features =
+        Features::init_all_features(&registry, symbol_actor_ref.clone(), channel.globs.clone());
 
     let features = Arc::new(Mutex::new(features));
 
@@ -202,8 +326,8 @@ async fn setup() -> (
     (collector_ref, ziofa_server)
 }
 
-pub async fn serve_forever_socket() {
-    let (collector_ref, ziofa_server) = setup().await;
+pub async fn serve_forever_socket(load_options: &LoadOptions) {
+    let (collector_ref, ziofa_server) = setup(load_options).await;
 
     Server::builder()
         .add_service(ziofa_server)
@@ -233,7 +357,7 @@ mod tests {
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
 
         // Normal setup like in the default case
-        let (collector_ref, ziofa_server) = setup().await;
+        let (collector_ref, ziofa_server) = setup(&LoadOptions::default()).await;
 
         // We create a new endpoint, the connection url is ignored in the `connect_with_connector` call
         let channel = Endpoint::try_from("http://[::1]:50051")
@@ -5,7 +5,7 @@
 // SPDX-License-Identifier: MIT
 
 use std::{
-    ffi::CStr, path::Path, process::id, sync::LazyLock
+    ffi::CStr, path::Path, process::id, sync::LazyLock, time::{SystemTime, UNIX_EPOCH}
 };
 
 use anyhow::bail;
@@ -21,7 +21,7 @@ use shared::{
         WriteConfig,
     },
     events::{
-        event::EventData, log_event::LogEventData, write_event::WriteSource, Event, EventContext, GarbageCollectEvent, LogEvent, WriteEvent
+        event::EventData, log_event::LogEventData, write_event::WriteSource, BackpressurePolicy, Event, EventContext, EventQuery, GarbageCollectEvent, LogEvent, StreamOptions, TimeSeriesQuery, WriteEvent
     }, google::protobuf::{Duration, Timestamp},
 };
 use tokio::{fs, select, signal::ctrl_c};
@@ -81,6 +81,42 @@ enum Commands {
         library_path: String,
     },
 
+    /// Get the number of events of each kind suppressed by rate limits
+    SuppressedEvents,
+
+    /// Get the events of the last minutes from the journal of the daemon
+    QueryEvents {
+        /// Minutes before now of the oldest event
+        #[arg(short, long, default_value_t = 10)]
+        minutes: u64,
+
+        /// Only events of these processes
+        #[arg(short, long)]
+        pid: Vec<u32>,
+    },
+
+    /// Get the number of events per process and kind over the last minutes
+    TimeSeries {
+        /// Minutes before now of the oldest bucket
+        #[arg(short, long, default_value_t = 10)]
+        minutes: u64,
+
+        /// Width of the buckets in seconds, the finest one kept for the range by default
+        #[arg(short, long, default_value_t = 0)]
+        resolution: u32,
+
+        /// Only events of these processes
+        #[arg(short, long)]
+        pid: Vec<u32>,
+    },
+
+    /// Get the events kept by the flight recorder of the daemon
+    DumpFlightRecorder {
+        /// Write them to a file on the device instead of printing them
+        #[arg(short, long)]
+        write_to_file: bool,
+    },
+
     /// Collects everything
     Collect {
         /// The path to the sqlite database
@@ -100,12 +136,13 @@ async fn sendmsg(client: &mut Client, pid: u32) -> Result<()> {
                     ..Default::default()
                 }),
                 threshold: Some(32_000_000),
+                ..Default::default()
             }),
             ..Default::default()
         })
         .await?;
 
-    let mut stream = client.init_stream().await?;
+    let mut stream = client.init_stream(StreamOptions::default()).await?;
 
     while let Some(next) = stream.next().await {
         println!("{next:?}");
@@ -272,7 +309,7 @@ struct DestructuredEventContent {
 
 impl DestructuredEventContent {
     pub async fn new(event: Event) -> Result<Self> {
-        let Some(EventData::Log(LogEvent { context: Some(EventContext { pid, tid, timestamp }), log_event_data: Some(data) })) = event.event_data else { bail!("no data") };
+        let Some(EventData::Log(LogEvent { context: Some(EventContext { pid, tid, timestamp, .. }), log_event_data: Some(data) })) = event.event_data else { bail!("no data") };
         
         let comm = get_comm(pid).await.unwrap_or_default();
         let cmdline = get_cmdline(pid).await.unwrap_or_default();
@@ -390,7 +427,13 @@ async fn collect<P: AsRef<Path>>(client: &mut Client, path: P) -> Result<()> {
     conn.execute(WRITE_EVENT_TABLE, ())?;
     conn.execute(GARBAGE_COLLECT_EVENT_TABLE, ())?;
 
-    let mut stream = client.init_stream().await?;
+    // Rather slow down the collector than miss events in the database
+    let mut stream = client
+        .init_stream(StreamOptions {
+            backpressure_policy: BackpressurePolicy::Block.into(),
+            ..Default::default()
+        })
+        .await?;
 
     let ctrlc = ctrl_c();
     tokio::pin!(ctrlc);
@@ -403,6 +446,13 @@ async fn collect<P: AsRef<Path>>(client: &mut Client, path: P) -> Result<()> {
             event = stream.next() => {
                 let Some(event) = event else { break };
                 let Ok(event) = event else { continue };
+
+                if let Some(EventData::StreamStatus(status)) = &event.event_data {
+                    if status.dropped_events > 0 {
+                        eprintln!("The daemon dropped {} events", status.dropped_events);
+                    }
+                    continue;
+                }
                 
                 let event = DestructuredEventContent::new(event).await?;
                 event.insert(&conn)?;
@@ -461,6 +511,54 @@ pub async fn main() -> anyhow::Result<()> {
         } => {
             get_symbol_offset(&mut client, symbol_name, library_path).await?;
         }
+        Commands::SuppressedEvents => {
+            for count in client.get_suppressed_events().await? {
+                println!("{count:?}");
+            }
+        }
+        Commands::QueryEvents { minutes, pid } => {
+            let start = SystemTime::now() - std::time::Duration::from_secs(minutes * 60);
+            let mut events = client
+                .query_events(EventQuery {
+                    start: Some(start.duration_since(UNIX_EPOCH)?.into()),
+                    pids: pid,
+                    ..Default::default()
+                })
+                .await?;
+            while let Some(event) = events.next().await {
+                println!("{:?}", event?);
+            }
+        }
+        Commands::TimeSeries {
+            minutes,
+            resolution,
+            pid,
+        } => {
+            let start = SystemTime::now() - std::time::Duration::from_secs(minutes * 60);
+            let list = client
+                .query_time_series(TimeSeriesQuery {
+                    start: Some(start.duration_since(UNIX_EPOCH)?.into()),
+                    resolution_secs: resolution,
+                    pids: pid,
+                    ..Default::default()
+                })
+                .await?;
+            println!("Buckets of {}s", list.resolution_secs);
+            for series in list.series {
+                println!("{series:?}");
+            }
+        }
+        Commands::DumpFlightRecorder { write_to_file } => {
+            let recording = client.dump_flight_recorder(write_to_file).await?;
+            match recording.path {
+                Some(path) => println!("Wrote {} events to {path}", recording.event_count),
+                None => {
+                    for event in recording.events {
+                        println!("{event:?}");
+                    }
+                }
+            }
+        }
         Commands::Collect { sqlite_path } => {
             collect(&mut client, sqlite_path).await?;
         }
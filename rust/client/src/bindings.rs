@@ -9,6 +9,8 @@ use shared::{
     config::Configuration,
     events::{
         file_descriptor_change_event::FileDescriptorOp, jni_references_event::JniMethodName, Event,
+        EventQuery, EventSeriesList, FlightRecording, StreamOptions, SuppressedEventCount,
+        TimeSeriesQuery,
     },
     processes::Process,
     symbols::search_symbols_response::Symbol,
@@ -33,6 +35,21 @@ impl EventStream {
     }
 }
 
+#[derive(uniffi::Object)]
+struct EventBatchStream(Mutex<Pin<Box<dyn Stream<Item = Result<Vec<Event>>> + Send>>>);
+
+#[uniffi::export(async_runtime = "tokio")]
+impl EventBatchStream {
+    pub async fn next(&self) -> Result<Option<Vec<Event>>> {
+        let mut guard = self.0.lock().await;
+        match guard.next().await {
+            Some(Ok(x)) => Ok(Some(x)),
+            Some(Err(e)) => Err(e),
+            None => Ok(None),
+        }
+    }
+}
+
 #[derive(uniffi::Object)]
 struct Client(Mutex<crate::client::Client>);
 
@@ -64,16 +81,40 @@ impl Client {
         Ok(self.0.lock().await.set_configuration(configuration).await?)
     }
 
-    pub async fn init_stream(&self) -> Result<EventStream> {
+    pub async fn init_stream(&self, options: StreamOptions) -> Result<EventStream> {
         let mut guard = self.0.lock().await;
         let stream = guard
-            .init_stream()
+            .init_stream(options)
             .await?
             .map(|x| x.map_err(ClientError::from));
 
         Ok(EventStream(Mutex::new(Box::pin(stream))))
     }
 
+    pub async fn init_batched_stream(&self, options: StreamOptions) -> Result<EventBatchStream> {
+        let mut guard = self.0.lock().await;
+        let stream = guard
+            .init_batched_stream(options)
+            .await?
+            .map(|x| x.map(|batch| batch.events).map_err(ClientError::from));
+
+        Ok(EventBatchStream(Mutex::new(Box::pin(stream))))
+    }
+
+    pub async fn query_events(&self, query: EventQuery) -> Result<EventStream> {
+        let mut guard = self.0.lock().await;
+        let stream = guard
+            .query_events(query)
+            .await?
+            .map(|x| x.map_err(ClientError::from));
+
+        Ok(EventStream(Mutex::new(Box::pin(stream))))
+    }
+
+    pub async fn query_time_series(&self, query: TimeSeriesQuery) -> Result<EventSeriesList> {
+        Ok(self.0.lock().await.query_time_series(query).await?)
+    }
+
     pub async fn index_symbols(&self) -> Result<()> {
         Ok(self.0.lock().await.index_symbols().await?)
     }
@@ -94,6 +135,19 @@ impl Client {
             .get_symbol_offset(symbol_name, library_path)
             .await?)
     }
+
+    pub async fn get_suppressed_events(&self) -> Result<Vec<SuppressedEventCount>> {
+        Ok(self.0.lock().await.get_suppressed_events().await?)
+    }
+
+    pub async fn dump_flight_recorder(&self, write_to_file: bool) -> Result<FlightRecording> {
+        Ok(self
+            .0
+            .lock()
+            .await
+            .dump_flight_recorder(write_to_file)
+            .await?)
+    }
 }
 
 #[uniffi::export]
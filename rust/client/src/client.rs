@@ -3,15 +3,30 @@
 //
 // SPDX-License-Identifier: MIT
 
+use std::{future::Future, time::Duration};
+
 use shared::{
     config::Configuration,
-    events::Event,
+    events::{
+        event::EventData, DumpFlightRecorderRequest, Event, EventBatch, EventQuery,
+        EventSeriesList, FlightRecording, StreamOptions, SuppressedEventCount, TimeSeriesQuery,
+    },
     processes::Process,
     symbols::{search_symbols_response::Symbol, GetSymbolOffsetRequest, SearchSymbolsRequest},
     ziofa::ziofa_client::ZiofaClient,
 };
-use tokio_stream::{Stream, StreamExt};
-use tonic::transport::{Channel, Endpoint};
+use tokio::{select, sync::mpsc, time::sleep};
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code, Status,
+};
+
+/// Delay before the first attempt to reconnect a stream, doubled for every failed attempt
+const RECONNECT_MIN_BACKOFF: Duration = Duration::from_millis(100);
+const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+/// Messages received but not yet taken from a stream
+const STREAM_BUFFER_SIZE: usize = 64;
 
 #[derive(Clone, Debug)]
 pub struct Client {
@@ -50,13 +65,51 @@ impl Client {
         Ok(())
     }
 
-    pub async fn init_stream(&mut self) -> Result<impl Stream<Item = Result<Event>>> {
+    /// The stream reconnects when the connection to the daemon is lost and resumes
+    /// after the last received event, as long as the daemon still buffers it
+    pub async fn init_stream(
+        &mut self,
+        options: StreamOptions,
+    ) -> Result<impl Stream<Item = Result<Event>>> {
+        let ziofa = self.ziofa.clone();
+        resumable(options, move |options| {
+            let mut ziofa = ziofa.clone();
+            async move { Ok(ziofa.init_stream(options).await?.into_inner()) }
+        })
+        .await
+    }
+
+    /// Reconnects and resumes like [`Client::init_stream`]
+    pub async fn init_batched_stream(
+        &mut self,
+        options: StreamOptions,
+    ) -> Result<impl Stream<Item = Result<EventBatch>>> {
+        let ziofa = self.ziofa.clone();
+        resumable(options, move |options| {
+            let mut ziofa = ziofa.clone();
+            async move { Ok(ziofa.init_batched_stream(options).await?.into_inner()) }
+        })
+        .await
+    }
+
+    /// Streams the matching events of the journal of the daemon, the stream ends
+    /// after the last one
+    pub async fn query_events(
+        &mut self,
+        query: EventQuery,
+    ) -> Result<impl Stream<Item = Result<Event>>> {
         Ok(self
             .ziofa
-            .init_stream(())
+            .query_events(query)
             .await?
             .into_inner()
-            .map(|s| Ok(s?)))
+            .map(|event| event.map_err(ClientError::from)))
+    }
+
+    /// Counts and sums of the events per process and kind, kept by the daemon
+    /// since it was started
+    pub async fn query_time_series(&mut self, query: TimeSeriesQuery) -> Result<EventSeriesList> {
+        Ok(self.ziofa.query_time_series(query).await?.into_inner())
     }
 
     pub async fn index_symbols(&mut self) -> Result<()> {
@@ -88,4 +141,253 @@ impl Client {
             .into_inner()
             .offset)
     }
+
+    pub async fn get_suppressed_events(&mut self) -> Result<Vec<SuppressedEventCount>> {
+        Ok(self
+            .ziofa
+            .get_suppressed_events(())
+            .await?
+            .into_inner()
+            .counts)
+    }
+
+    /// Returns the events or, with `write_to_file`, the file on the device they were
+    /// written to
+    pub async fn dump_flight_recorder(&mut self, write_to_file: bool) -> Result<FlightRecording> {
+        // The window can be larger than the default limit of a message
+        Ok(self
+            .ziofa
+            .clone()
+            .max_decoding_message_size(usize::MAX)
+            .dump_flight_recorder(DumpFlightRecorderRequest { write_to_file })
+            .await?
+            .into_inner())
+    }
+}
+
+trait Sequenced {
+    /// Sequence number of the last event, stream statuses have none
+    fn last_sequence(&self) -> Option<u64>;
+
+    /// Instance of the daemon, only sent with a stream status
+    fn daemon_instance(&self) -> Option<u64>;
+}
+
+impl Sequenced for Event {
+    fn last_sequence(&self) -> Option<u64> {
+        (self.sequence > 0).then_some(self.sequence)
+    }
+
+    fn daemon_instance(&self) -> Option<u64> {
+        match &self.event_data {
+            Some(EventData::StreamStatus(status)) => Some(status.daemon_instance),
+            _ => None,
+        }
+    }
+}
+
+impl Sequenced for EventBatch {
+    fn last_sequence(&self) -> Option<u64> {
+        self.events
+            .iter()
+            .filter_map(Sequenced::last_sequence)
+            .max()
+    }
+
+    fn daemon_instance(&self) -> Option<u64> {
+        self.events.iter().find_map(Sequenced::daemon_instance)
+    }
+}
+
+/// Remembers the instance of the daemon, true if it is not the one of the previous message
+fn daemon_restarted(instance: &mut Option<u64>, message: &impl Sequenced) -> bool {
+    message.daemon_instance().is_some_and(|current| {
+        instance
+            .replace(current)
+            .is_some_and(|previous| previous != current)
+    })
+}
+
+/// Errors which may go away by connecting again, e.g. a restarted daemon
+fn is_transient(status: &Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unavailable
+            | Code::Unknown
+            | Code::Cancelled
+            | Code::Aborted
+            | Code::Internal
+            | Code::DeadlineExceeded
+    )
+}
+
+/// Connects once and forwards the stream from a background task, which connects
+/// again with backoff whenever the stream ends or fails with a transient error.
+/// It stops once the returned stream is dropped.
+///
+/// The sequence numbers of a restarted daemon start over, the stream is then
+/// connected again to get all events the new daemon still buffers.
+async fn resumable<T, S, F, Fut>(
+    mut options: StreamOptions,
+    mut connect: F,
+) -> Result<ReceiverStream<Result<T>>>
+where
+    T: Sequenced + Send + 'static,
+    S: Stream<Item = core::result::Result<T, Status>> + Unpin + Send + 'static,
+    F: FnMut(StreamOptions) -> Fut + Send + 'static,
+    Fut: Future<Output = core::result::Result<S, Status>> + Send,
+{
+    let mut stream = connect(options).await?;
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER_SIZE);
+
+    tokio::spawn(async move {
+        let mut backoff = RECONNECT_MIN_BACKOFF;
+        let mut instance = None;
+        loop {
+            let message = select! {
+                message = stream.next() => message,
+                _ = tx.closed() => return,
+            };
+
+            match message {
+                // The replay was filtered with a sequence number of the previous instance
+                Some(Ok(message)) if daemon_restarted(&mut instance, &message) => {
+                    options.resume_after = Some(0);
+                }
+                Some(Ok(message)) => {
+                    if let Some(sequence) = message.last_sequence() {
+                        options.resume_after = Some(sequence);
+                    }
+                    backoff = RECONNECT_MIN_BACKOFF;
+                    if tx.send(Ok(message)).await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                Some(Err(status)) if !is_transient(&status) => {
+                    let _ = tx.send(Err(status.into())).await;
+                    return;
+                }
+                Some(Err(_)) | None => {}
+            }
+
+            stream = loop {
+                select! {
+                    _ = sleep(backoff) => {}
+                    _ = tx.closed() => return,
+                }
+                backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+
+                match connect(options).await {
+                    Ok(stream) => break stream,
+                    Err(status) if is_transient(&status) => continue,
+                    Err(status) => {
+                        let _ = tx.send(Err(status.into())).await;
+                        return;
+                    }
+                }
+            };
+        }
+    });
+
+    Ok(ReceiverStream::new(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use shared::events::StreamStatus;
+    use tokio_stream::iter;
+
+    use super::*;
+
+    fn event(sequence: u64) -> Event {
+        Event {
+            sequence,
+            ..Default::default()
+        }
+    }
+
+    fn status(daemon_instance: u64) -> Event {
+        Event {
+            event_data: Some(EventData::StreamStatus(StreamStatus {
+                daemon_instance,
+                ..Default::default()
+            })),
+            sequence: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn reconnects_and_resumes_after_the_last_event() {
+        let attempts = Arc::new(Mutex::new(Vec::new()));
+        let connects = attempts.clone();
+        let stream = resumable(StreamOptions::default(), move |options| {
+            let mut attempts = connects.lock().unwrap();
+            attempts.push(options.resume_after);
+            let result = match attempts.len() {
+                1 => Ok(iter(vec![
+                    Ok(event(1)),
+                    Ok(event(2)),
+                    Err(Status::unavailable("gone")),
+                ])),
+                2 => Err(Status::unavailable("still gone")),
+                3 => Ok(iter(vec![Ok(event(3))])),
+                _ => Err(Status::permission_denied("stop")),
+            };
+            async move { result }
+        })
+        .await
+        .unwrap();
+
+        let received: Vec<_> = stream.collect().await;
+        let sequences: Vec<_> = received
+            .iter()
+            .filter_map(|message| message.as_ref().ok())
+            .map(|event| event.sequence)
+            .collect();
+        assert_eq!(sequences, vec![1, 2, 3]);
+        assert!(matches!(
+            received.last(),
+            Some(Err(ClientError::Status(status))) if status.code() == Code::PermissionDenied
+        ));
+        assert_eq!(
+            *attempts.lock().unwrap(),
+            vec![None, Some(2), Some(2), Some(3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn resumes_from_the_start_after_a_daemon_restart() {
+        let attempts = Arc::new(Mutex::new(Vec::new()));
+        let connects = attempts.clone();
+        let stream = resumable(StreamOptions::default(), move |options| {
+            let mut attempts = connects.lock().unwrap();
+            attempts.push(options.resume_after);
+            let result = match attempts.len() {
+                1 => Ok(iter(vec![Ok(status(1)), Ok(event(5)), Ok(event(6))])),
+                // The restarted daemon filtered its events 1 to 6
+                2 => Ok(iter(vec![Ok(status(2)), Ok(event(7))])),
+                3 => Ok(iter(vec![Ok(status(2)), Ok(event(1)), Ok(event(2))])),
+                _ => Err(Status::permission_denied("stop")),
+            };
+            async move { result }
+        })
+        .await
+        .unwrap();
+
+        let received: Vec<_> = stream.collect().await;
+        let sequences: Vec<_> = received
+            .iter()
+            .filter_map(|message| message.as_ref().ok())
+            .filter(|event| event.sequence > 0)
+            .map(|event| event.sequence)
+            .collect();
+        assert_eq!(sequences, vec![5, 6, 1, 2]);
+        assert_eq!(
+            *attempts.lock().unwrap(),
+            vec![None, Some(6), Some(0), Some(2)]
+        );
+    }
 }
@@ -5,7 +5,7 @@
 // SPDX-License-Identifier: MIT
 
 use client::Client;
-use shared::{config::Configuration, processes::process::Cmd};
+use shared::{config::Configuration, events::StreamOptions, processes::process::Cmd};
 
 // client tests assume daemon is running!
 async fn setup() -> Client {
@@ -61,7 +61,7 @@ async fn init_stream() {
     let mut client = setup().await;
 
     let _ = client
-        .init_stream()
+        .init_stream(StreamOptions::default())
         .await
         .expect("init_stream should return a stream");
 }
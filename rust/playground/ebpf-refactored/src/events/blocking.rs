@@ -4,19 +4,24 @@
 
 use core::mem::MaybeUninit;
 
-use aya_ebpf::helpers::bpf_ktime_get_ns;
+use aya_ebpf::{helpers::bpf_ktime_get_ns, PtRegs};
+use ebpf_relocation_helpers::TaskStruct;
 use ebpf_types::Blocking;
 
 use super::SyscallProg;
 use crate::{
     event_local::{EventLocal, EventLocalData, EventLocalValue},
+    maps::BlockingThresholds,
+    path::{get_path_from_fd, read_path_to_buf_with_default},
     pipeline::{ProgramInfo, SysEnterInfo, SysExitInfo},
+    syscalls,
 };
 
 #[repr(C)]
 pub struct BlockingEntryData {
     pub syscall_id: u64,
     pub start_time: u64,
+    pub first_arg: u64,
 }
 
 impl EventLocalData for Blocking {
@@ -29,34 +34,43 @@ impl SyscallProg for Blocking {
         _: ProgramInfo,
         mem: &'a mut MaybeUninit<EventLocal<Self>>,
     ) -> Option<&'a mut EventLocal<Self>> {
-        initialize_blocking_enter(sys_enter.syscall_id, mem)
+        initialize_blocking_enter(sys_enter.syscall_id, sys_enter.pt_regs, mem)
     }
 
     fn exit<'a>(
-        _: SysExitInfo,
+        sys_exit: SysExitInfo,
         _: ProgramInfo,
         entry: &EventLocalValue<Self>,
         mem: &'a mut MaybeUninit<Self>,
     ) -> Option<&'a Self> {
-        initialize_blocking_exit(entry, mem)
+        initialize_blocking_exit(sys_exit.task, entry, mem)
     }
 }
 
 fn initialize_blocking_enter(
     syscall_id: i64,
+    pt_regs: PtRegs,
     blocking_data: &mut MaybeUninit<EventLocal<Blocking>>,
 ) -> Option<&mut EventLocal<Blocking>> {
+    if BlockingThresholds::is_ignored(syscall_id as u32) {
+        return None;
+    }
+
     let ptr = blocking_data.as_mut_ptr();
 
     unsafe {
         (&raw mut (*ptr).data.syscall_id).write(syscall_id as u64);
         (&raw mut (*ptr).data.start_time).write(bpf_ktime_get_ns());
+        // On aarch64 the first argument register is overwritten by the return value,
+        // so it has to be saved on entry.
+        (&raw mut (*ptr).data.first_arg).write(pt_regs.arg::<*const u64>(0)? as u64);
 
         Some(blocking_data.assume_init_mut())
     }
 }
 
 fn initialize_blocking_exit<'a>(
+    task: TaskStruct,
     blocking_entry: &EventLocalValue<Blocking>,
     blocking_data: &'a mut MaybeUninit<Blocking>,
 ) -> Option<&'a Blocking> {
@@ -65,7 +79,56 @@ fn initialize_blocking_exit<'a>(
     unsafe {
         (&raw mut (*ptr).duration).write(bpf_ktime_get_ns() - blocking_entry.data.start_time);
         (&raw mut (*ptr).syscall_id).write(blocking_entry.data.syscall_id);
+        (&raw mut (*ptr).first_arg).write(blocking_entry.data.first_arg);
+
+        (*ptr).file_path[0] = 0;
+        if first_arg_is_fd(blocking_entry.data.syscall_id as i64) {
+            if let Some(path) = get_path_from_fd(blocking_entry.data.first_arg, task) {
+                if read_path_to_buf_with_default(path, &mut (*ptr).file_path).is_none() {
+                    (*ptr).file_path[0] = 0;
+                }
+            }
+        }
 
         Some(blocking_data.assume_init_mut())
     }
 }
+
+/// Syscalls which can block and take a file descriptor as their first argument
+fn first_arg_is_fd(syscall_id: i64) -> bool {
+    matches!(
+        syscall_id,
+        syscalls::SYS_read
+            | syscalls::SYS_write
+            | syscalls::SYS_readv
+            | syscalls::SYS_writev
+            | syscalls::SYS_pread64
+            | syscalls::SYS_pwrite64
+            | syscalls::SYS_preadv
+            | syscalls::SYS_pwritev
+            | syscalls::SYS_preadv2
+            | syscalls::SYS_pwritev2
+            | syscalls::SYS_fsync
+            | syscalls::SYS_fdatasync
+            | syscalls::SYS_sync_file_range
+            | syscalls::SYS_ftruncate
+            | syscalls::SYS_fallocate
+            | syscalls::SYS_flock
+            | syscalls::SYS_fcntl
+            | syscalls::SYS_ioctl
+            | syscalls::SYS_getdents64
+            | syscalls::SYS_sendfile
+            | syscalls::SYS_splice
+            | syscalls::SYS_connect
+            | syscalls::SYS_accept
+            | syscalls::SYS_accept4
+            | syscalls::SYS_sendto
+            | syscalls::SYS_recvfrom
+            | syscalls::SYS_sendmsg
+            | syscalls::SYS_recvmsg
+            | syscalls::SYS_sendmmsg
+            | syscalls::SYS_recvmmsg
+            | syscalls::SYS_shutdown
+            | syscalls::SYS_epoll_pwait
+    )
+}
@@ -0,0 +1,83 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use core::{ffi::c_void, mem::MaybeUninit, ptr::copy_nonoverlapping};
+
+use aya_ebpf::{
+    bindings::BPF_F_USER_STACK, helpers::bpf_probe_read_kernel, programs::RawTracePointContext,
+    EbpfContext,
+};
+use ebpf_relocation_helpers::KSigaction;
+use ebpf_types::{Crash, ProcessContext, CRASH_STACK_DEPTH};
+
+use super::signal::{SIGINFO_SI_ADDR_OFFSET, SIGINFO_SI_CODE_OFFSET};
+
+const SIGILL: u32 = 4;
+const SIGABRT: u32 = 6;
+const SIGBUS: u32 = 7;
+const SIGSEGV: u32 = 11;
+
+/// Whether the signal terminates the process with a core dump, this is
+/// only the case if no handler is installed.
+///
+/// On Android `debuggerd` installs handlers for all fatal signals, it
+/// restores the default action and raises the signal again once the
+/// tombstone is written, so the crash is reported on the second delivery.
+#[inline(always)]
+pub fn is_fatal(signal: u32, action: KSigaction) -> bool {
+    if !matches!(signal, SIGILL | SIGABRT | SIGBUS | SIGSEGV) {
+        return false;
+    }
+
+    // SIG_DFL
+    matches!(action.sa().sa_handler(), Ok(handler) if handler.is_null())
+}
+
+/// `TP_PROTO(int sig, struct kernel_siginfo *info, struct k_sigaction *ka)`
+///
+/// The current task is the faulting thread.
+///
+/// # Safety
+///
+/// `info` must be the `struct kernel_siginfo` argument of `signal_deliver`.
+#[inline(always)]
+pub unsafe fn initialize_crash<'a>(
+    ctx: &RawTracePointContext,
+    signal: u32,
+    info: *const u8,
+    process: &ProcessContext,
+    crash_data: &'a mut MaybeUninit<Crash>,
+) -> Option<&'a Crash> {
+    let ptr = crash_data.as_mut_ptr();
+
+    let si_code =
+        bpf_probe_read_kernel(info.wrapping_add(SIGINFO_SI_CODE_OFFSET) as *const i32).ok()?;
+    // si_addr is only part of the siginfo for signals raised by the kernel
+    let fault_addr = if si_code > 0 {
+        bpf_probe_read_kernel(info.wrapping_add(SIGINFO_SI_ADDR_OFFSET) as *const u64).unwrap_or(0)
+    } else {
+        0
+    };
+
+    (&raw mut (*ptr).signal).write(signal);
+    (&raw mut (*ptr).si_code).write(si_code);
+    (&raw mut (*ptr).fault_addr).write(fault_addr);
+
+    let stack_size = aya_ebpf::helpers::gen::bpf_get_stack(
+        ctx.as_ptr(),
+        &raw mut (*ptr).stack as *mut c_void,
+        (CRASH_STACK_DEPTH * size_of::<u64>()) as u32,
+        BPF_F_USER_STACK as u64,
+    );
+    let stack_len = if stack_size > 0 {
+        stack_size as u32 / size_of::<u64>() as u32
+    } else {
+        0
+    };
+    (&raw mut (*ptr).stack_len).write(stack_len);
+
+    copy_nonoverlapping(&process.cmdline, &raw mut (*ptr).cmdline, 1);
+
+    Some(crash_data.assume_init_ref())
+}
@@ -10,7 +10,9 @@ use crate::{
 };
 
 pub mod blocking;
+pub mod crash;
 pub mod fdtracking;
+pub mod process;
 pub mod signal;
 pub mod write;
 
@@ -0,0 +1,120 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use core::{
+    mem::MaybeUninit,
+    ptr::{copy_nonoverlapping, write_bytes},
+};
+
+use ebpf_relocation_helpers::TaskStruct;
+use ebpf_types::{ProcessContext, ProcessLifecycle, ProcessOp};
+
+/// `TP_PROTO(struct task_struct *parent, struct task_struct *child)`
+///
+/// Returns `None` for new threads, only new processes are reported.
+#[inline(always)]
+pub fn initialize_process_fork<'a>(
+    parent: TaskStruct,
+    child: TaskStruct,
+    process: &ProcessContext,
+    process_data: &'a mut MaybeUninit<ProcessLifecycle>,
+) -> Option<&'a ProcessLifecycle> {
+    let parent_pid = parent.tgid().ok()?;
+    let pid = child.tgid().ok()?;
+    if pid == parent_pid {
+        return None;
+    }
+
+    initialize_process(
+        pid,
+        parent_pid,
+        0,
+        ProcessOp::Fork,
+        Some(process),
+        process_data,
+    )
+}
+
+/// `TP_PROTO(struct task_struct *p, pid_t old_pid, struct linux_binprm *bprm)`
+#[inline(always)]
+pub fn initialize_process_exec<'a>(
+    task: TaskStruct,
+    process: &ProcessContext,
+    process_data: &'a mut MaybeUninit<ProcessLifecycle>,
+) -> Option<&'a ProcessLifecycle> {
+    let pid = task.tgid().ok()?;
+    let parent_pid = parent_pid(task)?;
+
+    initialize_process(
+        pid,
+        parent_pid,
+        0,
+        ProcessOp::Exec,
+        Some(process),
+        process_data,
+    )
+}
+
+/// `TP_PROTO(struct task_struct *p)`
+///
+/// Returns `None` for exiting threads, only the exit of the thread group
+/// leader is reported. `exit_mm` already released the memory of the task, so
+/// the process context is only known if it was cached before, it is left
+/// empty otherwise.
+#[inline(always)]
+pub fn initialize_process_exit<'a>(
+    task: TaskStruct,
+    process: Option<&ProcessContext>,
+    process_data: &'a mut MaybeUninit<ProcessLifecycle>,
+) -> Option<&'a ProcessLifecycle> {
+    let pid = task.tgid().ok()?;
+    if task.pid().ok()? != pid {
+        return None;
+    }
+    let parent_pid = parent_pid(task)?;
+    let exit_code = task.exit_code().ok()? as u32;
+
+    initialize_process(
+        pid,
+        parent_pid,
+        exit_code,
+        ProcessOp::Exit,
+        process,
+        process_data,
+    )
+}
+
+#[inline(always)]
+fn parent_pid(task: TaskStruct) -> Option<u32> {
+    task.group_leader()
+        .and_then(|leader| leader.real_parent())
+        .and_then(|parent| parent.tgid())
+        .ok()
+}
+
+#[inline(always)]
+fn initialize_process<'a>(
+    pid: u32,
+    parent_pid: u32,
+    exit_code: u32,
+    operation: ProcessOp,
+    process: Option<&ProcessContext>,
+    process_data: &'a mut MaybeUninit<ProcessLifecycle>,
+) -> Option<&'a ProcessLifecycle> {
+    let ptr = process_data.as_mut_ptr();
+
+    unsafe {
+        (&raw mut (*ptr).pid).write(pid);
+        (&raw mut (*ptr).parent_pid).write(parent_pid);
+        (&raw mut (*ptr).exit_code).write(exit_code);
+        (&raw mut (*ptr).operation).write(operation);
+
+        match process {
+            Some(process) => copy_nonoverlapping(process, &raw mut (*ptr).process, 1),
+            None => write_bytes(&raw mut (*ptr).process, 0, 1),
+        }
+
+        Some(process_data.assume_init_ref())
+    }
+}
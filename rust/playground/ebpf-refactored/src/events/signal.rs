@@ -4,8 +4,12 @@
 
 use core::mem::MaybeUninit;
 
-use aya_ebpf::PtRegs;
-use ebpf_types::Signal;
+use aya_ebpf::{
+    helpers::{bpf_probe_read_kernel, bpf_probe_read_kernel_buf},
+    PtRegs,
+};
+use ebpf_relocation_helpers::TaskStruct;
+use ebpf_types::{Signal, SignalSource, TaskContext};
 
 use super::SyscallProg;
 use crate::{
@@ -28,29 +32,47 @@ impl SyscallProg for Signal {
     }
 
     fn exit<'a>(
-        _: SysExitInfo,
-        _: ProgramInfo,
+        sys_exit: SysExitInfo,
+        program_info: ProgramInfo,
         entry: &EventLocalValue<Self>,
         mem: &'a mut MaybeUninit<Self>,
     ) -> Option<&'a Self> {
-        initialize_signal_exit(entry, mem)
+        initialize_signal_exit(sys_exit.return_value, program_info.task_context, entry, mem)
     }
 }
 
+/*
+ * long sys_kill(pid_t pid, int sig);
+ * long sys_tkill(pid_t pid, int sig);
+ * long sys_tgkill(pid_t tgid, pid_t pid, int sig);
+ * long sys_rt_sigqueueinfo(pid_t pid, int sig, siginfo_t __user *uinfo);
+ * long sys_rt_tgsigqueueinfo(pid_t tgid, pid_t pid, int sig, siginfo_t __user *uinfo);
+ * long sys_pidfd_send_signal(int pidfd, int sig, siginfo_t __user *info, unsigned int flags);
+ */
 #[inline(always)]
 fn initialize_signal_enter(
     syscall_id: i64,
     pt_regs: PtRegs,
     signal_data: &mut MaybeUninit<EventLocal<Signal>>,
 ) -> Option<&mut EventLocal<Signal>> {
-    if syscall_id != syscalls::SYS_kill {
-        return None;
-    }
+    let source = signal_syscall_to_signal_source(syscall_id)?;
+
+    let arg = |n: usize| pt_regs.arg::<*const u64>(n).map(|arg| arg as u64);
+    let (target_pid, target_tid, signal) = match source {
+        SignalSource::Kill | SignalSource::RtSigqueueinfo | SignalSource::PidfdSendSignal => {
+            (arg(0)?, 0, arg(1)?)
+        }
+        SignalSource::Tkill => (0, arg(0)?, arg(1)?),
+        SignalSource::Tgkill | SignalSource::RtTgsigqueueinfo => (arg(0)?, arg(1)?, arg(2)?),
+        SignalSource::Generate | SignalSource::Deliver => return None,
+    };
 
     let ptr = signal_data.as_mut_ptr();
     unsafe {
-        (&raw mut (*ptr).data.target_pid).write(pt_regs.arg::<*const u64>(0)? as i32);
-        (&raw mut (*ptr).data.signal).write(pt_regs.arg::<*const u64>(1)? as u32);
+        (&raw mut (*ptr).data.target_pid).write(target_pid as i32);
+        (&raw mut (*ptr).data.target_tid).write(target_tid as i32);
+        (&raw mut (*ptr).data.signal).write(signal as u32);
+        (&raw mut (*ptr).data.source).write(source);
 
         Some(signal_data.assume_init_mut())
     }
@@ -58,6 +80,8 @@ fn initialize_signal_enter(
 
 #[inline(always)]
 fn initialize_signal_exit<'a>(
+    return_value: u64,
+    sender: &TaskContext,
     signal_entry: &EventLocalValue<Signal>,
     signal_data: &'a mut MaybeUninit<Signal>,
 ) -> Option<&'a Signal> {
@@ -65,7 +89,134 @@ fn initialize_signal_exit<'a>(
 
     unsafe {
         ptr.write(signal_entry.data);
+        (&raw mut (*ptr).result).write(return_value as i64);
+        (&raw mut (*ptr).sender_pid).write(sender.pid);
+        (&raw mut (*ptr).sender_comm).write(sender.comm);
+        (&raw mut (*ptr).target_comm).write([0; 16]);
+
+        Some(signal_data.assume_init_mut())
+    }
+}
+
+/// `TP_PROTO(int sig, struct kernel_siginfo *info, struct task_struct *task, int group, int result)`
+///
+/// The current task is the sender of signals from userspace. Signals raised by the
+/// kernel, e.g. SIGSEGV or SIGCHLD, are reported without a sender as the current
+/// task just happened to run.
+#[inline(always)]
+pub fn initialize_signal_generate<'a>(
+    signal: u32,
+    target: TaskStruct,
+    group: bool,
+    result: i32,
+    from_kernel: bool,
+    sender: &TaskContext,
+    signal_data: &'a mut MaybeUninit<Signal>,
+) -> Option<&'a Signal> {
+    let ptr = signal_data.as_mut_ptr();
+
+    unsafe {
+        (&raw mut (*ptr).target_pid).write(target.tgid().ok()? as i32);
+        (&raw mut (*ptr).target_tid).write(if group { 0 } else { target.pid().ok()? as i32 });
+        (&raw mut (*ptr).signal).write(signal);
+        if from_kernel {
+            (&raw mut (*ptr).sender_pid).write(0);
+            (&raw mut (*ptr).sender_comm).write([0; 16]);
+        } else {
+            (&raw mut (*ptr).sender_pid).write(sender.pid);
+            (&raw mut (*ptr).sender_comm).write(sender.comm);
+        }
+        (&raw mut (*ptr).result).write(result as i64);
+        bpf_probe_read_kernel_buf(target.comm() as *const u8, &mut (*ptr).target_comm).ok()?;
+        (&raw mut (*ptr).source).write(SignalSource::Generate);
 
         Some(signal_data.assume_init_mut())
     }
 }
+
+/// Offsets in `siginfo_t`, the layout is part of the uapi and
+/// `struct kernel_siginfo` matches it, so no relocation is needed.
+pub const SIGINFO_SI_CODE_OFFSET: usize = 8;
+const SIGINFO_SI_PID_OFFSET: usize = 16;
+/// `si_addr` shares the union with `si_pid`
+pub const SIGINFO_SI_ADDR_OFFSET: usize = 16;
+
+/// `TP_PROTO(int sig, struct kernel_siginfo *info, struct k_sigaction *ka)`
+///
+/// The current task is the receiver, the sender is only known from the siginfo.
+#[inline(always)]
+pub fn initialize_signal_deliver<'a>(
+    signal: u32,
+    sender_pid: u32,
+    target: &TaskContext,
+    signal_data: &'a mut MaybeUninit<Signal>,
+) -> Option<&'a Signal> {
+    let ptr = signal_data.as_mut_ptr();
+
+    unsafe {
+        (&raw mut (*ptr).target_pid).write(target.pid as i32);
+        (&raw mut (*ptr).target_tid).write(target.tid as i32);
+        (&raw mut (*ptr).signal).write(signal);
+        (&raw mut (*ptr).sender_pid).write(sender_pid);
+        (&raw mut (*ptr).result).write(0);
+        (&raw mut (*ptr).sender_comm).write([0; 16]);
+        (&raw mut (*ptr).target_comm).write(target.comm);
+        (&raw mut (*ptr).source).write(SignalSource::Deliver);
+
+        Some(signal_data.assume_init_mut())
+    }
+}
+
+/// `si_pid` is only valid for signals sent from userspace (`si_code <= 0`),
+/// reading fails for the special pointers used if there is no siginfo.
+///
+/// # Safety
+///
+/// `info` must be the `struct kernel_siginfo` argument of a signal tracepoint.
+#[inline(always)]
+pub unsafe fn siginfo_sender_pid(info: *const u8) -> u32 {
+    let Ok(si_code) =
+        bpf_probe_read_kernel(info.wrapping_add(SIGINFO_SI_CODE_OFFSET) as *const i32)
+    else {
+        return 0;
+    };
+    if si_code > 0 {
+        return 0;
+    }
+
+    bpf_probe_read_kernel(info.wrapping_add(SIGINFO_SI_PID_OFFSET) as *const i32)
+        .map(|pid| pid.max(0) as u32)
+        .unwrap_or(0)
+}
+
+/// `SEND_SIG_PRIV` as the siginfo and positive codes (`SI_KERNEL`, fault codes)
+/// mark signals raised by the kernel, `SEND_SIG_NOINFO` one sent by the current task.
+///
+/// # Safety
+///
+/// `info` must be the `struct kernel_siginfo` argument of a signal tracepoint.
+#[inline(always)]
+pub unsafe fn siginfo_from_kernel(info: *const u8) -> bool {
+    const SEND_SIG_NOINFO: usize = 0;
+    const SEND_SIG_PRIV: usize = 1;
+
+    match info as usize {
+        SEND_SIG_NOINFO => false,
+        SEND_SIG_PRIV => true,
+        _ => bpf_probe_read_kernel(info.wrapping_add(SIGINFO_SI_CODE_OFFSET) as *const i32)
+            .is_ok_and(|si_code| si_code > 0),
+    }
+}
+
+fn signal_syscall_to_signal_source(syscall: i64) -> Option<SignalSource> {
+    let source = match syscall {
+        syscalls::SYS_kill => SignalSource::Kill,
+        syscalls::SYS_tkill => SignalSource::Tkill,
+        syscalls::SYS_tgkill => SignalSource::Tgkill,
+        syscalls::SYS_rt_sigqueueinfo => SignalSource::RtSigqueueinfo,
+        syscalls::SYS_rt_tgsigqueueinfo => SignalSource::RtTgsigqueueinfo,
+        syscalls::SYS_pidfd_send_signal => SignalSource::PidfdSendSignal,
+        _ => return None,
+    };
+    Some(source)
+}
@@ -2,19 +2,53 @@
 //
 // SPDX-License-Identifier: MIT
 
-use aya_ebpf::maps::{Array, HashMap};
-use ebpf_types::{Equality, EventData, EventKind, FilterConfig, MissingBehavior};
+use core::ptr::copy_nonoverlapping;
+
+use aya_ebpf::{
+    helpers::{bpf_get_current_ancestor_cgroup_id, bpf_get_current_cgroup_id},
+    maps::{
+        lpm_trie::{Key, LpmTrie},
+        Array, HashMap, PerCpuArray,
+    },
+};
+use ebpf_types::{
+    Combination, Equality, EventData, EventKind, FilterConfig, MissingBehavior,
+    MAX_PREFIX_FILTER_LEN,
+};
 
 #[repr(transparent)]
 pub struct Filter<K: 'static, V: Matcher + 'static>(&'static HashMap<K, V>);
 
+/// Matches the longest prefix of a string, the first `N` bytes are used for the lookup.
+pub struct PrefixFilter<const N: usize> {
+    trie: &'static LpmTrie<[u8; N], Equality>,
+    /// Keys can be too large for the stack
+    key: &'static PerCpuArray<Key<[u8; MAX_PREFIX_FILTER_LEN]>>,
+}
+
+/// Exact matches of the whole string take precedence over prefix matches.
+pub struct StringFilter<const N: usize, const P: usize> {
+    exact: Filter<[u8; N], Equality>,
+    prefix: PrefixFilter<P>,
+}
+
+/// Ancestors deeper than this are only matched if they are the cgroup of the task itself
+const MAX_CGROUP_DEPTH: i32 = 8;
+
+/// Matches the cgroup v2 of the current task or the closest of its ancestors with an entry.
+#[repr(transparent)]
+pub struct CgroupFilter(Filter<u64, Equality>);
+
 pub struct FilterConfigs {
     config: &'static Array<FilterConfig>,
     host_pid: &'static Array<u32>,
     pid_filter: Filter<u32, Equality>,
-    comm_filter: Filter<[u8; 16], Equality>,
-    exe_path_filter: Filter<[u8; 4096], Equality>,
-    cmdline_filter: Filter<[u8; 256], Equality>,
+    comm_filter: StringFilter<16, 16>,
+    exe_path_filter: StringFilter<4096, MAX_PREFIX_FILTER_LEN>,
+    cmdline_filter: StringFilter<256, MAX_PREFIX_FILTER_LEN>,
+    process_comm_filter: StringFilter<16, 16>,
+    uid_filter: Filter<u32, Equality>,
+    cgroup_filter: CgroupFilter,
 }
 
 pub trait Matcher {
@@ -43,6 +77,16 @@ pub enum Match {
     NotSpecified,
 }
 
+impl Match {
+    #[inline(always)]
+    fn or_else(self, f: impl FnOnce() -> Match) -> Match {
+        match self {
+            Match::NotSpecified => f(),
+            other => other,
+        }
+    }
+}
+
 impl<K: 'static, V: Matcher + 'static> Filter<K, V> {
     pub const fn new(map: &'static HashMap<K, V>) -> Self {
         Self(map)
@@ -56,21 +100,119 @@ impl<K: 'static, V: Matcher + 'static> Filter<K, V> {
     }
 }
 
+impl<const N: usize> PrefixFilter<N> {
+    pub const fn new(
+        trie: &'static LpmTrie<[u8; N], Equality>,
+        key: &'static PerCpuArray<Key<[u8; MAX_PREFIX_FILTER_LEN]>>,
+    ) -> Self {
+        Self { trie, key }
+    }
+
+    #[inline(always)]
+    pub fn matches<const M: usize>(&self, value: &[u8; M], kind: EventKind) -> Match {
+        const { assert!(N <= M && N <= MAX_PREFIX_FILTER_LEN) };
+
+        let Some(key) = self.key.get_ptr_mut(0) else {
+            return Match::NotSpecified;
+        };
+        let key = key as *mut Key<[u8; N]>;
+
+        // The whole value is looked up, the trie returns the longest matching prefix
+        let matcher = unsafe {
+            (&raw mut (*key).prefix_len).write_unaligned((N * 8) as u32);
+            copy_nonoverlapping(value.as_ptr(), &raw mut (*key).data as *mut u8, N);
+            self.trie.get(&*key)
+        };
+
+        match matcher {
+            Some(matcher) => matcher.matches(kind),
+            None => Match::NotSpecified,
+        }
+    }
+}
+
+impl<const N: usize, const P: usize> StringFilter<N, P> {
+    pub const fn new(exact: &'static HashMap<[u8; N], Equality>, prefix: PrefixFilter<P>) -> Self {
+        Self {
+            exact: Filter::new(exact),
+            prefix,
+        }
+    }
+
+    #[inline(always)]
+    pub fn matches(&self, value: &[u8; N], kind: EventKind) -> Match {
+        self.exact
+            .matches(value, kind)
+            .or_else(|| self.prefix.matches(value, kind))
+    }
+}
+
+impl CgroupFilter {
+    pub const fn new(map: &'static HashMap<u64, Equality>) -> Self {
+        Self(Filter::new(map))
+    }
+
+    #[inline(always)]
+    pub fn matches(&self, kind: EventKind) -> Match {
+        let id = unsafe { bpf_get_current_cgroup_id() };
+        let mut result = self.0.matches(&id, kind);
+
+        // Levels deeper than the cgroup of the task have the id 0, level 0 is the root
+        for level in (0..MAX_CGROUP_DEPTH).rev() {
+            result =
+                result.or_else(
+                    || match unsafe { bpf_get_current_ancestor_cgroup_id(level) } {
+                        0 => Match::NotSpecified,
+                        id => self.0.matches(&id, kind),
+                    },
+                );
+        }
+
+        result
+    }
+}
+
 pub enum FilterEntry<'a> {
     OwnPid(&'a u32),
     Pid(&'a u32),
     Tid(&'a u32),
+    Uid(&'a u32),
+    /// The cgroup of the current task, not necessarily the task of the other entries
+    Cgroup,
     Comm(&'a [u8; 16]),
     ExePath(&'a [u8; 4096]),
     Cmdline(&'a [u8; 256]),
+    ProcessComm(&'a [u8; 16]),
 }
 
 macro_rules! filter_matches {
     ($filter:expr, $key:expr, $kind:expr, $missing_behavior:expr) => {
-        match (
+        ShouldFilter::new(
             $filter.matches($key, $kind),
             $missing_behavior.map(|m| m.missing_behavior),
-        ) {
+        )
+    };
+    ($filter:expr, $kind:expr, $missing_behavior:expr) => {
+        ShouldFilter::new(
+            $filter.matches($kind),
+            $missing_behavior.map(|m| m.missing_behavior),
+        )
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShouldFilter {
+    AlwaysAccept,
+    AlwaysReject,
+    AcceptIfNothingElse,
+    RejectIfNothingElse,
+}
+
+impl ShouldFilter {
+    /// A filter without configuration never rejects an event
+    #[inline(always)]
+    fn new(result: Match, missing_behavior: Option<MissingBehavior>) -> Self {
+        match (result, missing_behavior) {
             (_, None) => ShouldFilter::AlwaysAccept,
             (Match::Accept, _) => ShouldFilter::AlwaysAccept,
             (Match::Reject, _) => ShouldFilter::AlwaysReject,
@@ -81,32 +223,62 @@ macro_rules! filter_matches {
                 ShouldFilter::AcceptIfNothingElse
             }
         }
-    };
-}
+    }
 
-enum ShouldFilter {
-    AlwaysAccept,
-    AlwaysReject,
-    AcceptIfNothingElse,
-    RejectIfNothingElse,
+    /// true if the event should be filtered
+    ///
+    /// With `Combination::Any` the first result of `AlwaysAccept` or `AlwaysReject`
+    /// decides, otherwise the event is filtered if any result is `RejectIfNothingElse`.
+    /// With `Combination::All` the event is filtered if any result rejects it,
+    /// unconfigured filters always accept and do not take part.
+    #[inline(always)]
+    fn combine(combination: Combination, results: impl IntoIterator<Item = Self>) -> bool {
+        match combination {
+            Combination::Any => {
+                let mut any_true = false;
+                for result in results {
+                    match result {
+                        ShouldFilter::AlwaysAccept => return false,
+                        ShouldFilter::AlwaysReject => return true,
+                        ShouldFilter::AcceptIfNothingElse => {}
+                        ShouldFilter::RejectIfNothingElse => any_true = true,
+                    }
+                }
+                any_true
+            }
+            Combination::All => results.into_iter().any(|result| {
+                matches!(
+                    result,
+                    ShouldFilter::AlwaysReject | ShouldFilter::RejectIfNothingElse
+                )
+            }),
+        }
+    }
 }
 
 impl FilterConfigs {
+    #[allow(clippy::too_many_arguments)]
     pub const fn new(
         config: &'static Array<FilterConfig>,
         host_pid: &'static Array<u32>,
         pid_filter: &'static HashMap<u32, Equality>,
-        comm_filter: &'static HashMap<[u8; 16], Equality>,
-        exe_path_filter: &'static HashMap<[u8; 4096], Equality>,
-        cmdline_filter: &'static HashMap<[u8; 256], Equality>,
+        comm_filter: StringFilter<16, 16>,
+        exe_path_filter: StringFilter<4096, MAX_PREFIX_FILTER_LEN>,
+        cmdline_filter: StringFilter<256, MAX_PREFIX_FILTER_LEN>,
+        process_comm_filter: StringFilter<16, 16>,
+        uid_filter: &'static HashMap<u32, Equality>,
+        cgroup_filter: &'static HashMap<u64, Equality>,
     ) -> Self {
         Self {
             config,
             host_pid,
             pid_filter: Filter::new(pid_filter),
-            comm_filter: Filter::new(comm_filter),
-            exe_path_filter: Filter::new(exe_path_filter),
-            cmdline_filter: Filter::new(cmdline_filter),
+            comm_filter,
+            exe_path_filter,
+            cmdline_filter,
+            process_comm_filter,
+            uid_filter: Filter::new(uid_filter),
+            cgroup_filter: CgroupFilter::new(cgroup_filter),
         }
     }
 
@@ -134,6 +306,17 @@ impl FilterConfigs {
                 T::EVENT_KIND,
                 filter_config.pid_filter
             ),
+            FilterEntry::Uid(uid) => filter_matches!(
+                self.uid_filter,
+                uid,
+                T::EVENT_KIND,
+                filter_config.uid_filter
+            ),
+            FilterEntry::Cgroup => filter_matches!(
+                self.cgroup_filter,
+                T::EVENT_KIND,
+                filter_config.cgroup_filter
+            ),
             FilterEntry::Comm(comm) => filter_matches!(
                 self.comm_filter,
                 comm,
@@ -152,6 +335,12 @@ impl FilterConfigs {
                 T::EVENT_KIND,
                 filter_config.cmdline_filter
             ),
+            FilterEntry::ProcessComm(process_comm) => filter_matches!(
+                self.process_comm_filter,
+                process_comm,
+                T::EVENT_KIND,
+                filter_config.process_comm_filter
+            ),
         }
     }
 
@@ -175,16 +364,100 @@ impl FilterConfigs {
             return true;
         };
 
-        let mut any_true = false;
-        for entry in entries {
-            match self.filter_one_inner::<T>(entry, filter_config) {
-                ShouldFilter::AlwaysAccept => return false,
-                ShouldFilter::AlwaysReject => return true,
-                ShouldFilter::AcceptIfNothingElse => {}
-                ShouldFilter::RejectIfNothingElse => any_true = true,
-            }
-        }
+        let results = entries
+            .iter()
+            .map(|entry| self.filter_one_inner::<T>(entry, filter_config));
+
+        ShouldFilter::combine(filter_config.combination.unwrap_or_default(), results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MATCH: Option<MissingBehavior> = Some(MissingBehavior::Match);
+    const NOT_MATCH: Option<MissingBehavior> = Some(MissingBehavior::NotMatch);
+
+    #[test]
+    fn should_filter_from_match() {
+        use ShouldFilter::*;
+
+        assert_eq!(ShouldFilter::new(Match::Reject, None), AlwaysAccept);
+        assert_eq!(ShouldFilter::new(Match::Accept, NOT_MATCH), AlwaysAccept);
+        assert_eq!(ShouldFilter::new(Match::Reject, MATCH), AlwaysReject);
+        assert_eq!(
+            ShouldFilter::new(Match::NotSpecified, MATCH),
+            AcceptIfNothingElse
+        );
+        assert_eq!(
+            ShouldFilter::new(Match::NotSpecified, NOT_MATCH),
+            RejectIfNothingElse
+        );
+    }
+
+    #[test]
+    fn any_is_decided_by_first_entry() {
+        use ShouldFilter::*;
+
+        assert!(!ShouldFilter::combine(
+            Combination::Any,
+            [RejectIfNothingElse, AlwaysAccept]
+        ));
+        assert!(ShouldFilter::combine(
+            Combination::Any,
+            [AlwaysReject, AlwaysAccept]
+        ));
+        assert!(!ShouldFilter::combine(
+            Combination::Any,
+            [AlwaysAccept, AlwaysReject]
+        ));
+        assert!(ShouldFilter::combine(
+            Combination::Any,
+            [AcceptIfNothingElse, RejectIfNothingElse]
+        ));
+        assert!(!ShouldFilter::combine(
+            Combination::Any,
+            [AcceptIfNothingElse, AcceptIfNothingElse]
+        ));
+        assert!(!ShouldFilter::combine(Combination::Any, []));
+    }
+
+    #[test]
+    fn all_requires_every_entry() {
+        use ShouldFilter::*;
+
+        assert!(!ShouldFilter::combine(
+            Combination::All,
+            [AlwaysAccept, AcceptIfNothingElse]
+        ));
+        assert!(ShouldFilter::combine(
+            Combination::All,
+            [AlwaysAccept, AlwaysReject]
+        ));
+        assert!(ShouldFilter::combine(
+            Combination::All,
+            [AlwaysAccept, RejectIfNothingElse]
+        ));
+        assert!(!ShouldFilter::combine(Combination::All, []));
+    }
+
+    #[test]
+    fn all_ignores_unconfigured_filters() {
+        // comm = RenderThread AND pid = 1234, the uid filter is not configured
+        let pid = ShouldFilter::new(Match::Accept, NOT_MATCH);
+        let comm = ShouldFilter::new(Match::Accept, NOT_MATCH);
+        let uid = ShouldFilter::new(Match::NotSpecified, None);
+        assert!(!ShouldFilter::combine(Combination::All, [pid, comm, uid]));
 
-        any_true
+        let other_comm = ShouldFilter::new(Match::NotSpecified, NOT_MATCH);
+        assert!(ShouldFilter::combine(
+            Combination::All,
+            [pid, other_comm, uid]
+        ));
+        assert!(!ShouldFilter::combine(
+            Combination::Any,
+            [pid, other_comm, uid]
+        ));
     }
 }
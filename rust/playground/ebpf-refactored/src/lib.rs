@@ -15,6 +15,7 @@ pub mod iterator_ext;
 pub mod maps;
 pub mod path;
 pub mod pipeline;
+pub mod rate_limit;
 pub mod scratch;
 pub mod syscalls;
 pub mod task_ext;
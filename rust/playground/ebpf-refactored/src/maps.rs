@@ -3,31 +3,70 @@
 // SPDX-License-Identifier: MIT
 
 use aya_ebpf::{
+    bindings::BPF_NOEXIST,
     macros::map,
-    maps::{Array, HashMap, LruHashMap, PerCpuArray, RingBuf},
+    maps::{lpm_trie::Key, Array, HashMap, LpmTrie, LruHashMap, PerCpuArray, RingBuf},
 };
 use ebpf_relocation_helpers::TaskStruct;
-use ebpf_types::{Equality, EventData, EventKind, FilterConfig, ProcessContext, TaskContext};
+use ebpf_types::{
+    signal_mask_bit, BlockingThresholdKey, Equality, EventData, EventKind, FilterConfig,
+    ProcessContext, RateLimit, RingBufRoute, SuppressedEvents, TaskContext, MAX_PREFIX_FILTER_LEN,
+};
 
 use crate::{
     cache::{Cache, TryWithCache},
     event_local::{EventLocal, EventLocalData, EventLocalStorage, EventLocalValue, PlaceHolder},
-    filter::{FilterConfigs, FilterEntry},
+    filter::{FilterConfigs, FilterEntry, PrefixFilter, StringFilter},
+    rate_limit::{RateLimiter, TokenBucket, TokenBucketKey},
     scratch::{ScratchSpace, ScratchValue},
+    task_ext::task_context_is_current,
 };
 
+// The filter maps are resized by userspace on load, 256 entries are only the default
+
 #[map]
 static PID_FILTER: HashMap<u32, Equality> = HashMap::with_max_entries(256, 0);
 
+/// Event kinds whose pid filter follows children, one bit per kind
+#[map]
+static PID_FILTER_FOLLOW: Array<u64> = Array::with_max_entries(1, 0);
+
+#[map]
+static UID_FILTER: HashMap<u32, Equality> = HashMap::with_max_entries(256, 0);
+
+#[map]
+static CGROUP_FILTER: HashMap<u64, Equality> = HashMap::with_max_entries(256, 0);
+
 #[map]
 static COMM_FILTER: HashMap<[u8; 16], Equality> = HashMap::with_max_entries(256, 0);
 
+#[map]
+static PROCESS_COMM_FILTER: HashMap<[u8; 16], Equality> = HashMap::with_max_entries(256, 0);
+
 #[map]
 static EXE_PATH_FILTER: HashMap<[u8; 4096], Equality> = HashMap::with_max_entries(256, 0);
 
 #[map]
 static CMDLINE_FILTER: HashMap<[u8; 256], Equality> = HashMap::with_max_entries(256, 0);
 
+#[map]
+static COMM_PREFIX_FILTER: LpmTrie<[u8; 16], Equality> = LpmTrie::with_max_entries(256, 0);
+
+#[map]
+static PROCESS_COMM_PREFIX_FILTER: LpmTrie<[u8; 16], Equality> = LpmTrie::with_max_entries(256, 0);
+
+#[map]
+static EXE_PATH_PREFIX_FILTER: LpmTrie<[u8; MAX_PREFIX_FILTER_LEN], Equality> =
+    LpmTrie::with_max_entries(256, 0);
+
+#[map]
+static CMDLINE_PREFIX_FILTER: LpmTrie<[u8; MAX_PREFIX_FILTER_LEN], Equality> =
+    LpmTrie::with_max_entries(256, 0);
+
+#[map]
+static PREFIX_FILTER_KEY: PerCpuArray<Key<[u8; MAX_PREFIX_FILTER_LEN]>> =
+    PerCpuArray::with_max_entries(1, 0);
+
 #[map]
 static FILTER_CONFIG: Array<FilterConfig> = Array::with_max_entries(EventKind::MAX as u32, 0);
 
@@ -38,17 +77,75 @@ static CONFIG: Array<u32> = Array::with_max_entries(1, 0);
 static EVENT_LOCAL_BUFFER: HashMap<u64, EventLocal<PlaceHolder>> =
     HashMap::with_max_entries(10240, 0);
 
+// The ring buffers are resized by userspace on load, unused ones only take a page
+
+#[map]
+static EVENTS: RingBuf = RingBuf::with_byte_size(8192 * 1024, 0);
+
+#[map]
+static PRIORITY_EVENTS: RingBuf = RingBuf::with_byte_size(4096, 0);
+
+#[map]
+static WRITE_EVENTS: RingBuf = RingBuf::with_byte_size(4096, 0);
+
+#[map]
+static BLOCKING_EVENTS: RingBuf = RingBuf::with_byte_size(4096, 0);
+
+#[map]
+static SIGNAL_EVENTS: RingBuf = RingBuf::with_byte_size(4096, 0);
+
+#[map]
+static GARBAGE_COLLECT_EVENTS: RingBuf = RingBuf::with_byte_size(4096, 0);
+
+#[map]
+static FILE_DESCRIPTOR_CHANGE_EVENTS: RingBuf = RingBuf::with_byte_size(4096, 0);
+
+#[map]
+static JNI_REFERENCES_EVENTS: RingBuf = RingBuf::with_byte_size(4096, 0);
+
+#[map]
+static PROCESS_LIFECYCLE_EVENTS: RingBuf = RingBuf::with_byte_size(4096, 0);
+
+#[map]
+static CRASH_EVENTS: RingBuf = RingBuf::with_byte_size(4096, 0);
+
+#[map]
+static RING_BUF_ROUTES: Array<RingBufRoute> = Array::with_max_entries(EventKind::MAX as u32, 0);
+
+#[map]
+static RATE_LIMITS: Array<RateLimit> = Array::with_max_entries(EventKind::MAX as u32, 0);
+
+#[map]
+static SAMPLE_COUNTERS: PerCpuArray<u64> = PerCpuArray::with_max_entries(EventKind::MAX as u32, 0);
+
+/// One bucket per process and event kind
+#[map]
+static TOKEN_BUCKETS: LruHashMap<TokenBucketKey, TokenBucket> =
+    LruHashMap::with_max_entries(10240, 0);
+
+/// Summed up over all cpus by userspace
+#[map]
+static SUPPRESSED_EVENTS: PerCpuArray<SuppressedEvents> =
+    PerCpuArray::with_max_entries(EventKind::MAX as u32, 0);
+
+#[map]
+static BLOCKING_THRESHOLDS: HashMap<BlockingThresholdKey, u64> = HashMap::with_max_entries(1024, 0);
+
 #[map]
-pub static EVENTS: RingBuf = RingBuf::with_byte_size(8192 * 1024, 0);
+static BLOCKING_IGNORED_SYSCALLS: HashMap<u32, u8> = HashMap::with_max_entries(512, 0);
 
+/// Bitmask of the denied signals, the second entry is non-zero if signals
+/// without a bit are denied as well
 #[map]
-pub static GLOBAL_BLOCKING_THRESHOLD: Array<u64> = Array::with_max_entries(1, 0);
+static SIGNAL_DENY_MASK: Array<u64> = Array::with_max_entries(2, 0);
 
+/// Resized by userspace on load, one entry per thread
 #[map]
 static TASK_INFO: LruHashMap<u32, TaskContext> = LruHashMap::with_max_entries(10240, 0);
 
+/// Resized by userspace on load, one entry per process
 #[map]
-static PROCESS_INFO: LruHashMap<u32, ProcessContext> = LruHashMap::with_max_entries(1, 0);
+static PROCESS_INFO: LruHashMap<u32, ProcessContext> = LruHashMap::with_max_entries(1024, 0);
 
 #[map]
 static SCRATCH_MAP: PerCpuArray<(bool, [u8; 8192])> = PerCpuArray::with_max_entries(1, 0);
@@ -92,9 +189,31 @@ static FILTER_CONFIGS: FilterConfigs = FilterConfigs::new(
     &FILTER_CONFIG,
     &CONFIG,
     &PID_FILTER,
-    &COMM_FILTER,
-    &EXE_PATH_FILTER,
-    &CMDLINE_FILTER,
+    StringFilter::new(
+        &COMM_FILTER,
+        PrefixFilter::new(&COMM_PREFIX_FILTER, &PREFIX_FILTER_KEY),
+    ),
+    StringFilter::new(
+        &EXE_PATH_FILTER,
+        PrefixFilter::new(&EXE_PATH_PREFIX_FILTER, &PREFIX_FILTER_KEY),
+    ),
+    StringFilter::new(
+        &CMDLINE_FILTER,
+        PrefixFilter::new(&CMDLINE_PREFIX_FILTER, &PREFIX_FILTER_KEY),
+    ),
+    StringFilter::new(
+        &PROCESS_COMM_FILTER,
+        PrefixFilter::new(&PROCESS_COMM_PREFIX_FILTER, &PREFIX_FILTER_KEY),
+    ),
+    &UID_FILTER,
+    &CGROUP_FILTER,
+);
+
+static RATE_LIMITER: RateLimiter = RateLimiter::new(
+    &RATE_LIMITS,
+    &SAMPLE_COUNTERS,
+    &TOKEN_BUCKETS,
+    &SUPPRESSED_EVENTS,
 );
 
 pub struct ScratchPath;
@@ -118,10 +237,25 @@ impl ScratchEventLocal {
 pub struct TaskInfoCache;
 
 impl TaskInfoCache {
+    /// Entries which no longer match the task are read again
     #[inline(always)]
     pub fn get(task: TaskStruct) -> Option<&'static TaskContext> {
+        let context = task
+            .with_cache(&TASK_INFO_CACHE, SCRATCH_SPACE.cast())
+            .ok()?;
+        if task_context_is_current(&task, context).ok()? {
+            return Some(context);
+        }
+
+        Self::evict(task.pid().ok()?);
         task.with_cache(&TASK_INFO_CACHE, SCRATCH_SPACE.cast()).ok()
     }
+
+    /// Must be called if the thread changes, e.g. on exec or exit
+    #[inline(always)]
+    pub fn evict(tid: u32) {
+        let _ = TASK_INFO_CACHE.delete(&tid);
+    }
 }
 
 pub struct ProcessInfoCache;
@@ -132,6 +266,19 @@ impl ProcessInfoCache {
         task.with_cache(&PROCESS_INFO_CACHE, SCRATCH_SPACE.cast())
             .ok()
     }
+
+    /// Only looks up the cache, the context can not be read anymore once
+    /// the memory of the process is released
+    #[inline(always)]
+    pub fn get_cached(pid: u32) -> Option<&'static ProcessContext> {
+        PROCESS_INFO_CACHE.get(&pid)
+    }
+
+    /// Must be called if the process changes, e.g. on exec or exit
+    #[inline(always)]
+    pub fn evict(pid: u32) {
+        let _ = PROCESS_INFO_CACHE.delete(&pid);
+    }
 }
 
 pub struct EventFilter;
@@ -146,6 +293,128 @@ impl EventFilter {
     }
 }
 
+pub struct RateLimits;
+
+impl RateLimits {
+    #[inline(always)]
+    pub fn admit<T: EventData>(pid: u32) -> bool {
+        RATE_LIMITER.admit::<T>(pid)
+    }
+}
+
+pub struct EventRingBufs;
+
+impl EventRingBufs {
+    #[inline(always)]
+    pub fn get<T: EventData>() -> &'static RingBuf {
+        match RING_BUF_ROUTES.get(T::EVENT_KIND as u32) {
+            Some(RingBufRoute::Dedicated) => Self::dedicated(T::EVENT_KIND),
+            Some(RingBufRoute::Priority) => &PRIORITY_EVENTS,
+            _ => &EVENTS,
+        }
+    }
+
+    #[inline(always)]
+    fn dedicated(kind: EventKind) -> &'static RingBuf {
+        match kind {
+            EventKind::Write => &WRITE_EVENTS,
+            EventKind::Blocking => &BLOCKING_EVENTS,
+            EventKind::Signal => &SIGNAL_EVENTS,
+            EventKind::GarbageCollect => &GARBAGE_COLLECT_EVENTS,
+            EventKind::FileDescriptorChange => &FILE_DESCRIPTOR_CHANGE_EVENTS,
+            EventKind::JniReferences => &JNI_REFERENCES_EVENTS,
+            EventKind::ProcessLifecycle => &PROCESS_LIFECYCLE_EVENTS,
+            EventKind::Crash => &CRASH_EVENTS,
+            EventKind::MAX => &EVENTS,
+        }
+    }
+}
+
+pub struct PidFilterFollow;
+
+impl PidFilterFollow {
+    /// A new process inherits the pid filter entry of its parent
+    /// for each event kind that follows children.
+    /// Entries configured by userspace are not overwritten.
+    #[inline(always)]
+    pub fn fork(parent_pid: u32, child_pid: u32) -> Option<()> {
+        let follow = *PID_FILTER_FOLLOW.get(0)?;
+        let parent = unsafe { PID_FILTER.get(&parent_pid) }?;
+
+        let used_for_event_kind = parent.used_for_event_kind & follow;
+        if used_for_event_kind == 0 {
+            return None;
+        }
+
+        let child = Equality {
+            eq_for_event_kind: parent.eq_for_event_kind & follow,
+            used_for_event_kind,
+        };
+        PID_FILTER
+            .insert(&child_pid, &child, BPF_NOEXIST as u64)
+            .ok()
+    }
+
+    /// Pids are reused, so the entry of an exited process must not match its successor
+    #[inline(always)]
+    pub fn exit(pid: u32) -> Option<()> {
+        let follow = *PID_FILTER_FOLLOW.get(0)?;
+        let entry = unsafe { PID_FILTER.get(&pid) }?;
+
+        if entry.used_for_event_kind & follow == 0 {
+            return None;
+        }
+
+        PID_FILTER.remove(&pid).ok()
+    }
+}
+
+pub struct BlockingThresholds;
+
+impl BlockingThresholds {
+    /// Looks up the threshold of the most specific matching rule,
+    /// rules for a pid take precedence over rules for a syscall.
+    #[inline(always)]
+    pub fn get(pid: u32, syscall_id: u32) -> Option<u64> {
+        let keys = [
+            BlockingThresholdKey::new(pid, syscall_id),
+            BlockingThresholdKey::new(pid, BlockingThresholdKey::ANY_SYSCALL),
+            BlockingThresholdKey::new(BlockingThresholdKey::ANY_PID, syscall_id),
+            BlockingThresholdKey::new(
+                BlockingThresholdKey::ANY_PID,
+                BlockingThresholdKey::ANY_SYSCALL,
+            ),
+        ];
+
+        for key in &keys {
+            if let Some(threshold) = unsafe { BLOCKING_THRESHOLDS.get(key) } {
+                return Some(*threshold);
+            }
+        }
+
+        None
+    }
+
+    #[inline(always)]
+    pub fn is_ignored(syscall_id: u32) -> bool {
+        unsafe { BLOCKING_IGNORED_SYSCALLS.get(&syscall_id) }.is_some()
+    }
+}
+
+pub struct SignalFilter;
+
+impl SignalFilter {
+    #[inline(always)]
+    pub fn is_denied(signal: u32) -> bool {
+        match signal_mask_bit(signal) {
+            0 => SIGNAL_DENY_MASK.get(1).is_some_and(|denied| *denied != 0),
+            bit => SIGNAL_DENY_MASK
+                .get(0)
+                .is_some_and(|deny_mask| deny_mask & bit != 0),
+        }
+    }
+}
+
 pub struct EventStorage;
 
 impl EventStorage {
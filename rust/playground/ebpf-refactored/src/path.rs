@@ -190,6 +190,9 @@ pub fn read_path_to_buf_with_default(path: Path, buf: &mut [u8; PATH_MAX]) -> Op
 pub fn get_path_from_fd(fd: u64, task: TaskStruct) -> Option<Path> {
     let files = task.files().ok()?;
     let fdtable = files.fdt().ok()?;
+    if fd >= fdtable.max_fds().ok()? as u64 {
+        return None;
+    }
     let fds: *mut *mut *mut _ = fdtable.fd();
 
     let file = unsafe { bpf_probe_read_kernel(fds).ok()? };
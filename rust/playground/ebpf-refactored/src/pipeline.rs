@@ -11,24 +11,32 @@ use aya_ebpf::bindings::pt_regs;
 use aya_ebpf::bindings::user_pt_regs as pt_regs;
 
 use aya_ebpf::{
-    helpers::{bpf_get_current_task, bpf_ktime_get_ns, bpf_probe_read_user},
+    helpers::{bpf_get_current_task, bpf_ktime_get_boot_ns, bpf_probe_read_user},
     macros::{raw_tracepoint, uprobe, uretprobe},
     programs::{ProbeContext, RawTracePointContext, RetProbeContext},
     EbpfContext, PtRegs,
 };
-use ebpf_relocation_helpers::{ffi::art_heap, ArtHeap, TaskStruct};
+use ebpf_relocation_helpers::{ffi::art_heap, ArtHeap, KSigaction, TaskStruct};
 use ebpf_types::{
-    Blocking, Event, EventData, FileDescriptorChange, GarbageCollect, JniReferences,
-    ProcessContext, Signal, TaskContext, Write,
+    Blocking, Crash, Event, EventData, FileDescriptorChange, GarbageCollect, JniReferences,
+    ProcessContext, ProcessLifecycle, RecordHeader, Signal, TaskContext, Write,
 };
 
 use crate::{
     event_local::{EventLocal, EventLocalData, EventLocalValue},
-    events::SyscallProg,
+    events::{
+        crash::{initialize_crash, is_fatal},
+        process::{initialize_process_exec, initialize_process_exit, initialize_process_fork},
+        signal::{
+            initialize_signal_deliver, initialize_signal_generate, siginfo_from_kernel,
+            siginfo_sender_pid,
+        },
+        SyscallProg,
+    },
     filter::FilterEntry,
     maps::{
-        EventFilter, EventStorage, ProcessInfoCache, ScratchEventLocal, TaskInfoCache, EVENTS,
-        GLOBAL_BLOCKING_THRESHOLD,
+        BlockingThresholds, EventFilter, EventRingBufs, EventStorage, PidFilterFollow,
+        ProcessInfoCache, RateLimits, ScratchEventLocal, SignalFilter, TaskInfoCache,
     },
     scratch::ScratchValue,
 };
@@ -48,24 +56,33 @@ impl ProgramInfo<'_> {
         })
     }
 
-    fn filters(&self) -> [FilterEntry; 6] {
+    fn filters(&self) -> [FilterEntry; 9] {
         [
             FilterEntry::OwnPid(&self.task_context.pid),
             FilterEntry::Pid(&self.task_context.pid),
             FilterEntry::Tid(&self.task_context.tid),
+            FilterEntry::Uid(&self.task_context.uid),
+            FilterEntry::Cgroup,
             FilterEntry::Comm(&self.task_context.comm),
+            FilterEntry::ProcessComm(&self.task_context.process_comm),
             FilterEntry::ExePath(&self.process_context.exe_path),
             FilterEntry::Cmdline(&self.process_context.cmdline),
         ]
     }
 
     fn submit<T: EventData + 'static>(self, event: &T) -> Option<()> {
-        let mut entry = EVENTS.reserve::<Event<T>>(0)?;
+        // Suppressed events never take space in the ring buffer
+        if !RateLimits::admit::<T>(self.task_context.pid) {
+            return None;
+        }
+
+        let mut entry = EventRingBufs::get::<T>().reserve::<Event<T>>(0)?;
         let ptr = entry.as_mut_ptr();
 
         unsafe {
-            (&raw mut (*ptr).kind).write(T::EVENT_KIND);
-            (&raw mut (*ptr).context.timestamp).write(bpf_ktime_get_ns());
+            (&raw mut (*ptr).header).write(RecordHeader::new::<T>());
+            // Unlike the monotonic clock, the boot clock keeps counting during suspend
+            (&raw mut (*ptr).context.timestamp).write(bpf_ktime_get_boot_ns());
             copy_nonoverlapping(self.task_context, &raw mut (*ptr).context.task, 1);
             copy_nonoverlapping(event, &raw mut (*ptr).data, 1);
         }
@@ -123,6 +140,10 @@ unsafe fn sys_exit_return_value(ctx: &RawTracePointContext) -> u64 {
     *(ctx.as_ptr().add(8) as *mut u64)
 }
 
+unsafe fn raw_tracepoint_arg<T>(ctx: &RawTracePointContext, n: usize) -> T {
+    (ctx.as_ptr().add(n * 8) as *mut T).read()
+}
+
 pub struct SysEnterInfo {
     pub task: TaskStruct,
     pub syscall_id: i64,
@@ -170,7 +191,7 @@ fn sys_enter<P: SyscallProg>(ctx: &RawTracePointContext) -> Option<()> {
 
 fn sys_exit<P: SyscallProg>(
     ctx: &RawTracePointContext,
-    filter: impl Fn(&P) -> Option<()>,
+    filter: impl Fn(&P, &TaskContext) -> Option<()>,
 ) -> Option<()> {
     let exit_info = SysExitInfo::new(ctx);
     let program_info = ProgramInfoExit::new(exit_info.task)?;
@@ -187,7 +208,7 @@ fn sys_exit<P: SyscallProg>(
         &mut event,
     )?;
 
-    filter(event)?;
+    filter(event, program_info.info.task_context)?;
 
     program_info.info.submit(event)
 }
@@ -199,7 +220,7 @@ pub fn sys_enter_write(ctx: RawTracePointContext) -> Option<()> {
 
 #[raw_tracepoint]
 pub fn sys_exit_write(ctx: RawTracePointContext) -> Option<()> {
-    sys_exit::<Write>(&ctx, |_| Some(()))
+    sys_exit::<Write>(&ctx, |_, _| Some(()))
 }
 
 #[raw_tracepoint]
@@ -209,9 +230,9 @@ pub fn sys_enter_blocking(ctx: RawTracePointContext) -> Option<()> {
 
 #[raw_tracepoint]
 pub fn sys_exit_blocking(ctx: RawTracePointContext) -> Option<()> {
-    sys_exit::<Blocking>(&ctx, |event| {
-        if let Some(threshold) = GLOBAL_BLOCKING_THRESHOLD.get(0) {
-            if event.duration <= *threshold {
+    sys_exit::<Blocking>(&ctx, |event, task| {
+        if let Some(threshold) = BlockingThresholds::get(task.pid, event.syscall_id as u32) {
+            if event.duration <= threshold {
                 return None;
             }
         }
@@ -226,7 +247,100 @@ pub fn sys_enter_signal(ctx: RawTracePointContext) -> Option<()> {
 
 #[raw_tracepoint]
 pub fn sys_exit_signal(ctx: RawTracePointContext) -> Option<()> {
-    sys_exit::<Signal>(&ctx, |_| Some(()))
+    sys_exit::<Signal>(&ctx, filter_signal)
+}
+
+fn filter_signal(event: &Signal, _: &TaskContext) -> Option<()> {
+    if SignalFilter::is_denied(event.signal) {
+        return None;
+    }
+    Some(())
+}
+
+#[raw_tracepoint]
+pub fn trace_signal_generate(ctx: RawTracePointContext) -> Option<()> {
+    let signal = unsafe { raw_tracepoint_arg::<u64>(&ctx, 0) as u32 };
+    if SignalFilter::is_denied(signal) {
+        return None;
+    }
+
+    let task = unsafe { current_task() };
+    let program_info = ProgramInfo::new(task)?;
+
+    if EventFilter::filter_many::<Signal>(&program_info.filters()) {
+        return None;
+    }
+
+    let mut event = ScratchEventLocal::get::<Signal>()?;
+    let event = unsafe {
+        initialize_signal_generate(
+            signal,
+            TaskStruct::new(raw_tracepoint_arg(&ctx, 2)),
+            raw_tracepoint_arg::<u64>(&ctx, 3) as i32 != 0,
+            raw_tracepoint_arg::<u64>(&ctx, 4) as i32,
+            siginfo_from_kernel(raw_tracepoint_arg(&ctx, 1)),
+            program_info.task_context,
+            &mut event,
+        )?
+    };
+
+    program_info.submit(event)
+}
+
+#[raw_tracepoint]
+pub fn trace_signal_deliver(ctx: RawTracePointContext) -> Option<()> {
+    let signal = unsafe { raw_tracepoint_arg::<u64>(&ctx, 0) as u32 };
+    if SignalFilter::is_denied(signal) {
+        return None;
+    }
+
+    let task = unsafe { current_task() };
+    let program_info = ProgramInfo::new(task)?;
+
+    if EventFilter::filter_many::<Signal>(&program_info.filters()) {
+        return None;
+    }
+
+    let mut event = ScratchEventLocal::get::<Signal>()?;
+    let event = unsafe {
+        initialize_signal_deliver(
+            signal,
+            siginfo_sender_pid(raw_tracepoint_arg(&ctx, 1)),
+            program_info.task_context,
+            &mut event,
+        )?
+    };
+
+    program_info.submit(event)
+}
+
+#[raw_tracepoint]
+pub fn trace_crash(ctx: RawTracePointContext) -> Option<()> {
+    let signal = unsafe { raw_tracepoint_arg::<u64>(&ctx, 0) as u32 };
+    let action = unsafe { KSigaction::new(raw_tracepoint_arg(&ctx, 2)) };
+    if !is_fatal(signal, action) {
+        return None;
+    }
+
+    let task = unsafe { current_task() };
+    let program_info = ProgramInfo::new(task)?;
+
+    if EventFilter::filter_many::<Crash>(&program_info.filters()) {
+        return None;
+    }
+
+    let mut event = ScratchEventLocal::get::<Crash>()?;
+    let event = unsafe {
+        initialize_crash(
+            &ctx,
+            signal,
+            raw_tracepoint_arg(&ctx, 1),
+            program_info.process_context,
+            &mut event,
+        )?
+    };
+
+    program_info.submit(event)
 }
 
 #[raw_tracepoint]
@@ -236,7 +350,165 @@ pub fn sys_enter_fdtracking(ctx: RawTracePointContext) -> Option<()> {
 
 #[raw_tracepoint]
 pub fn sys_exit_fdtracking(ctx: RawTracePointContext) -> Option<()> {
-    sys_exit::<FileDescriptorChange>(&ctx, |_| Some(()))
+    sys_exit::<FileDescriptorChange>(&ctx, |_, _| Some(()))
+}
+
+#[raw_tracepoint]
+pub fn trace_process_fork(ctx: RawTracePointContext) -> Option<()> {
+    let task = unsafe { current_task() };
+    let program_info = ProgramInfo::new(task)?;
+
+    if EventFilter::filter_many::<ProcessLifecycle>(&program_info.filters()) {
+        return None;
+    }
+
+    let mut event = ScratchEventLocal::get::<ProcessLifecycle>()?;
+    let event = unsafe {
+        initialize_process_fork(
+            TaskStruct::new(raw_tracepoint_arg(&ctx, 0)),
+            TaskStruct::new(raw_tracepoint_arg(&ctx, 1)),
+            program_info.process_context,
+            &mut event,
+        )?
+    };
+
+    program_info.submit(event)
+}
+
+#[raw_tracepoint]
+pub fn trace_process_exec(ctx: RawTracePointContext) -> Option<()> {
+    let task = unsafe { current_task() };
+
+    // Might run before `evict_cache_exec`, the event must have the new identity
+    evict_exec(&ctx, task)?;
+
+    let program_info = ProgramInfo::new(task)?;
+
+    if EventFilter::filter_many::<ProcessLifecycle>(&program_info.filters()) {
+        return None;
+    }
+
+    let mut event = ScratchEventLocal::get::<ProcessLifecycle>()?;
+    let event = initialize_process_exec(task, program_info.process_context, &mut event)?;
+
+    program_info.submit(event)
+}
+
+#[raw_tracepoint]
+pub fn trace_process_exit(_: RawTracePointContext) -> Option<()> {
+    let task = unsafe { current_task() };
+
+    let _ = submit_process_exit(task);
+
+    // Submitting caches the exiting task again
+    evict_exit(task)
+}
+
+/// The memory of the task is already released, so the process context is taken
+/// from the cache and filtered like the one in the event.
+#[inline(always)]
+fn submit_process_exit(task: TaskStruct) -> Option<()> {
+    let task_context = TaskInfoCache::get(task)?;
+    let process_context = ProcessInfoCache::get_cached(task.tgid().ok()?);
+
+    let mut event = ScratchEventLocal::get::<ProcessLifecycle>()?;
+    let event = initialize_process_exit(task, process_context, &mut event)?;
+
+    let program_info = ProgramInfo {
+        task_context,
+        process_context: &event.process,
+    };
+    if EventFilter::filter_many::<ProcessLifecycle>(&program_info.filters()) {
+        return None;
+    }
+
+    program_info.submit(event)
+}
+
+/// Attached to `sched_process_exec` independent of the enabled features.
+#[raw_tracepoint]
+pub fn evict_cache_exec(ctx: RawTracePointContext) -> Option<()> {
+    evict_exec(&ctx, unsafe { current_task() })
+}
+
+/// Attached to `sched_process_exit` independent of the enabled features.
+#[raw_tracepoint]
+pub fn evict_cache_exit(_: RawTracePointContext) -> Option<()> {
+    evict_exit(unsafe { current_task() })
+}
+
+/// `TP_PROTO(struct task_struct *task, const char *comm)`
+///
+/// Attached to `task_rename` independent of the enabled features, this covers
+/// `prctl(PR_SET_NAME)` and writes to `/proc/<pid>/comm`. The zygote renames
+/// an app this way after rewriting its cmdline, so the process is evicted too.
+#[raw_tracepoint]
+pub fn evict_cache_rename(ctx: RawTracePointContext) -> Option<()> {
+    let task = unsafe { TaskStruct::new(raw_tracepoint_arg(&ctx, 0)) };
+
+    TaskInfoCache::evict(task.pid().ok()?);
+    ProcessInfoCache::evict(task.tgid().ok()?);
+
+    Some(())
+}
+
+/// `TP_PROTO(struct task_struct *parent, struct task_struct *child)`
+///
+/// Attached to `sched_process_fork` independent of the enabled features.
+#[raw_tracepoint]
+pub fn follow_children_fork(ctx: RawTracePointContext) -> Option<()> {
+    let parent = unsafe { TaskStruct::new(raw_tracepoint_arg(&ctx, 0)) };
+    let child = unsafe { TaskStruct::new(raw_tracepoint_arg(&ctx, 1)) };
+
+    let parent_pid = parent.tgid().ok()?;
+    let child_pid = child.tgid().ok()?;
+    if child_pid == parent_pid {
+        // A new thread, not a new process
+        return None;
+    }
+
+    PidFilterFollow::fork(parent_pid, child_pid)
+}
+
+/// Attached to `sched_process_exit` independent of the enabled features.
+#[raw_tracepoint]
+pub fn follow_children_exit(_: RawTracePointContext) -> Option<()> {
+    let task = unsafe { current_task() };
+
+    let pid = task.tgid().ok()?;
+    if task.pid().ok()? != pid {
+        return None;
+    }
+
+    PidFilterFollow::exit(pid)
+}
+
+/// `TP_PROTO(struct task_struct *p, pid_t old_pid, struct linux_binprm *bprm)`
+///
+/// The cached comm, cmdline and exe path belong to the old program.
+/// A non-leader thread calling exec takes over the pid of the leader,
+/// its old tid is `old_pid`.
+#[inline(always)]
+fn evict_exec(ctx: &RawTracePointContext, task: TaskStruct) -> Option<()> {
+    TaskInfoCache::evict(task.pid().ok()?);
+    TaskInfoCache::evict(unsafe { raw_tracepoint_arg::<u64>(ctx, 1) } as u32);
+    ProcessInfoCache::evict(task.tgid().ok()?);
+
+    Some(())
+}
+
+/// Pids are reused, so entries of exited tasks must not be found again
+#[inline(always)]
+fn evict_exit(task: TaskStruct) -> Option<()> {
+    let tid = task.pid().ok()?;
+    let pid = task.tgid().ok()?;
+
+    TaskInfoCache::evict(tid);
+    if tid == pid {
+        ProcessInfoCache::evict(pid);
+    }
+
+    Some(())
 }
 
 unsafe fn trace_jni_enter(data: JniReferences) -> Option<()> {
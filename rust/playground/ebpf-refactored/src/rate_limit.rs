@@ -0,0 +1,210 @@
+// SPDX-FileCopyrightText: 2025 Felix Hilgers <felix.hilgers@fau.de>
+//
+// SPDX-License-Identifier: MIT
+
+use aya_ebpf::{
+    helpers::bpf_ktime_get_ns,
+    maps::{Array, LruHashMap, PerCpuArray},
+};
+use ebpf_types::{EventData, RateLimit, SuppressedEvents};
+
+/// An event takes one token, tokens are counted in billionths
+/// so that the refill of a nanosecond is an integer.
+const TOKEN: u64 = 1_000_000_000;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct TokenBucketKey {
+    pid: u32,
+    kind: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct TokenBucket {
+    tokens: u64,
+    last_refill: u64,
+}
+
+impl TokenBucket {
+    /// The first events of a process are not limited
+    fn full(limit: &RateLimit, now: u64) -> Self {
+        Self {
+            tokens: capacity(limit),
+            last_refill: now,
+        }
+    }
+
+    /// Refills the tokens of the time since the last refill and takes one if available
+    fn take(&mut self, limit: &RateLimit, now: u64) -> bool {
+        let elapsed = now.saturating_sub(self.last_refill);
+        let refill = elapsed.saturating_mul(limit.events_per_second as u64);
+
+        self.tokens = self.tokens.saturating_add(refill).min(capacity(limit));
+        self.last_refill = now;
+
+        if self.tokens < TOKEN {
+            return false;
+        }
+        self.tokens -= TOKEN;
+        true
+    }
+}
+
+fn capacity(limit: &RateLimit) -> u64 {
+    let burst = match limit.burst {
+        0 => limit.events_per_second,
+        burst => burst,
+    };
+    burst as u64 * TOKEN
+}
+
+/// The counter is per cpu, so each cpu submits every n-th of its events
+fn is_sampled(count: u64, sample_every: u32) -> bool {
+    sample_every <= 1 || count.is_multiple_of(sample_every as u64)
+}
+
+pub struct RateLimiter {
+    limits: &'static Array<RateLimit>,
+    sample_counters: &'static PerCpuArray<u64>,
+    buckets: &'static LruHashMap<TokenBucketKey, TokenBucket>,
+    suppressed: &'static PerCpuArray<SuppressedEvents>,
+}
+
+impl RateLimiter {
+    pub const fn new(
+        limits: &'static Array<RateLimit>,
+        sample_counters: &'static PerCpuArray<u64>,
+        buckets: &'static LruHashMap<TokenBucketKey, TokenBucket>,
+        suppressed: &'static PerCpuArray<SuppressedEvents>,
+    ) -> Self {
+        Self {
+            limits,
+            sample_counters,
+            buckets,
+            suppressed,
+        }
+    }
+
+    /// Whether an event of process `pid` may be submitted,
+    /// suppressed events are counted for their kind.
+    #[inline(always)]
+    pub fn admit<T: EventData>(&self, pid: u32) -> bool {
+        let kind = T::EVENT_KIND as u32;
+        let Some(limit) = self.limits.get(kind) else {
+            return true;
+        };
+
+        if !self.sample(kind, limit) {
+            self.count(kind, |suppressed| suppressed.sampled += 1);
+            return false;
+        }
+
+        if !self.take_token(kind, pid, limit) {
+            self.count(kind, |suppressed| suppressed.rate_limited += 1);
+            return false;
+        }
+
+        true
+    }
+
+    #[inline(always)]
+    fn sample(&self, kind: u32, limit: &RateLimit) -> bool {
+        if limit.sample_every <= 1 {
+            return true;
+        }
+
+        let Some(counter) = self.sample_counters.get_ptr_mut(kind) else {
+            return true;
+        };
+
+        unsafe {
+            let count = *counter;
+            *counter = count.wrapping_add(1);
+            is_sampled(count, limit.sample_every)
+        }
+    }
+
+    /// Buckets are shared by all cpus, concurrent events of a process
+    /// may race for the last token and both be submitted.
+    #[inline(always)]
+    fn take_token(&self, kind: u32, pid: u32, limit: &RateLimit) -> bool {
+        if limit.events_per_second == 0 {
+            return true;
+        }
+
+        let key = TokenBucketKey { pid, kind };
+        let now = unsafe { bpf_ktime_get_ns() };
+
+        match self.buckets.get_ptr_mut(&key) {
+            Some(bucket) => unsafe { (*bucket).take(limit, now) },
+            None => {
+                let mut bucket = TokenBucket::full(limit, now);
+                let admitted = bucket.take(limit, now);
+                let _ = self.buckets.insert(&key, &bucket, 0);
+                admitted
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn count(&self, kind: u32, f: impl FnOnce(&mut SuppressedEvents)) {
+        if let Some(suppressed) = self.suppressed.get_ptr_mut(kind) {
+            f(unsafe { &mut *suppressed });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        sample_every: 0,
+        events_per_second: 10,
+        burst: 2,
+    };
+
+    #[test]
+    fn bucket_allows_burst() {
+        let mut bucket = TokenBucket::full(&LIMIT, 0);
+
+        assert!(bucket.take(&LIMIT, 0));
+        assert!(bucket.take(&LIMIT, 0));
+        assert!(!bucket.take(&LIMIT, 0));
+    }
+
+    #[test]
+    fn bucket_refills_at_rate() {
+        let mut bucket = TokenBucket::full(&LIMIT, 0);
+        bucket.take(&LIMIT, 0);
+        bucket.take(&LIMIT, 0);
+
+        // One token every 100ms
+        assert!(!bucket.take(&LIMIT, 99_999_999));
+        assert!(bucket.take(&LIMIT, 100_000_000));
+        assert!(!bucket.take(&LIMIT, 100_000_000));
+
+        // Never more than the burst after a long pause
+        let later = 3600 * TOKEN;
+        assert!(bucket.take(&LIMIT, later));
+        assert!(bucket.take(&LIMIT, later));
+        assert!(!bucket.take(&LIMIT, later));
+    }
+
+    #[test]
+    fn burst_defaults_to_rate() {
+        let limit = RateLimit { burst: 0, ..LIMIT };
+
+        assert_eq!(TokenBucket::full(&limit, 0).tokens, 10 * TOKEN);
+    }
+
+    #[test]
+    fn sampling_submits_every_nth() {
+        let sampled = (0..9).filter(|&count| is_sampled(count, 3)).count();
+        assert_eq!(sampled, 3);
+
+        assert!((0..9).all(|count| is_sampled(count, 0)));
+        assert!((0..9).all(|count| is_sampled(count, 1)));
+    }
+}
@@ -4,7 +4,9 @@
 
 use core::{mem::MaybeUninit, ptr::write_bytes};
 
-use aya_ebpf::helpers::{bpf_probe_read_kernel_buf, bpf_probe_read_user_buf};
+use aya_ebpf::helpers::{
+    bpf_probe_read_kernel, bpf_probe_read_kernel_buf, bpf_probe_read_user_buf,
+};
 use ebpf_relocation_helpers::TaskStruct;
 use ebpf_types::{ProcessContext, TaskContext};
 
@@ -55,17 +57,38 @@ fn task_get_task_context<'a>(
     let leader = task.group_leader()?;
     let parent = leader.real_parent()?;
     let parent_process = parent.group_leader()?;
+    let cred = task.cred()?;
 
     unsafe {
         (&raw mut (*ptr).pid).write(task.tgid()?);
         (&raw mut (*ptr).tid).write(task.pid()?);
         (&raw mut (*ptr).ppid).write(parent_process.pid()?);
+        (&raw mut (*ptr).uid).write(cred.uid()?.val);
+        (&raw mut (*ptr).gid).write(cred.gid()?.val);
         bpf_probe_read_kernel_buf(task.comm() as *const u8, &mut (*ptr).comm)?;
+        bpf_probe_read_kernel_buf(leader.comm() as *const u8, &mut (*ptr).process_comm)?;
 
         Ok(mem.assume_init_mut())
     }
 }
 
+/// The entry is cached per thread, but the leader can rename itself at any
+/// time, e.g. the zygote names the app after forking. Only the renamed thread
+/// is evicted then, so the comm of the leader is compared on every lookup.
+/// The same goes for the credentials, which change with `setuid` or `setgid`.
+#[inline(always)]
+pub fn task_context_is_current(task: &TaskStruct, context: &TaskContext) -> Result<bool, i64> {
+    let cred = task.cred()?;
+    if cred.uid()?.val != context.uid || cred.gid()?.val != context.gid {
+        return Ok(false);
+    }
+
+    let leader = task.group_leader()?;
+    let process_comm = unsafe { bpf_probe_read_kernel(leader.comm() as *const [u8; 16])? };
+
+    Ok(u128::from_ne_bytes(process_comm) == u128::from_ne_bytes(context.process_comm))
+}
+
 #[inline(always)]
 fn task_get_process_context<'a>(
     task: &TaskStruct,
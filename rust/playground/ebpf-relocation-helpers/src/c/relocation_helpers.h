@@ -85,6 +85,19 @@ struct files_struct {
 	struct fdtable *fdt;
 };
 
+typedef struct {
+	u32 val;
+} kuid_t;
+
+typedef struct {
+	u32 val;
+} kgid_t;
+
+struct cred {
+	kuid_t uid;
+	kgid_t gid;
+};
+
 struct task_struct {
 	struct mm_struct *mm;
 	s32 pid;
@@ -94,6 +107,16 @@ struct task_struct {
 	struct files_struct *files;
 	struct task_struct *real_parent;
 	struct task_struct *group_leader;
+	s32 exit_code;
+	const struct cred *cred;
+};
+
+struct sigaction {
+	void *sa_handler;
+};
+
+struct k_sigaction {
+	struct sigaction sa;
 };
 
 struct art_heap {
@@ -149,6 +172,26 @@ inline struct task_struct **task_struct_group_leader(struct task_struct *task)
 	return &task->group_leader;
 }
 
+inline s32 *task_struct_exit_code(struct task_struct *task)
+{
+	return &task->exit_code;
+}
+
+inline const struct cred **task_struct_cred(struct task_struct *task)
+{
+	return &task->cred;
+}
+
+inline kuid_t *cred_uid(struct cred *cred)
+{
+	return &cred->uid;
+}
+
+inline kgid_t *cred_gid(struct cred *cred)
+{
+	return &cred->gid;
+}
+
 inline u64 *mm_struct_arg_start(struct mm_struct *mm)
 {
 	return &mm->arg_start;
@@ -249,6 +292,16 @@ inline long unsigned int **fdtable_open_fds(struct fdtable *fdt)
 	return &fdt->open_fds;
 }
 
+inline struct sigaction *k_sigaction_sa(struct k_sigaction *ka)
+{
+	return &ka->sa;
+}
+
+inline void **sigaction_sa_handler(struct sigaction *sa)
+{
+	return &sa->sa_handler;
+}
+
 inline u64 *art_heap_target_footprint(struct art_heap *heap)
 {
 	return &heap->target_footprint;
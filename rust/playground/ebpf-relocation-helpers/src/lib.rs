@@ -304,6 +304,13 @@ gen_accessors!(task_struct => {
     wrapper group_leader: TaskStruct,
     wrapper files: FilesStruct,
     no_read comm: *mut [i8; 16],
+    plain exit_code: i32,
+    wrapper cred: Cred,
+});
+
+gen_accessors!(cred => {
+    plain uid: kuid_t,
+    plain gid: kgid_t,
 });
 
 gen_accessors!(mm_struct => {
@@ -348,6 +355,14 @@ gen_accessors!(fdtable => {
     plain open_fds: *mut u64,
 });
 
+gen_accessors!(k_sigaction => {
+    no_read_wrapped sa: Sigaction,
+});
+
+gen_accessors!(sigaction => {
+    plain sa_handler: *mut core::ffi::c_void,
+});
+
 gen_accessors!(art_heap => {
     no_read target_footprint: *mut u64,
     no_read num_bytes_allocated: *mut u64,
@@ -17,9 +17,12 @@ use aya_log::EbpfLogger;
 use aya_obj::generated::{bpf_attr, bpf_cmd::BPF_PROG_TEST_RUN};
 use bytemuck::{checked, CheckedBitPattern};
 use ebpf_types::{
-    Blocking, Event, EventKind, FileDescriptorChange, FileDescriptorOp, Signal, Write, WriteSource,
+    Blocking, Event, EventKind, FileDescriptorChange, FileDescriptorOp, Signal, SignalSource,
+    Write, WriteSource,
+};
+use libc::{
+    syscall, SYS_bpf, SYS_fsync, SYS_futex, SYS_gettid, SYS_kill, SYS_open, SYS_tgkill, SYS_write,
 };
-use libc::{syscall, SYS_bpf, SYS_futex, SYS_gettid, SYS_kill, SYS_open, SYS_write};
 
 const PROG_BYTES: &[u8] = aya::include_bytes_aligned!(concat!(env!("OUT_DIR"), "/ebpf.o"));
 
@@ -155,9 +158,45 @@ async fn test_blocking() {
     let _ = prog_run(exit_fd, &[&raw const pt_regs_exit as u64, ret]).unwrap();
 
     let event = get_event::<Blocking>(&mut ebpf);
-    assert!(matches!(event.kind, EventKind::Blocking));
+    assert_eq!(event.header.kind, EventKind::Blocking as u64);
     assert_eq!(event.data.syscall_id, syscall);
     assert!(event.data.duration > 0);
+    assert_eq!(event.data.file_path[0], 0);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_blocking_file_path() {
+    let mut ebpf = setup();
+    let enter_fd = load_tracepoint(&mut ebpf, "sys_enter_blocking");
+    let exit_fd = load_tracepoint(&mut ebpf, "sys_exit_blocking");
+
+    let syscall = SYS_fsync as u64;
+    let file_descriptor = enter_fd as u64;
+    let ret = 0;
+
+    let pt_regs_enter = PtRegs {
+        arg1: file_descriptor,
+        ..Default::default()
+    }
+    .build();
+    let pt_regs_exit = PtRegs {
+        ret,
+        syscall,
+        ..Default::default()
+    }
+    .build();
+
+    let _ = prog_run(enter_fd, &[&raw const pt_regs_enter as u64, syscall]).unwrap();
+    let _ = prog_run(exit_fd, &[&raw const pt_regs_exit as u64, ret]).unwrap();
+
+    let event = get_event::<Blocking>(&mut ebpf);
+    assert_eq!(event.data.syscall_id, syscall);
+    assert_eq!(event.data.first_arg, file_descriptor);
+    let file_path = CStr::from_bytes_until_nul(&event.data.file_path[..])
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert_eq!(file_path, "bpf-prog");
 }
 
 #[test_log::test(tokio::test)]
@@ -187,9 +226,48 @@ async fn test_signal() {
     let _ = prog_run(exit_fd, &[&raw const pt_regs_exit as u64, ret]).unwrap();
 
     let event = get_event::<Signal>(&mut ebpf);
-    assert!(matches!(event.kind, EventKind::Signal));
+    assert_eq!(event.header.kind, EventKind::Signal as u64);
+    assert_eq!(event.data.target_pid, target_pid);
+    assert_eq!(event.data.target_tid, 0);
+    assert_eq!(event.data.signal, signal);
+    assert_eq!(event.data.sender_pid, id());
+    assert!(matches!(event.data.source, SignalSource::Kill));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_signal_tgkill() {
+    let mut ebpf = setup();
+    let enter_fd = load_tracepoint(&mut ebpf, "sys_enter_signal");
+    let exit_fd = load_tracepoint(&mut ebpf, "sys_exit_signal");
+
+    let syscall = SYS_tgkill as u64;
+    let ret = -3i64 as u64;
+    let target_pid = 123;
+    let target_tid = 124;
+    let signal = 6;
+
+    let pt_regs_enter = PtRegs {
+        arg1: target_pid as u64,
+        arg2: target_tid as u64,
+        arg3: signal as u64,
+        ..Default::default()
+    }
+    .build();
+    let pt_regs_exit = PtRegs {
+        ret,
+        syscall,
+        ..Default::default()
+    };
+
+    let _ = prog_run(enter_fd, &[&raw const pt_regs_enter as u64, syscall]).unwrap();
+    let _ = prog_run(exit_fd, &[&raw const pt_regs_exit as u64, ret]).unwrap();
+
+    let event = get_event::<Signal>(&mut ebpf);
     assert_eq!(event.data.target_pid, target_pid);
+    assert_eq!(event.data.target_tid, target_tid);
     assert_eq!(event.data.signal, signal);
+    assert_eq!(event.data.result, -3);
+    assert!(matches!(event.data.source, SignalSource::Tgkill));
 }
 
 #[test_log::test(tokio::test)]
@@ -217,7 +295,7 @@ async fn test_fdtracking() {
     let _ = prog_run(exit_fd, &[&raw const pt_regs_exit as u64, ret]).unwrap();
 
     let event = get_event::<FileDescriptorChange>(&mut ebpf);
-    assert!(matches!(event.kind, EventKind::FileDescriptorChange));
+    assert_eq!(event.header.kind, EventKind::FileDescriptorChange as u64);
     assert!(matches!(event.data.operation, FileDescriptorOp::Open));
 
     let open_fds = read_dir("/proc/self/fd").unwrap().count() as u64 - 1;
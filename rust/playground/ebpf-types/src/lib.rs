@@ -15,8 +15,14 @@ pub struct TaskContext {
     pub tid: u32,
     /// Parent PID in userspace
     pub ppid: u32,
-    /// comm
+    /// comm of the thread, e.g. `RenderThread`
     pub comm: [u8; 16],
+    /// comm of the thread group leader, this is the process name
+    pub process_comm: [u8; 16],
+    /// Real uid of the task, on Android one per app
+    pub uid: u32,
+    /// Real gid of the task
+    pub gid: u32,
 }
 
 #[derive(Debug, Clone, Copy, AnyBitPattern)]
@@ -46,17 +52,55 @@ unsafe impl aya::Pod for FilterConfig {}
 #[cfg(feature = "user")]
 unsafe impl aya::Pod for Equality {}
 
+#[cfg(feature = "user")]
+unsafe impl aya::Pod for BlockingThresholdKey {}
+
+#[cfg(feature = "user")]
+unsafe impl aya::Pod for RateLimit {}
+
+#[cfg(feature = "user")]
+unsafe impl aya::Pod for SuppressedEvents {}
+
+#[cfg(feature = "user")]
+unsafe impl aya::Pod for RingBufRoute {}
+
 #[derive(Debug, Clone, Copy, AnyBitPattern)]
 #[repr(C)]
 pub struct EventContext {
     pub task: TaskContext,
+    /// `CLOCK_BOOTTIME` in nanoseconds
     pub timestamp: u64,
 }
 
+/// Has to be increased with every change of the layout of the records in the ring buffers
+pub const RECORD_FORMAT_VERSION: u32 = 1;
+
+/// Start of every record in the ring buffers, the header keeps its layout across versions
+/// so that userspace can recognize and skip records it does not understand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AnyBitPattern)]
+#[repr(C)]
+pub struct RecordHeader {
+    pub version: u32,
+    /// Size of the whole record including the header in bytes
+    pub length: u32,
+    /// An `EventKind`, but kinds unknown to userspace are valid here
+    pub kind: u64,
+}
+
+impl RecordHeader {
+    pub const fn new<T: EventData>() -> Self {
+        Self {
+            version: RECORD_FORMAT_VERSION,
+            length: size_of::<Event<T>>() as u32,
+            kind: T::EVENT_KIND as u64,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct Event<T: ?Sized> {
-    pub kind: EventKind,
+    pub header: RecordHeader,
     pub context: EventContext,
     pub data: T,
 }
@@ -64,7 +108,7 @@ pub struct Event<T: ?Sized> {
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct EventBits<T: CheckedBitPattern> {
-    kind: <EventKind as CheckedBitPattern>::Bits,
+    header: <RecordHeader as CheckedBitPattern>::Bits,
     context: <EventContext as CheckedBitPattern>::Bits,
     data: <T as CheckedBitPattern>::Bits,
 }
@@ -75,7 +119,7 @@ unsafe impl<T: CheckedBitPattern + 'static> CheckedBitPattern for Event<T> {
     type Bits = EventBits<T>;
 
     fn is_valid_bit_pattern(bits: &Self::Bits) -> bool {
-        EventKind::is_valid_bit_pattern(&bits.kind)
+        RecordHeader::is_valid_bit_pattern(&bits.header)
             && EventContext::is_valid_bit_pattern(&bits.context)
             && T::is_valid_bit_pattern(&bits.data)
     }
@@ -108,6 +152,31 @@ pub enum WriteSource {
 pub struct Blocking {
     pub syscall_id: u64,
     pub duration: u64,
+    /// First argument of the syscall, this is the file descriptor for most blocking syscalls
+    pub first_arg: u64,
+    /// Path of `first_arg` if the syscall takes a file descriptor, empty otherwise
+    pub file_path: [u8; 4096],
+}
+
+/// Key of a blocking threshold rule, wildcards are used if a rule
+/// applies to every process or every syscall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AnyBitPattern)]
+#[repr(C)]
+pub struct BlockingThresholdKey {
+    /// PID in userspace, `ANY_PID` for all processes
+    pub pid: u32,
+    /// Syscall number, `ANY_SYSCALL` for all syscalls
+    pub syscall_id: u32,
+}
+
+impl BlockingThresholdKey {
+    /// PID 0 is the idle task, it never shows up as a userspace PID
+    pub const ANY_PID: u32 = 0;
+    pub const ANY_SYSCALL: u32 = u32::MAX;
+
+    pub const fn new(pid: u32, syscall_id: u32) -> Self {
+        Self { pid, syscall_id }
+    }
 }
 
 #[derive(Debug, Clone, Copy, CheckedBitPattern)]
@@ -119,11 +188,58 @@ pub enum JniReferences {
     DeleteGlobalRef,
 }
 
-#[derive(Debug, Clone, Copy, AnyBitPattern)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern)]
 #[repr(C)]
 pub struct Signal {
+    /// Target process, for `PidfdSendSignal` this is the pidfd of the sender
+    /// and 0 for `Tkill` as only the thread is known
     pub target_pid: i32,
+    /// Target thread, 0 if the signal is directed at the whole process
+    pub target_tid: i32,
     pub signal: u32,
+    /// PID in userspace of the sender, 0 if sent by the kernel
+    pub sender_pid: u32,
+    /// Return value of the syscall or `TRACE_SIGNAL_*` result of `signal_generate`
+    pub result: i64,
+    /// Empty if unknown in the kernel
+    pub sender_comm: [u8; 16],
+    /// Empty if unknown in the kernel
+    pub target_comm: [u8; 16],
+    pub source: SignalSource,
+}
+
+/// Highest signal number, the realtime signals end at 64
+pub const SIGNAL_MAX: u32 = 64;
+
+/// Bit of a signal in a signal bitmask, bit `n - 1` corresponds to signal `n`.
+/// Signal 0 only checks for permissions and, like invalid numbers, has no bit.
+pub const fn signal_mask_bit(signal: u32) -> u64 {
+    if signal == 0 || signal > SIGNAL_MAX {
+        0
+    } else {
+        1 << (signal - 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, CheckedBitPattern)]
+#[repr(u8)]
+pub enum SignalSource {
+    /// Corresponds to `kill` syscall
+    Kill,
+    /// Corresponds to `tkill` syscall
+    Tkill,
+    /// Corresponds to `tgkill` syscall
+    Tgkill,
+    /// Corresponds to `rt_sigqueueinfo` syscall
+    RtSigqueueinfo,
+    /// Corresponds to `rt_tgsigqueueinfo` syscall
+    RtTgsigqueueinfo,
+    /// Corresponds to `pidfd_send_signal` syscall
+    PidfdSendSignal,
+    /// Corresponds to `signal_generate` tracepoint
+    Generate,
+    /// Corresponds to `signal_deliver` tracepoint
+    Deliver,
 }
 
 #[derive(Debug, Clone, Copy, AnyBitPattern)]
@@ -154,6 +270,52 @@ pub enum FileDescriptorOp {
     Close,
 }
 
+#[derive(Debug, Clone, Copy, CheckedBitPattern)]
+#[repr(C)]
+pub struct ProcessLifecycle {
+    /// PID in userspace of the new, executing or exiting process
+    pub pid: u32,
+    /// PID in userspace of the parent
+    pub parent_pid: u32,
+    /// Wait status as in `waitpid`, only set for `Exit`
+    pub exit_code: u32,
+    pub operation: ProcessOp,
+    /// Inherited from the parent for `Fork`, the new program for `Exec`,
+    /// zeroed for `Exit` if the process was not cached
+    pub process: ProcessContext,
+}
+
+#[derive(Debug, Clone, Copy, CheckedBitPattern)]
+#[repr(u8)]
+pub enum ProcessOp {
+    /// Corresponds to `sched_process_fork` tracepoint
+    Fork,
+    /// Corresponds to `sched_process_exec` tracepoint
+    Exec,
+    /// Corresponds to `sched_process_exit` tracepoint
+    Exit,
+}
+
+pub const CRASH_STACK_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, AnyBitPattern)]
+#[repr(C)]
+pub struct Crash {
+    /// One of `SIGSEGV`, `SIGABRT`, `SIGBUS` or `SIGILL`
+    pub signal: u32,
+    /// `si_code` of the siginfo, positive if raised by the kernel
+    pub si_code: i32,
+    /// `si_addr` of the siginfo, only set if raised by the kernel
+    pub fault_addr: u64,
+    /// Number of valid entries in `stack`, zero if the stack could not be captured
+    pub stack_len: u32,
+    /// User stack of the faulting thread, innermost frame first
+    pub stack: [u64; CRASH_STACK_DEPTH],
+    /// Command line of the crashing process, the process is usually gone
+    /// before userspace could read it from procfs
+    pub cmdline: [u8; 256],
+}
+
 #[derive(Debug, Clone, Copy, CheckedBitPattern)]
 #[repr(u64)]
 pub enum EventKind {
@@ -163,9 +325,24 @@ pub enum EventKind {
     GarbageCollect,
     FileDescriptorChange,
     JniReferences,
+    ProcessLifecycle,
+    Crash,
     MAX,
 }
 
+impl EventKind {
+    pub const ALL: [EventKind; EventKind::MAX as usize] = [
+        EventKind::Write,
+        EventKind::Blocking,
+        EventKind::Signal,
+        EventKind::GarbageCollect,
+        EventKind::FileDescriptorChange,
+        EventKind::JniReferences,
+        EventKind::ProcessLifecycle,
+        EventKind::Crash,
+    ];
+}
+
 pub trait EventData {
     const EVENT_KIND: EventKind;
 }
@@ -186,7 +363,9 @@ impl_event_data! {
     Signal,
     GarbageCollect,
     FileDescriptorChange,
-    JniReferences
+    JniReferences,
+    ProcessLifecycle,
+    Crash
 }
 
 #[derive(Debug, Clone, Copy, Default, AnyBitPattern)]
@@ -196,6 +375,11 @@ pub struct FilterConfig {
     pub comm_filter: Option<Filter>,
     pub exe_path_filter: Option<Filter>,
     pub cmdline_filter: Option<Filter>,
+    pub process_comm_filter: Option<Filter>,
+    pub uid_filter: Option<Filter>,
+    pub cgroup_filter: Option<Filter>,
+    /// None behaves like `Combination::Any`
+    pub combination: Option<Combination>,
 }
 
 #[derive(Debug, Clone, Copy, Default, CheckedBitPattern)]
@@ -219,6 +403,64 @@ pub enum MissingBehavior {
     NotMatch,
 }
 
+/// How the results of the filters of an event kind are combined
+#[derive(Debug, Clone, Copy, CheckedBitPattern, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Combination {
+    /// The first filter with an entry for the event decides,
+    /// without any entry the missing behaviors decide
+    #[default]
+    Any = 1,
+    /// Every specified filter has to match the event
+    All,
+}
+
+/// # Safety
+///
+/// Combination starts at 1 so it is invalid to have a value of 0
+/// E.g. None = 0, Some(Any) = 1, Some(All) = 2
+unsafe impl PodInOption for Combination {}
+unsafe impl ZeroableInOption for Combination {}
+
+/// Limits the events of a kind which are submitted to the ring buffer,
+/// the default submits every event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, AnyBitPattern)]
+#[repr(C)]
+pub struct RateLimit {
+    /// Only every n-th event is submitted, 0 and 1 submit every event
+    pub sample_every: u32,
+    /// Average number of events each process may submit per second, 0 for no limit
+    pub events_per_second: u32,
+    /// Number of events a process may submit at once, 0 for `events_per_second`
+    pub burst: u32,
+}
+
+/// Events of a kind which were not submitted because of its `RateLimit`
+#[derive(Debug, Clone, Copy, Default, AnyBitPattern)]
+#[repr(C)]
+pub struct SuppressedEvents {
+    /// Skipped by sampling
+    pub sampled: u64,
+    /// Rejected by the token bucket of their process
+    pub rate_limited: u64,
+}
+
+/// Ring buffer which the events of a kind are submitted to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CheckedBitPattern)]
+#[repr(u32)]
+pub enum RingBufRoute {
+    /// Shared by all kinds without another route
+    #[default]
+    Shared,
+    /// Only used by the kind itself
+    Dedicated,
+    /// Shared by low volume kinds, so they are never crowded out by high volume kinds
+    Priority,
+}
+
+/// Longest prefix of a string filter, this is the maximum key size of an LPM trie
+pub const MAX_PREFIX_FILTER_LEN: usize = 256;
+
 /// Each bit corresponds to an EventKind, e.g. 1 << EventKind::Write
 #[derive(Debug, Clone, Copy, AnyBitPattern)]
 #[repr(C)]
@@ -246,6 +488,10 @@ macro_rules! unpack_event {
             }
             EventKind::JniReferences => Box::new(*checked::from_bytes::<Event<JniReferences>>(&$rbe)),
             EventKind::Blocking => Box::new(*checked::from_bytes::<Event<Blocking>>(&$rbe)),
+            EventKind::ProcessLifecycle => {
+                Box::new(*checked::from_bytes::<Event<ProcessLifecycle>>(&$rbe))
+            }
+            EventKind::Crash => Box::new(*checked::from_bytes::<Event<Crash>>(&$rbe)),
             EventKind::MAX => unreachable!(),
         }
     }};
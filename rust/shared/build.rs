@@ -31,14 +31,19 @@ static UNIFFI_RECORDS: LazyLock<Vec<&str>> = LazyLock::new(|| {
             "Configuration",
             "WriteConfig",
             "BlockingConfig",
+            "BlockingThresholdRule",
             "JniReferencesConfig",
             "SignalConfig",
             "UprobeConfig",
             "GarbageCollectConfig",
             "FileDescriptorChangeConfig",
+            "ProcessConfig",
+            "CrashConfig",
             "StringFilter",
             "UInt32Filter",
+            "UidFilter",
             "Filter",
+            "RateLimit",
             
             // events.proto
             "Event",
@@ -52,6 +57,19 @@ static UNIFFI_RECORDS: LazyLock<Vec<&str>> = LazyLock::new(|| {
             "SignalEvent",
             "GarbageCollectEvent",
             "FileDescriptorChangeEvent",
+            "ProcessEvent",
+            "CrashEvent",
+            "SuppressedEvents",
+            "SuppressedEventCount",
+            "StreamOptions",
+            "StreamStatus",
+            "DumpFlightRecorderRequest",
+            "FlightRecording",
+            "EventQuery",
+            "TimeSeriesQuery",
+            "SeriesPoint",
+            "EventSeries",
+            "EventSeriesList",
             
             "Duration",
             "Timestamp",
@@ -69,10 +87,14 @@ static UNIFFI_ENUMS: LazyLock<Vec<&str>> = LazyLock::new(|| {
         vec![
             "Process.cmd",
             "MissingBehavior",
+            "FilterCombination",
             "Event.event_data",
             "LogEvent.log_event_data",
             "JniMethodName",
+            "SignalSource",
+            "ProcessOp",
             "FileDescriptorOp",
+            "BackpressurePolicy",
             ]
     } else {
         vec![]
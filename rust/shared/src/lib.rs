@@ -94,6 +94,8 @@ impl<'a> From<&'a LogEvent> for EventKind {
             LogEventData::FileDescriptorChange(_) => EventKind::FileDescriptorChange,
             LogEventData::Blocking(_) => EventKind::Blocking,
             LogEventData::Write(_) => EventKind::Write,
+            LogEventData::Process(_) => EventKind::Process,
+            LogEventData::Crash(_) => EventKind::Crash,
         }
     }
 }
\ No newline at end of file